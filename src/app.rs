@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::time::Instant;
 use egui_file::DialogType;
@@ -6,35 +7,592 @@ use egui_sfml::{
     SfEgui,
 };
 use egui_sfml::egui::Widget;
+use serde::{Serialize, Deserialize};
+use geo::{BooleanOps, Intersects};
 use serde_json::{from_str, to_string};
 use glu_sys as gl;
+use sfml::SfBox;
 
-use sfml::graphics::RenderTarget;
-use crate::line_alg::{LinePainter, LinePainterAlgorithm};
-use crate::polygon::{Polygon, PolygonObject, RawPolygonCoords};
-use crate::state_machine::{IdleState, State};
+use sfml::graphics::{RenderTarget, Shape, Transformable};
+use crate::line_alg::{self, LinePainter, LinePainterAlgorithm, LineStyle};
+use crate::polygon::{DropShadow, FillStyle, MirrorWinding, PolygonObject, RawPolygonCoords};
+use crate::snap::SnapIndicator;
+use crate::toast::Toasts;
+use crate::grid::Grid;
+use crate::units::Units;
+use crate::state_machine::{IdleState, ResumePolygonState, StampState, State};
+use crate::library::ShapeLibrary;
+use crate::shortcuts::{self, Action};
+use crate::obj_export;
+use crate::dxf;
+use crate::history::{Change, History};
+use crate::commands::{CommandRegistry, EditorCommand};
+use crate::config::Config;
 
 use super::sf;
 use super::polygon;
 use super::style;
+use super::my_math;
 
-#[derive(Debug)]
-#[derive(PartialEq)]
+// What the buttons of a polygon entry in the options window asked for
+enum EntryAction {
+    None,
+    Delete,
+    Duplicate,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DrawingMode {
     GPU,
     CPU,
 }
 
+/// Modifier keys state, read by the states.
+#[derive(Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+}
+
 pub struct AppContext<'a> {
     pub polygon_obj_factory: polygon::PolygonObjectFactory<'a>,
     pub polygon_objs: Vec<polygon::PolygonObject<'a>>,
+
+    // Shared by every snapping feature, hidden at the beginning of each frame
+    pub snap_indicator: SnapIndicator<'a>,
+
+    pub toasts: Toasts,
+
+    pub grid: Grid,
+
+    pub units: Units,
+
+    pub shadow: DropShadow,
+
+    pub modifiers: Modifiers,
+
+    // Show the pre-drag shape of the dragged polygons
+    pub show_drag_ghost: bool,
+
+    // Snap the points of a new polygon onto the edges of the existing ones, Alt disables it
+    pub edge_snap: bool,
+
+    // Snap placed and dragged points to the midpoints of the edges, Alt disables it
+    pub midpoint_snap: bool,
+
+    // After a drag, insert a vertex wherever an edge passes through a vertex of another polygon
+    pub insert_shared_vertices: bool,
+
+    // After a drag or a point removal, remove the vertices between two collinear edges
+    pub merge_collinear: bool,
+
+    // Where the vertex numbering of a mirrored polygon starts
+    pub mirror_winding: MirrorWinding,
+
+    // Passes of the Subdivide command
+    pub subdivide_iterations: u32,
+
+    // Vertex count the Simplify command reduces the polygon to
+    pub simplify_target: usize,
+
+    // Hide the point circles, vertices are identified by their index labels only
+    pub indices_only: bool,
+
+    // Short text shown next to the cursor, cleared at the beginning of each frame
+    pub hud: Option<String>,
+
+    // Corners of the selection rectangle, cleared at the beginning of each frame
+    pub marquee: Option<(sf::Vector2f, sf::Vector2f)>,
+
+    // World region visible outside of the docked egui panels, updated every frame
+    pub canvas: sf::FloatRect,
+
+    // World regions covered by the egui windows, floating ones included, updated every frame
+    pub panels: Vec<sf::FloatRect>,
+
+    pub history: History<'a>,
+
+    // Copies of the polygons from the last Ctrl + C
+    pub clipboard: Vec<polygon::PolygonObject<'a>>,
+}
+
+impl AppContext<'_> {
+    /// Creates an empty document with the canvas covering a window of the given size.
+    pub fn new(width: u32, height: u32) -> AppContext<'static> {
+        AppContext {
+            polygon_objs: Vec::new(),
+            polygon_obj_factory: polygon::PolygonObjectFactory::new(),
+            snap_indicator: SnapIndicator::new(),
+            toasts: Toasts::new(),
+            grid: Grid::new(),
+            units: Units::new(),
+            shadow: DropShadow::new(),
+            modifiers: Modifiers::default(),
+            show_drag_ghost: true,
+            edge_snap: false,
+            midpoint_snap: false,
+            insert_shared_vertices: false,
+            merge_collinear: false,
+            mirror_winding: MirrorWinding::KeepFirstVertex,
+            subdivide_iterations: 1,
+            simplify_target: 3,
+            indices_only: false,
+            hud: None,
+            marquee: None,
+            canvas: sf::FloatRect::new(0., 0., width as f32, height as f32),
+            panels: Vec::new(),
+            history: History::new(),
+            clipboard: Vec::new(),
+        }
+    }
+
+    /// Whether the world position is visible, neither outside of the canvas nor under a panel.
+    pub fn is_on_canvas(&self, pos: sf::Vector2f) -> bool {
+        let canvas = self.canvas;
+        pos.x >= canvas.left && pos.x <= canvas.left + canvas.width &&
+            pos.y >= canvas.top && pos.y <= canvas.top + canvas.height &&
+            !self.panels.iter().any(|panel| panel.contains(pos))
+    }
+
+    /// Index of the polygon with the given uid, None once it has been removed.
+    pub fn find_polygon(&self, uid: usize) -> Option<usize> {
+        self.polygon_objs.iter().position(|poly| poly.uid() == uid)
+    }
+
+    /// Records the whole document before an edit that adds, removes or changes several polygons.
+    pub fn checkpoint(&mut self) {
+        self.history.push(Change::Document(self.polygon_objs.clone()));
+    }
+
+    /// Returns the edge midpoint within style::POINT_DETECTION_RADIUS closest to pos and shows
+    /// the midpoint marker. The edges with a selected end are moving, they are skipped.
+    pub fn snap_to_midpoints(&mut self, pos: sf::Vector2f) -> Option<sf::Vector2f> {
+        if !self.midpoint_snap || self.modifiers.alt {
+            return None;
+        }
+
+        let mut result: Option<(sf::Vector2f, sf::Vector2f, sf::Vector2f)> = None;
+        let mut min_dist = style::POINT_DETECTION_RADIUS;
+        for poly in self.polygon_objs.iter() {
+            for i in 0..poly.polygon().points_count() as isize {
+                if poly.is_point_selected(i) || poly.is_point_selected(i + 1) {
+                    continue;
+                }
+                let p0 = poly.polygon().get_point_pos(i);
+                let p1 = poly.polygon().get_point_pos(i + 1);
+                let mid = (p0 + p1) / 2.;
+                let dist = my_math::distance(&pos, &mid);
+                if dist <= min_dist {
+                    min_dist = dist;
+                    result = Some((mid, p0, p1));
+                }
+            }
+        }
+
+        let (mid, p0, p1) = result?;
+        self.snap_indicator.show_midpoint(mid);
+        self.snap_indicator.show_edge(p0, p1);
+        Some(mid)
+    }
+
+    /// Moves pos onto the nearest edge of the existing polygons within
+    /// style::LINE_DETECTION_DISTANCE and highlights that edge, so adjacent polygons can share
+    /// a boundary. Edge midpoints nearby take precedence.
+    pub fn snap_to_polygon_edges(&mut self, pos: sf::Vector2f) -> sf::Vector2f {
+        if let Some(mid) = self.snap_to_midpoints(pos) {
+            return mid;
+        }
+        if !self.edge_snap || self.modifiers.alt {
+            return pos;
+        }
+
+        let mut result: Option<(sf::Vector2f, sf::Vector2f, sf::Vector2f)> = None;
+        let mut min_dist = f32::INFINITY;
+        for poly in self.polygon_objs.iter() {
+            if let Some((id, t)) = poly.edge_at(pos) {
+                let p0 = poly.polygon().get_point_pos(id as isize);
+                let p1 = poly.polygon().get_point_pos(id as isize + 1);
+                let target = poly.polygon().edge_point(id as isize, t);
+                let dist = my_math::distance(&pos, &target);
+                if dist < min_dist {
+                    min_dist = dist;
+                    result = Some((target, p0, p1));
+                }
+            }
+        }
+
+        match result {
+            Some((target, p0, p1)) => {
+                self.snap_indicator.show(target);
+                self.snap_indicator.show_edge(p0, p1);
+                target
+            }
+            None => pos,
+        }
+    }
+
+    /// Keeps the polygons with selected points conformal with the others: every edge passing
+    /// through a vertex of another polygon gets a coincident vertex. Returns the number of
+    /// inserted vertices.
+    pub fn insert_shared_vertices(&mut self) -> usize {
+        let positions: Vec<Vec<sf::Vector2f>> = self.polygon_objs
+            .iter()
+            .map(|poly| (0..poly.polygon().points_count() as isize).map(|i| poly.polygon().get_point_pos(i)).collect())
+            .collect();
+        let edited: Vec<bool> = self.polygon_objs.iter().map(|poly| poly.selected_points_count() > 0).collect();
+
+        let mut inserted = 0;
+        for (id, poly) in self.polygon_objs.iter_mut().enumerate() {
+            for other in 0..positions.len() {
+                if other != id && (edited[id] || edited[other]) {
+                    inserted += poly.insert_shared_vertices(&positions[other]);
+                }
+            }
+        }
+        inserted
+    }
+
+    /// Removes the redundant vertices of the given polygon if merging collinear edges is on.
+    /// Vertices shared with the other polygons are kept when shared vertices are inserted.
+    pub fn merge_collinear_edges(&mut self, id: usize) -> usize {
+        if !self.merge_collinear {
+            return 0;
+        }
+
+        let mut keep: Vec<sf::Vector2f> = Vec::new();
+        if self.insert_shared_vertices {
+            for (other, poly) in self.polygon_objs.iter().enumerate() {
+                if other != id {
+                    keep.extend((0..poly.polygon().points_count() as isize).map(|i| poly.polygon().get_point_pos(i)));
+                }
+            }
+        }
+        self.polygon_objs[id].merge_collinear_edges(style::COLLINEAR_TOLERANCE, &keep)
+    }
+
+    /// Records the edits made in the egui panels of the polygons.
+    pub fn collect_pending_changes(&mut self) {
+        for (id, poly) in self.polygon_objs.iter_mut().enumerate() {
+            if let Some(polygon) = poly.take_pending_change() {
+                self.history.push(Change::Polygon(id, polygon));
+            }
+        }
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let done = self.history.undo(&mut self.polygon_objs);
+        self.deselect_all();
+        done
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let done = self.history.redo(&mut self.polygon_objs);
+        self.deselect_all();
+        done
+    }
+
+    fn deselect_all(&mut self) {
+        for poly in self.polygon_objs.iter_mut() {
+            poly.deselect_all_points();
+        }
+    }
+
+    /// Selects all points of every polygon grouped with a polygon that has a selection.
+    pub fn expand_selection_to_groups(&mut self) {
+        let groups: HashSet<usize> = self.polygon_objs
+            .iter()
+            .filter(|poly| poly.selected_points_count() > 0)
+            .filter_map(|poly| poly.group_id())
+            .collect();
+
+        for poly in self.polygon_objs.iter_mut() {
+            if poly.group_id().is_some_and(|id| groups.contains(&id)) {
+                poly.select_all_points();
+            }
+        }
+    }
+
+    /// Puts every polygon with a selection into a new group. Returns the new group id.
+    pub fn group_selected(&mut self) -> usize {
+        let group_id = self.polygon_objs
+            .iter()
+            .filter_map(|poly| poly.group_id())
+            .max()
+            .map_or(0, |id| id + 1);
+
+        for poly in self.polygon_objs.iter_mut() {
+            if poly.selected_points_count() > 0 {
+                poly.set_group_id(Some(group_id));
+            }
+        }
+        group_id
+    }
+
+    /// Dissolves the groups of the polygons with a selection.
+    pub fn ungroup_selected(&mut self) {
+        let groups: HashSet<usize> = self.polygon_objs
+            .iter()
+            .filter(|poly| poly.selected_points_count() > 0)
+            .filter_map(|poly| poly.group_id())
+            .collect();
+
+        for poly in self.polygon_objs.iter_mut() {
+            if poly.group_id().is_some_and(|id| groups.contains(&id)) {
+                poly.set_group_id(None);
+            }
+        }
+    }
+
+    /// Pairs (inner, outer) of the polygons lying entirely inside another polygon. Nested
+    /// polygons are reported for every polygon around them.
+    pub fn find_containment(&self) -> Vec<(usize, usize)> {
+        let bounds: Vec<sf::FloatRect> = self.polygon_objs.iter().map(|poly| poly.polygon().bounds()).collect();
+        let inside = |inner: &sf::FloatRect, outer: &sf::FloatRect| {
+            inner.left >= outer.left && inner.top >= outer.top &&
+                inner.left + inner.width <= outer.left + outer.width &&
+                inner.top + inner.height <= outer.top + outer.height
+        };
+
+        let mut result = Vec::new();
+        for (i, inner) in self.polygon_objs.iter().enumerate() {
+            for (j, outer) in self.polygon_objs.iter().enumerate() {
+                // The bounding boxes rule out most of the pairs before the vertex tests
+                if i != j && inside(&bounds[i], &bounds[j]) && outer.polygon().contains_polygon(inner.polygon()) {
+                    result.push((i, j));
+                }
+            }
+        }
+        result
+    }
+
+    /// Groups of polygons with the same outline (up to the start point and the winding),
+    /// each group is sorted by the polygon index.
+    pub fn find_duplicates(&self) -> Vec<Vec<usize>> {
+        let outlines: Vec<Vec<sf::Vector2f>> = self.polygon_objs
+            .iter()
+            .map(|poly| {
+                let polygon = poly.polygon();
+                let points: Vec<sf::Vector2f> = (0..polygon.points_count() as isize)
+                    .map(|id| polygon.get_point_pos(id))
+                    .collect();
+                my_math::canonical_outline(&points, style::DUPLICATE_EPSILON)
+            })
+            .collect();
+
+        let same = |a: &Vec<sf::Vector2f>, b: &Vec<sf::Vector2f>| {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(p, q)| my_math::distance(p, q) <= style::DUPLICATE_EPSILON)
+        };
+
+        let mut visited = vec![false; outlines.len()];
+        let mut result = Vec::new();
+        for i in 0..outlines.len() {
+            if visited[i] {
+                continue;
+            }
+            let mut group = vec![i];
+            for j in (i + 1)..outlines.len() {
+                if !visited[j] && same(&outlines[i], &outlines[j]) {
+                    visited[j] = true;
+                    group.push(j);
+                }
+            }
+            if group.len() > 1 {
+                result.push(group);
+            }
+        }
+        result
+    }
+
+    /// Replaces every group of overlapping (or edge sharing) polygons with their union, the
+    /// polygons overlapping nothing are left untouched. A group touching only at the corners
+    /// doesn't merge and is left untouched too. The editor has no holes, so the holes
+    /// of the results are dropped. Returns the number of the merged polygons and of the
+    /// dropped holes.
+    pub fn union_all(&mut self) -> (usize, usize) {
+        // Boolean operations need valid input, the self-crossing polygons don't take part
+        let shapes: Vec<Option<geo::Polygon<f64>>> = self.polygon_objs
+            .iter()
+            .map(|poly| {
+                let polygon = poly.polygon();
+                (polygon.is_proper() && !polygon.is_self_crossing()).then(|| polygon.to_geo())
+            })
+            .collect();
+
+        // Connected components of the overlap graph
+        let mut component: Vec<usize> = (0..shapes.len()).collect();
+        fn root(component: &mut [usize], mut i: usize) -> usize {
+            while component[i] != i {
+                component[i] = component[component[i]];
+                i = component[i];
+            }
+            i
+        }
+        for i in 0..shapes.len() {
+            for j in (i + 1)..shapes.len() {
+                if let (Some(a), Some(b)) = (&shapes[i], &shapes[j]) {
+                    if a.intersects(b) {
+                        let (ri, rj) = (root(&mut component, i), root(&mut component, j));
+                        component[rj] = ri;
+                    }
+                }
+            }
+        }
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for i in 0..shapes.len() {
+            let r = root(&mut component, i);
+            if r == i && shapes[i].is_some() {
+                groups.push((i..shapes.len()).filter(|&j| root(&mut component, j) == i).collect());
+            }
+        }
+        groups.retain(|group| group.len() > 1);
+
+        let (mut merged, mut holes) = (0, 0);
+        let mut results = Vec::new();
+        let mut to_remove: Vec<usize> = Vec::new();
+        for group in groups.iter() {
+            let mut union = geo::MultiPolygon::new(vec![shapes[group[0]].clone().unwrap()]);
+            for &id in group[1..].iter() {
+                union = union.union(&geo::MultiPolygon::new(vec![shapes[id].clone().unwrap()]));
+            }
+            // intersects is true for the polygons touching at a vertex only
+            if union.0.len() >= group.len() {
+                continue;
+            }
+            for shape in union.0.iter() {
+                holes += shape.interiors().len();
+                let mut points: Vec<sf::Vector2f> = shape.exterior()
+                    .coords()
+                    .map(|c| sf::Vector2f::new(c.x as f32, c.y as f32))
+                    .collect();
+                // geo repeats the first coordinate at the end
+                points.pop();
+                if points.len() < 3 {
+                    continue;
+                }
+                let mut poly = self.polygon_obj_factory.build_from_raw(RawPolygonCoords::from_sf_points(points));
+                poly.assert_ccw();
+                results.push(poly);
+            }
+            merged += group.len();
+            to_remove.extend(group.iter());
+        }
+
+        to_remove.sort();
+        for id in to_remove.iter().rev() {
+            self.polygon_objs.remove(*id);
+        }
+        self.polygon_objs.extend(results);
+        (merged, holes)
+    }
+
+    /// Copies the polygons with selected points into the clipboard and returns their count.
+    /// The clipboard is left as it was when nothing is selected.
+    pub fn copy_selection(&mut self) -> usize {
+        let copied: Vec<polygon::PolygonObject> = self.polygon_objs
+            .iter()
+            .filter(|poly| poly.selected_points_count() > 0)
+            .cloned()
+            .collect();
+        let count = copied.len();
+        if count > 0 {
+            self.clipboard = copied;
+        }
+        count
+    }
+
+    /// Adds the clipboard polygons shifted by style::PASTE_OFFSET, deselected and with new
+    /// names. The clipboard moves along, so pasting again doesn't stack the copies.
+    pub fn paste(&mut self) -> usize {
+        if self.clipboard.is_empty() {
+            return 0;
+        }
+        self.checkpoint();
+        let offset = sf::Vector2f::new(style::PASTE_OFFSET, style::PASTE_OFFSET);
+        for poly in self.clipboard.iter_mut() {
+            let mut pasted = poly.duplicate(offset);
+            pasted.set_name(self.polygon_obj_factory.next_name());
+            self.polygon_objs.push(pasted);
+            poly.translate(offset);
+        }
+        self.clipboard.len()
+    }
+
+    /// Adds a copy of the polygon shifted by style::PASTE_OFFSET, with a new name.
+    pub fn duplicate_polygon(&mut self, id: usize) {
+        let offset = sf::Vector2f::new(style::PASTE_OFFSET, style::PASTE_OFFSET);
+        let mut copy = self.polygon_objs[id].duplicate(offset);
+        copy.set_name(self.polygon_obj_factory.next_name());
+        self.polygon_objs.push(copy);
+    }
+
+    /// Selects every polygon with a duplicate and deselects the rest. Returns the duplicate
+    /// groups, as find_duplicates.
+    pub fn select_duplicates(&mut self) -> Vec<Vec<usize>> {
+        let duplicates = self.find_duplicates();
+        self.deselect_all();
+        for id in duplicates.iter().flatten() {
+            self.polygon_objs[*id].select_all_points();
+        }
+        duplicates
+    }
+
+    /// Keeps the first polygon of every duplicate group. Returns the number of removed polygons.
+    pub fn remove_duplicates(&mut self) -> usize {
+        let mut to_remove: Vec<usize> = self.find_duplicates()
+            .iter()
+            .flat_map(|group| group[1..].to_vec())
+            .collect();
+        to_remove.sort();
+        for id in to_remove.iter().rev() {
+            self.polygon_objs.remove(*id);
+        }
+        to_remove.len()
+    }
+
+    /// Shared pivot of the group transforms: the mean of the members' centers.
+    pub fn group_pivot(&self, group_id: usize) -> Option<sf::Vector2f> {
+        let centers: Vec<sf::Vector2f> = self.polygon_objs
+            .iter()
+            .filter(|poly| poly.group_id() == Some(group_id))
+            .map(|poly| poly.polygon().find_center())
+            .collect();
+
+        if centers.is_empty() {
+            return None;
+        }
+        let sum = centers.iter().fold(sf::Vector2f::new(0., 0.), |acc, c| acc + *c);
+        Some(sum / centers.len() as f32)
+    }
 }
 
 pub struct Application<'a> {
     window: sf::RenderWindow,
     cpu_drawing_image: sf::Image,
+    // Uploaded copy of cpu_drawing_image, reused between frames
+    cpu_texture: SfBox<sf::Texture>,
+    // Filled with the background color, copied over the framebuffer to clear it
+    cpu_background: Option<sf::Image>,
+    // The scene only changes in reaction to the events, without them the last frame is reused
+    cpu_dirty: bool,
+    // CPU supersampling factor (1 means no supersampling) and its render target
+    supersampling: u32,
+    supersampled_image: Option<sf::Image>,
+    // Algorithm used for the right half of the framebuffer in the comparison mode
+    comparison_alg: Option<LinePainterAlgorithm>,
+    // Pixels set by the line algorithms in the last CPU frame: (polygon name, algorithm, count)
+    pixel_stats: Vec<(String, LinePainterAlgorithm, usize)>,
     ui_scale: f32,
 
+    // Polygons live in the world space seen through the camera, egui stays in the screen space
+    camera: SfBox<sf::View>,
+    // World units per pixel
+    zoom: f32,
+    // Last cursor position of the middle mouse drag
+    panning: Option<sf::Vector2i>,
+    // Cursor position of the last mouse event, polling the window could lag behind the events
+    mouse_pixel: sf::Vector2i,
+
     // Option is required, since we are temporary taking ownership
     // of the State, each time the transition function is called.
     // In this application curr_state is always Some.
@@ -46,55 +604,122 @@ pub struct Application<'a> {
 
     // Egui
     egui_rects: Vec<egui::Rect>,
+    // A text field has the focus, the keys don't trigger the shortcuts then
+    egui_wants_keyboard: bool,
+    // Uid and last measured height of every polygon entry in the Options list, the entries
+    // scrolled out of view are replaced by the space of that height
+    entry_heights: Vec<(usize, f32)>,
     opened_file: Option<std::path::PathBuf>,
     file_dialog: Option<egui_file::FileDialog>,
+    obj_export_dialog: Option<egui_file::FileDialog>,
+    dxf_export_dialog: Option<egui_file::FileDialog>,
+    dxf_import_dialog: Option<egui_file::FileDialog>,
+    export_normalized: bool,
+    export_stretched: bool,
+    shape_library: ShapeLibrary,
+    commands: CommandRegistry,
+    show_shortcuts: bool,
+    show_loupe: bool,
+    show_normals: bool,
+    show_coords_dialog: bool,
+    coords_input: String,
+    coords_error: Option<String>,
+    // Uids of the polygons whose distance is shown
+    measured_pair: Option<(usize, usize)>,
+    // Real length of the distance between the two selected points, used to calibrate the units
+    calibration_length: f32,
+    // Connect the polygons lying inside another polygon with their outer polygons
+    show_containment: bool,
+    // Highlight the polygons containing the cursor, a demo of Polygon::contains_point
+    test_point: bool,
 
     // Input
     a_pressed: bool,
     ctrl_pressed: bool,
     left_mouse_pressed: bool,
+    // Time and cursor position of the last left click, used to detect double clicks
+    last_left_click: Option<(Instant, sf::Vector2i)>,
 }
 
 impl Application<'_> {
-    pub fn new() -> Application<'static> {
+    pub fn new(config: Config) -> Application<'static> {
         let mut settings = sf::ContextSettings::default();
         settings.antialiasing_level = 8;
 
         let mut window = sf::RenderWindow::new(
-            (style::WIN_SIZE_X, style::WIN_SIZE_Y),
+            (config.window_width, config.window_height),
             "Polygon editor",
-            sf::Style::CLOSE,
+            sf::Style::DEFAULT,
             &settings,
         );
         window.set_vertical_sync_enabled(true);
+        let mouse_pixel = window.mouse_position();
+        let (width, height) = (config.window_width, config.window_height);
+        let mut line_painter = LinePainter::new(style::LINES_COLOR, 1.0);
+        line_painter.set_alg(config.line_algorithm);
 
         Application {
             window,
-            ui_scale: 0.8,
-            cpu_drawing_image: sf::Image::new(style::WIN_SIZE_X, style::WIN_SIZE_Y),
+            ui_scale: config.ui_scale,
+            cpu_drawing_image: sf::Image::new(width, height),
+            cpu_texture: sf::Texture::new().expect("Couldn't create the framebuffer texture"),
+            cpu_background: None,
+            cpu_dirty: true,
+            supersampling: 1,
+            supersampled_image: None,
+            comparison_alg: None,
+            pixel_stats: Vec::new(),
+            camera: sf::View::from_rect(sf::FloatRect::new(0., 0., width as f32, height as f32)),
+            zoom: 1.0,
+            panning: None,
+            mouse_pixel,
             curr_state: Some(Box::new(IdleState)),
-            app_ctx: AppContext {
-                polygon_objs: Vec::new(),
-                polygon_obj_factory: polygon::PolygonObjectFactory::new(),
-            },
-            drawing_mode: DrawingMode::GPU,
+            app_ctx: AppContext::new(width, height),
+            drawing_mode: config.drawing_mode,
             egui_rects: Vec::new(),
+            egui_wants_keyboard: false,
+            entry_heights: Vec::new(),
             a_pressed: false,
             ctrl_pressed: false,
             left_mouse_pressed: false,
+            last_left_click: None,
             opened_file: None,
             file_dialog: None,
-            line_painter: LinePainter::new(style::LINES_COLOR, 1.0),
-            gpu_antialiasing: false,
+            obj_export_dialog: None,
+            dxf_export_dialog: None,
+            dxf_import_dialog: None,
+            export_normalized: false,
+            export_stretched: false,
+            shape_library: ShapeLibrary::load(),
+            commands: CommandRegistry::new(),
+            show_shortcuts: false,
+            show_loupe: false,
+            show_normals: false,
+            show_coords_dialog: false,
+            coords_input: String::new(),
+            coords_error: None,
+            measured_pair: None,
+            calibration_length: 1.,
+            show_containment: false,
+            test_point: false,
+            line_painter,
+            gpu_antialiasing: config.antialiasing,
         }
     }
 
+    /// Adds a command to the "Commands" section of the options window.
+    pub fn register_command(&mut self, command: Box<dyn EditorCommand>) {
+        self.commands.register(command);
+    }
+
     pub fn run(&mut self) {
         let mut sfegui = SfEgui::new(&self.window);
         let mut clock = Instant::now();
 
         while self.window.is_open() {
             while let Some(ev) = self.window.poll_event() {
+                self.cpu_dirty = true;
+
                 // Feed egui with the input detected by the sfml
                 sfegui.add_event(&ev);
 
@@ -103,13 +728,17 @@ impl Application<'_> {
                     self.window.close()
                 }
 
+                // Keep the view and the framebuffer in sync with the window
+                if let sf::Event::Resized { width, height } = ev {
+                    self.on_resize(width, height);
+                }
+
                 // If mouse has been clicked do not react when it's inside of the egui window bounds
                 match ev {
-                    sf::Event::MouseButtonPressed { button: _, x, y } => {
-                        for rect_id in 0..self.egui_rects.len() {
-                            if !self.egui_rects[rect_id].contains(egui::Pos2::new(x as f32, y as f32)) {
-                                self.handle_input(&ev);
-                            }
+                    sf::Event::MouseButtonPressed { x, y, .. } | sf::Event::MouseWheelScrolled { x, y, .. } => {
+                        let pos = egui::Pos2::new(x as f32, y as f32);
+                        if !self.egui_rects.iter().any(|rect| rect.contains(pos)) {
+                            self.handle_input(&ev);
                         }
                     }
                     _ => self.handle_input(&ev),
@@ -125,6 +754,8 @@ impl Application<'_> {
                 .do_frame(|ctx| {
                     self.set_egui_scale(&ctx, self.ui_scale);
                     self.render_egui(&ctx);
+                    self.app_ctx.collect_pending_changes();
+                    self.egui_wants_keyboard = ctx.wants_keyboard_input();
                 })
                 .unwrap();
 
@@ -134,6 +765,99 @@ impl Application<'_> {
             sfegui.draw(&mut self.window, None);
             self.window.display();
         }
+        self.app_ctx.polygon_obj_factory.save_default_style();
+    }
+
+    fn on_resize(&mut self, width: u32, height: u32) {
+        // Keep the center and the zoom, show more of the world instead of stretching it
+        self.camera.set_size(sf::Vector2f::new(width as f32, height as f32) * self.zoom);
+        self.sync_framebuffer_size();
+    }
+
+    fn screen_view(&self) -> SfBox<sf::View> {
+        let size = self.window.size();
+        sf::View::from_rect(sf::FloatRect::new(0., 0., size.x as f32, size.y as f32))
+    }
+
+    fn to_world(&self, pixel: sf::Vector2i) -> sf::Vector2f {
+        self.window.map_pixel_to_coords(pixel, &self.camera)
+    }
+
+    // Remembers the click, the click after a double click starts a new one
+    fn is_double_click(&mut self, pixel: sf::Vector2i) -> bool {
+        let now = Instant::now();
+        let double = match self.last_left_click {
+            Some((time, prev)) => {
+                now.duration_since(time).as_secs_f32() <= style::DOUBLE_CLICK_TIME &&
+                    (pixel.x - prev.x).abs() <= style::DOUBLE_CLICK_DISTANCE &&
+                    (pixel.y - prev.y).abs() <= style::DOUBLE_CLICK_DISTANCE
+            }
+            None => false,
+        };
+        self.last_left_click = if double { None } else { Some((now, pixel)) };
+        double
+    }
+
+    fn mouse_world_pos(&self) -> sf::Vector2f {
+        self.to_world(self.mouse_pixel)
+    }
+
+    /// World region of the window not covered by the panels docked to its sides (the menu bar).
+    /// Floating windows cover the canvas only partially, they are left to AppContext::panels.
+    fn canvas_rect(&self) -> sf::FloatRect {
+        let size = self.window.size();
+        let (mut min, mut max) = (egui::Pos2::new(0., 0.), egui::Pos2::new(size.x as f32, size.y as f32));
+        for rect in self.egui_rects.iter() {
+            if rect.width() >= size.x as f32 - 1. {
+                if rect.min.y <= min.y {
+                    min.y = min.y.max(rect.max.y);
+                } else if rect.max.y >= max.y {
+                    max.y = max.y.min(rect.min.y);
+                }
+            } else if rect.height() >= size.y as f32 - 1. {
+                if rect.min.x <= min.x {
+                    min.x = min.x.max(rect.max.x);
+                } else if rect.max.x >= max.x {
+                    max.x = max.x.min(rect.min.x);
+                }
+            }
+        }
+
+        self.to_world_rect(egui::Rect::from_min_max(min, max))
+    }
+
+    fn to_world_rect(&self, rect: egui::Rect) -> sf::FloatRect {
+        let min = self.to_world(sf::Vector2i::new(rect.min.x as i32, rect.min.y as i32));
+        let max = self.to_world(sf::Vector2i::new(rect.max.x as i32, rect.max.y as i32));
+        sf::FloatRect::new(min.x, min.y, max.x - min.x, max.y - min.y)
+    }
+
+    /// Zooms the camera keeping the world point under the cursor in place.
+    fn zoom_at(&mut self, pixel: sf::Vector2i, delta: f32) {
+        let zoom = (self.zoom * style::ZOOM_STEP.powf(-delta)).clamp(style::MIN_ZOOM, style::MAX_ZOOM);
+        let before = self.to_world(pixel);
+        self.camera.zoom(zoom / self.zoom);
+        self.zoom = zoom;
+        let after = self.to_world(pixel);
+        self.camera.move_(before - after);
+    }
+
+    fn reset_camera(&mut self) {
+        self.camera = self.screen_view();
+        self.zoom = 1.0;
+    }
+
+    // Reallocates the CPU framebuffer if it doesn't match the window anymore.
+    // Returns false if there is nothing to draw into (e.g. minimized window).
+    fn sync_framebuffer_size(&mut self) -> bool {
+        let win_size = self.window.size();
+        if win_size.x == 0 || win_size.y == 0 {
+            return false;
+        }
+        if self.cpu_drawing_image.size() != win_size {
+            self.cpu_drawing_image = sf::Image::new(win_size.x, win_size.y);
+        }
+        true
     }
 
     fn set_egui_scale(&self, ctx: &egui::Context, scale: f32) {
@@ -171,15 +895,77 @@ impl Application<'_> {
 
         let raw_polygons: Vec<RawPolygonCoords> = self.app_ctx.polygon_objs
             .iter()
-            .map(|pobj| pobj.get_raw())
+            .map(|pobj| {
+                if self.export_normalized {
+                    pobj.get_raw().normalized(self.export_stretched)
+                } else {
+                    pobj.get_raw()
+                }
+            })
             .collect();
 
         let json_string = to_string(&raw_polygons).unwrap();
         if let Err(err) = fs::write(self.opened_file.clone().unwrap().as_path(), json_string) {
-            eprintln!("Error writing to file: {}", err);
+            log::error!("Error writing to file: {}", err);
+        } else {
+            log::info!("String successfully saved");
+            for pobj in self.app_ctx.polygon_objs.iter_mut() {
+                pobj.mark_baseline();
+            }
+        }
+    }
+
+    fn export_obj(&mut self, path: &std::path::Path) {
+        let obj = obj_export::polygons_to_obj(&self.app_ctx.polygon_objs);
+        if let Err(err) = fs::write(path, obj) {
+            log::error!("Error writing to file: {}", err);
+            self.app_ctx.toasts.push("OBJ export failed");
+        } else {
+            self.app_ctx.toasts.push(format!("Exported {}", path.display()));
+        }
+    }
+
+    fn export_dxf(&mut self, path: &std::path::Path) {
+        let dxf = dxf::polygons_to_dxf(&self.app_ctx.polygon_objs);
+        if let Err(err) = fs::write(path, dxf) {
+            log::error!("Error writing to file: {}", err);
+            self.app_ctx.toasts.push("DXF export failed");
         } else {
-            println!("String successfully saved");
+            self.app_ctx.toasts.push(format!("Exported {}", path.display()));
+        }
+    }
+
+    /// Adds the polygons of the DXF file to the document, self-crossing ones are skipped.
+    fn import_dxf(&mut self, path: &std::path::Path) {
+        let import = match fs::read_to_string(path).map_err(|err| err.to_string()).and_then(|text| dxf::parse_dxf(&text)) {
+            Ok(import) => import,
+            Err(err) => {
+                log::error!("Error importing {}: {}", path.display(), err);
+                self.app_ctx.toasts.push("DXF import failed");
+                return;
+            }
+        };
+
+        let warnings = import.warnings;
+        let mut polygons = Vec::new();
+        for raw in import.polygons {
+            let mut poly = self.app_ctx.polygon_obj_factory.build_from_raw(raw);
+            poly.assert_ccw();
+            polygons.push(poly);
+        }
+        for warning in warnings.iter() {
+            log::warn!("DXF import: {}", warning);
+        }
+
+        if !polygons.is_empty() {
+            self.app_ctx.checkpoint();
         }
+        let mut message = format!("Imported {} polygons", polygons.len());
+        if !warnings.is_empty() {
+            message += &format!(", {} entities skipped (see the log)", warnings.len());
+        }
+        self.app_ctx.polygon_objs.extend(polygons);
+        self.app_ctx.toasts.push(message);
     }
 
     fn load(&mut self) {
@@ -192,13 +978,16 @@ impl Application<'_> {
                 let raw_polygons: Vec<RawPolygonCoords> = from_str(&contents).unwrap();
                 self.app_ctx.polygon_objs.clear();
                 self.app_ctx.polygon_obj_factory.clear();
+                self.app_ctx.history.clear();
 
                 for raw in raw_polygons {
-                    self.app_ctx.polygon_objs.push(self.app_ctx.polygon_obj_factory.build_from_raw(raw));
+                    let mut poly = self.app_ctx.polygon_obj_factory.build_from_raw(raw);
+                    poly.mark_baseline();
+                    self.app_ctx.polygon_objs.push(poly);
                 }
             }
             Err(err) => {
-                eprintln!("Error reading from the file: {}", err);
+                log::error!("Error reading from the file: {}", err);
                 self.opened_file = None;
             }
         }
@@ -206,124 +995,948 @@ impl Application<'_> {
 
     fn handle_input(&mut self, ev: &sf::Event) {
         match ev {
-            sf::Event::KeyPressed { code: key, .. } => {
+            sf::Event::KeyPressed { code: key, ctrl, shift, .. } => {
                 match *key {
                     sfml::window::Key::LControl => self.ctrl_pressed = true,
                     sfml::window::Key::A => self.a_pressed = true,
+                    sfml::window::Key::LShift | sfml::window::Key::RShift => self.app_ctx.modifiers.shift = true,
+                    sfml::window::Key::LAlt | sfml::window::Key::RAlt => self.app_ctx.modifiers.alt = true,
                     _ => (),
                 };
+
+                if let Some(action) = shortcuts::find_key_action(*key, *ctrl, *shift, self.egui_wants_keyboard) {
+                    self.run_action(action);
+                }
             }
             sf::Event::KeyReleased { code: key, .. } => {
                 match *key {
                     sfml::window::Key::LControl => self.ctrl_pressed = false,
                     sfml::window::Key::A => self.a_pressed = false,
+                    sfml::window::Key::LShift | sfml::window::Key::RShift => self.app_ctx.modifiers.shift = false,
+                    sfml::window::Key::LAlt | sfml::window::Key::RAlt => self.app_ctx.modifiers.alt = false,
                     _ => (),
                 };
             }
             sf::Event::MouseButtonPressed { button: btn, x, y } => {
+                self.mouse_pixel = sf::Vector2i::new(*x, *y);
+                if *btn == sfml::window::mouse::Button::Middle {
+                    self.panning = Some(sf::Vector2i::new(*x, *y));
+                }
                 if *btn == sfml::window::mouse::Button::Left {
+                    let pos = self.to_world(sf::Vector2i::new(*x, *y));
                     self.left_mouse_pressed = true;
                     if self.ctrl_pressed {
                         if self.a_pressed {
                             // CTRL + A + LM
                             self.curr_state = Some(self.curr_state.take().unwrap().on_ctrl_a_left_mouse_clicked(
-                                sf::Vector2f::new(*x as f32, *y as f32),
+                                pos,
                                 &mut self.app_ctx,
                             ));
-                            println!("Ctrl + A + LM clicked");
+                            log::debug!("Ctrl + A + LM clicked");
                         } else {
                             // CTRL + LM
                             self.curr_state = Some(self.curr_state.take().unwrap().on_ctrl_left_mouse_clicked(
-                                sf::Vector2f::new(*x as f32, *y as f32),
+                                pos,
                                 &mut self.app_ctx,
                             ));
-                            println!("Ctrl + LM clicked");
+                            log::debug!("Ctrl + LM clicked");
                         }
+                    } else if self.is_double_click(sf::Vector2i::new(*x, *y)) {
+                        // LM double click
+                        self.curr_state = Some(self.curr_state.take().unwrap().on_left_mouse_double_clicked(
+                            pos,
+                            &mut self.app_ctx,
+                        ));
+                        log::debug!("LM double clicked");
                     } else {
                         // LM
                         self.curr_state = Some(self.curr_state.take().unwrap().on_left_mouse_clicked(
-                            sf::Vector2f::new(*x as f32, *y as f32),
+                            pos,
                             &mut self.app_ctx,
                         ));
-                        println!("LM clicked");
+                        log::debug!("LM clicked");
+                    }
+                }
+            }
+            sf::Event::MouseWheelScrolled { delta, x, y, .. } => {
+                if self.ctrl_pressed && self.drawing_mode == DrawingMode::CPU {
+                    // CTRL + scroll changes the line thickness
+                    let thickness = self.line_painter.thickness() + delta * style::SCROLL_THICKNESS_STEP;
+                    self.line_painter.set_thickness(thickness.clamp(1.0, 10.0));
+                } else if self.app_ctx.modifiers.shift {
+                    // Shift + scroll changes the offset of the selected polygon
+                    if let Some(poly) = self.app_ctx.polygon_objs.iter_mut().find(|p| p.selected_points_count() > 0) {
+                        poly.set_offset_size(poly.offset_size() + delta * style::SCROLL_OFFSET_STEP);
                     }
+                } else {
+                    self.zoom_at(sf::Vector2i::new(*x, *y), *delta);
+                }
+            }
+            sf::Event::MouseMoved { x, y } => {
+                // Hover follows the events, the frame's update only refreshes it (the view
+                // or the polygons may change while the mouse stands still)
+                self.mouse_pixel = sf::Vector2i::new(*x, *y);
+                let pos = self.mouse_world_pos();
+                self.curr_state.as_mut().unwrap().on_mouse_moved(pos, &mut self.app_ctx);
+
+                if let Some(prev) = self.panning {
+                    let pixel = sf::Vector2i::new(*x, *y);
+                    let d = self.to_world(prev) - self.to_world(pixel);
+                    self.camera.move_(d);
+                    self.panning = Some(pixel);
                 }
             }
-            sf::Event::MouseButtonReleased { button: btn, x, y } => {
+            sf::Event::MouseButtonReleased { button: btn, .. } => {
+                if *btn == sfml::window::mouse::Button::Middle {
+                    self.panning = None;
+                }
                 if *btn == sfml::window::mouse::Button::Left {
                     self.left_mouse_pressed = false;
                     self.curr_state = Some(self.curr_state.take().unwrap().on_left_mouse_released(
-                        sf::Vector2f::new(self.window.mouse_position().x as f32, self.window.mouse_position().y as f32),
+                        self.mouse_world_pos(),
                         &mut self.app_ctx,
                     ));
-                    println!("LM released");
+                    log::debug!("LM released");
                 }
             }
             _ => (),
         }
     }
 
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::ToggleShortcutsHelp => self.show_shortcuts = !self.show_shortcuts,
+            Action::SetGridOriginToCursor => {
+                self.app_ctx.grid.origin = self.mouse_world_pos();
+            }
+            Action::RotateSelection => {
+                let pos = self.mouse_world_pos();
+                self.curr_state = Some(self.curr_state.take().unwrap().on_rotate_key(
+                    pos,
+                    &mut self.app_ctx,
+                ));
+            }
+            Action::Cancel => {
+                // Idle state ignores it
+                self.curr_state = Some(self.curr_state.take().unwrap().on_cancel_btn(&mut self.app_ctx));
+            }
+            Action::Undo | Action::Redo => {
+                // Abort the current operation, the restored polygons would not match it
+                self.curr_state = Some(self.curr_state.take().unwrap().on_cancel_btn(&mut self.app_ctx));
+                self.app_ctx.polygon_obj_factory.clear();
+                let done = if action == Action::Undo { self.app_ctx.undo() } else { self.app_ctx.redo() };
+                if !done {
+                    self.app_ctx.toasts.push(if action == Action::Undo { "Nothing to undo" } else { "Nothing to redo" });
+                }
+                self.curr_state = Some(Box::new(IdleState::new(&mut self.app_ctx)));
+            }
+            Action::Copy => {
+                let count = self.app_ctx.copy_selection();
+                if count == 0 {
+                    self.app_ctx.toasts.push("No polygon selected");
+                } else {
+                    self.app_ctx.toasts.push(format!("Copied {} polygon(s)", count));
+                }
+            }
+            Action::Paste => {
+                // Like undo, the current operation wouldn't match the new polygons
+                self.curr_state = Some(self.curr_state.take().unwrap().on_cancel_btn(&mut self.app_ctx));
+                self.app_ctx.polygon_obj_factory.clear();
+                if self.app_ctx.paste() == 0 {
+                    self.app_ctx.toasts.push("Nothing to paste");
+                }
+                self.curr_state = Some(Box::new(IdleState::new(&mut self.app_ctx)));
+            }
+            // Mouse gestures are handled in handle_input
+            Action::SelectOrDrag | Action::ToggleSelection | Action::SelectPolygon | Action::SelectPolygonByEdge |
+            Action::AxisLockedDrag | Action::CopyDrag | Action::MarqueeSelect | Action::PlaceWithoutSnap | Action::ZoomView | Action::PanView |
+            Action::ScrollOffset => (),
+        }
+    }
+
     fn update(&mut self, dt: f32) {
+        self.app_ctx.toasts.update(dt);
+        self.app_ctx.snap_indicator.hide();
+        self.app_ctx.hud = None;
+        self.app_ctx.marquee = None;
+        self.app_ctx.canvas = self.canvas_rect();
+        self.app_ctx.panels = self.egui_rects.iter().map(|rect| self.to_world_rect(*rect)).collect();
+        let mouse_pos = self.mouse_world_pos();
+        if self.curr_state.as_ref().unwrap().is_stale(&self.app_ctx) {
+            self.curr_state = Some(self.curr_state.take().unwrap().on_cancel_btn(&mut self.app_ctx));
+        }
         self.curr_state.as_mut().unwrap().update(
             dt,
-            sf::Vector2f::new(
-                self.window.mouse_position().x as f32,
-                self.window.mouse_position().y as f32,
-            ),
+            mouse_pos,
             &mut self.app_ctx,
         );
-    }
+
+        for poly in self.app_ctx.polygon_objs.iter_mut() {
+            poly.refresh_offset();
+        }
+    }
+
+    // Matches the entry heights with the polygons again after any change of the list (undo,
+    // the commands, loading, pasting). The polygons still there keep their heights.
+    fn sync_entry_heights(&mut self, collapsed_height: f32) {
+        let polygons = &self.app_ctx.polygon_objs;
+        let unchanged = self.entry_heights.len() == polygons.len() &&
+            self.entry_heights.iter().zip(polygons.iter()).all(|((uid, _), poly)| *uid == poly.uid());
+        if unchanged {
+            return;
+        }
+
+        let heights: HashMap<usize, f32> = self.entry_heights.drain(..).collect();
+        self.entry_heights = polygons
+            .iter()
+            .map(|poly| (poly.uid(), heights.get(&poly.uid()).copied().unwrap_or(collapsed_height)))
+            .collect();
+    }
+
+    // Closest points of the measured polygons, recomputed every frame so it stays valid while
+    // editing. The measurement is cleared once either polygon is removed.
+    fn measurement(&mut self) -> Option<(sf::Vector2f, sf::Vector2f)> {
+        let (a, b) = self.measured_pair?;
+        let (a, b) = match (self.app_ctx.find_polygon(a), self.app_ctx.find_polygon(b)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                self.measured_pair = None;
+                return None;
+            }
+        };
+        let polygons = &self.app_ctx.polygon_objs;
+        polygons[a].polygon().closest_points_to(polygons[b].polygon())
+    }
+
+    fn draw_measurement(&mut self) {
+        if let Some((p, q)) = self.measurement() {
+            let vertices = [
+                sf::Vertex::with_pos_color(p, style::MEASUREMENT_COLOR),
+                sf::Vertex::with_pos_color(q, style::MEASUREMENT_COLOR),
+            ];
+            self.window.draw_primitives(&vertices, sf::PrimitiveType::LINES, &sf::RenderStates::DEFAULT);
+        }
+    }
+
+    fn draw_containment(&mut self) {
+        if !self.show_containment {
+            return;
+        }
+        for (inner, outer) in self.app_ctx.find_containment() {
+            let vertices = [
+                sf::Vertex::with_pos_color(self.app_ctx.polygon_objs[inner].polygon().find_center(), style::CONTAINMENT_COLOR),
+                sf::Vertex::with_pos_color(self.app_ctx.polygon_objs[outer].polygon().find_center(), style::CONTAINMENT_COLOR),
+            ];
+            self.window.draw_primitives(&vertices, sf::PrimitiveType::LINES, &sf::RenderStates::DEFAULT);
+        }
+    }
+
+    fn draw_shadow_egui(&mut self, ui: &mut egui::Ui) {
+        let shadow = &mut self.app_ctx.shadow;
+        ui.checkbox(&mut shadow.enabled, "Drop shadow")
+            .on_hover_text("Draw a soft shadow behind the filled polygons");
+        ui.add_enabled_ui(shadow.enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Shadow offset");
+                ui.add(egui::DragValue::new(&mut shadow.offset.x).speed(0.5));
+                ui.add(egui::DragValue::new(&mut shadow.offset.y).speed(0.5));
+            });
+            ui.add(egui::Slider::new(&mut shadow.opacity, 0.0..=1.0).text("Shadow opacity"));
+            ui.add(egui::Slider::new(&mut shadow.softness, 0.0..=20.0).text("Shadow softness"));
+        });
+    }
+
+    fn draw_test_point(&mut self) {
+        if !self.test_point {
+            return;
+        }
+        let mouse_pos = self.mouse_world_pos();
+        for poly in self.app_ctx.polygon_objs.iter() {
+            let polygon = poly.polygon();
+            if !polygon.contains_point(mouse_pos) {
+                continue;
+            }
+            let vertices: Vec<sf::Vertex> = (0..=polygon.points_count() as isize)
+                .map(|i| sf::Vertex::with_pos_color(polygon.get_point_pos(i), style::TEST_POINT_INSIDE_COLOR))
+                .collect();
+            self.window.draw_primitives(&vertices, sf::PrimitiveType::LINE_STRIP, &sf::RenderStates::DEFAULT);
+        }
+    }
+
+    fn draw_containment_egui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Containment")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.checkbox(&mut self.show_containment, "Show on canvas")
+                    .on_hover_text("Connect the center of every contained polygon with the center of the polygon around it");
+                let containment = self.app_ctx.find_containment();
+                if containment.is_empty() {
+                    ui.label("No polygon lies inside another one");
+                }
+                for (inner, outer) in containment {
+                    let polygons = &self.app_ctx.polygon_objs;
+                    ui.label(format!("{} is inside {}", polygons[inner].polygon().get_name(), polygons[outer].polygon().get_name()));
+                }
+            });
+    }
+
+    fn draw_measurement_egui(&mut self, ui: &mut egui::Ui) {
+        let selected: Vec<usize> = self.app_ctx.polygon_objs
+            .iter()
+            .enumerate()
+            .filter(|(_, poly)| poly.selected_points_count() > 0)
+            .map(|(id, _)| id)
+            .collect();
+
+        ui.horizontal(|ui| {
+            if ui.add_enabled(selected.len() == 2, egui::Button::new("Measure distance"))
+                .on_hover_text("Minimum distance between the boundaries of the two selected polygons")
+                .clicked() {
+                let polygons = &self.app_ctx.polygon_objs;
+                self.measured_pair = Some((polygons[selected[0]].uid(), polygons[selected[1]].uid()));
+            }
+            if self.measured_pair.is_some() && ui.button("Clear").clicked() {
+                self.measured_pair = None;
+            }
+        });
+
+        if let Some((p, q)) = self.measurement() {
+            let dist = my_math::distance(&p, &q);
+            if dist == 0. {
+                ui.label("Distance: 0 (the polygons overlap)");
+            } else {
+                ui.label(format!("Distance: {}", self.app_ctx.units.format_length(dist)));
+            }
+        }
+    }
+
+    // Distance between the only two selected points, whichever polygons they belong to
+    fn selected_points_distance(&self) -> Option<f32> {
+        let points: Vec<sf::Vector2f> = self.app_ctx.polygon_objs
+            .iter()
+            .flat_map(|poly| poly.selected_points())
+            .map(|(_, pos)| pos)
+            .collect();
+        match points.as_slice() {
+            [p, q] => Some(my_math::distance(p, q)),
+            _ => None,
+        }
+    }
+
+    fn draw_units_egui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Units")
+            .default_open(false)
+            .show(ui, |ui| {
+                let units = &mut self.app_ctx.units;
+                ui.checkbox(&mut units.enabled, "Real units")
+                    .on_hover_text("Show the lengths and areas in the calibrated units instead of px");
+                ui.horizontal(|ui| {
+                    ui.label("Unit");
+                    ui.add(egui::TextEdit::singleline(&mut units.name).desired_width(40.));
+                    ui.add(egui::DragValue::new(&mut units.scale).clamp_range(0.000001..=f32::MAX).speed(0.001));
+                    ui.label("per px");
+                });
+
+                let distance = self.selected_points_distance();
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.calibration_length).clamp_range(0.0..=f32::MAX).speed(0.1));
+                    let clicked = ui.add_enabled(distance.is_some(), egui::Button::new("Calibrate"))
+                        .on_hover_text("The distance between the two selected points measures the given length")
+                        .on_disabled_hover_text("Select exactly two points")
+                        .clicked();
+                    if let (true, Some(distance)) = (clicked, distance) {
+                        if let Err(err) = self.app_ctx.units.calibrate(distance, self.calibration_length) {
+                            self.app_ctx.toasts.push(format!("Can't calibrate: {}", err));
+                        }
+                    }
+                });
+            });
+    }
+
+    fn draw_points_and_overlays(&mut self) {
+        for poly in &self.app_ctx.polygon_objs {
+            poly.draw_triangulation(&mut self.window);
+        }
+        for poly in &self.app_ctx.polygon_objs {
+            poly.draw_points(&mut self.window, self.app_ctx.indices_only);
+        }
+
+        if self.show_normals {
+            for poly in &self.app_ctx.polygon_objs {
+                poly.polygon().draw_normals(&mut self.window);
+            }
+        }
+
+        // Selection and hover are drawn in a separate pass to stay on top of every polygon
+        for poly in &self.app_ctx.polygon_objs {
+            poly.draw_overlays(&mut self.window);
+        }
+        self.app_ctx.polygon_obj_factory.draw_ctx(&mut self.window, self.app_ctx.indices_only);
+
+        if let Some((p0, p1)) = self.app_ctx.marquee {
+            let mut rect = sf::RectangleShape::new();
+            rect.set_position(sf::Vector2f::new(p0.x.min(p1.x), p0.y.min(p1.y)));
+            rect.set_size(sf::Vector2f::new((p1.x - p0.x).abs(), (p1.y - p0.y).abs()));
+            rect.set_fill_color(style::MARQUEE_FILL_COLOR);
+            rect.set_outline_color(style::MARQUEE_OUTLINE_COLOR);
+            // Keep the outline one pixel wide at any zoom
+            rect.set_outline_thickness(style::MARQUEE_THICKNESS * self.zoom);
+            self.window.draw(&rect);
+        }
+    }
 
     fn render(&mut self) {
+        self.window.set_view(&self.camera);
+
         // Draw edges of the polygons
         match self.drawing_mode {
             DrawingMode::GPU => {
+                self.app_ctx.grid.draw(&mut self.window);
+
+                // Shadows and fills are drawn first, so they never cover the edges
+                if self.app_ctx.shadow.enabled {
+                    for poly in &self.app_ctx.polygon_objs {
+                        poly.draw_shadow(&mut self.window, &self.app_ctx.shadow);
+                    }
+                }
                 for poly in &self.app_ctx.polygon_objs {
-                    poly.draw_edges(&mut self.window);
-                    poly.draw_ctx(&mut self.window);
+                    poly.draw_fill(&mut self.window);
                 }
 
+                for poly in &self.app_ctx.polygon_objs {
+                    poly.draw_edges(&mut self.window);
+                }
                 self.app_ctx.polygon_obj_factory.draw_edges(&mut self.window);
-                self.app_ctx.polygon_obj_factory.draw_ctx(&mut self.window);
+
+                self.draw_points_and_overlays();
             }
             DrawingMode::CPU => {
-                // Clear the framebuffer
-                for y in 0..style::WIN_SIZE_Y {
-                    for x in 0..style::WIN_SIZE_X {
-                        unsafe { self.cpu_drawing_image.set_pixel(x, y, style::BACKGROUND_COLOR); }
-                    }
+                // The window might have been resized before the event got handled
+                if !self.sync_framebuffer_size() {
+                    return;
                 }
 
-                for poly in &self.app_ctx.polygon_objs {
-                    poly.draw_bresenham_edges(&mut self.window, &mut self.cpu_drawing_image, &mut self.line_painter);
-                }
-                self.app_ctx.polygon_obj_factory.draw_bresenham_edges(&mut self.window, &mut self.cpu_drawing_image, &mut self.line_painter);
-
-                // Draw the framebuffer
-                let mut texture = sf::Texture::new();
-                let _err = texture.as_mut().unwrap().load_from_image(
-                    &self.cpu_drawing_image,
-                    sf::IntRect::new(
-                        0,
-                        0,
-                        style::WIN_SIZE_X as i32,
-                        style::WIN_SIZE_Y as i32,
-                    ),
-                );
+                let size = self.cpu_drawing_image.size();
+                if self.cpu_dirty || self.cpu_texture.size() != size {
+                    self.cpu_dirty = false;
+                    self.rasterize_framebuffer(size);
+                }
 
-                let sprite = sf::Sprite::with_texture(texture.as_ref().unwrap());
+                // The framebuffer is already in the screen space
+                let sprite = sf::Sprite::with_texture(&self.cpu_texture);
+                self.window.set_view(&self.screen_view());
                 self.window.draw(&sprite);
+                self.window.set_view(&self.camera);
 
-                for poly in &self.app_ctx.polygon_objs {
-                    poly.draw_ctx(&mut self.window);
+                self.draw_points_and_overlays();
+            }
+        };
+
+        self.draw_measurement();
+        self.draw_containment();
+        self.draw_test_point();
+        self.app_ctx.snap_indicator.draw(&mut self.window);
+
+        // Egui is drawn in the screen space
+        self.window.set_view(&self.screen_view());
+    }
+
+    // Draws the grid and the polygons into cpu_drawing_image and uploads it into cpu_texture
+    fn rasterize_framebuffer(&mut self, size: sf::Vector2u) {
+        // The framebuffer shows the region of the world seen through the camera
+        let origin = self.camera.center() - self.camera.size() / 2.;
+        self.line_painter.set_origin(origin);
+        if self.supersampling > 1 {
+            // Render into a larger image and downsample it into the framebuffer
+            let factor = self.supersampling;
+            let needs_alloc = match self.supersampled_image.as_ref() {
+                Some(img) => img.size() != size * factor,
+                None => true,
+            };
+            if needs_alloc {
+                self.supersampled_image = Some(sf::Image::new(size.x * factor, size.y * factor));
+            }
+            let image = self.supersampled_image.as_mut().unwrap();
+            Self::clear_image(image, &mut self.cpu_background);
+            self.app_ctx.grid.draw_into_image(image, origin, factor as f32 / self.zoom);
+
+            let thickness = self.line_painter.thickness();
+            self.line_painter.set_scale(factor as f32 / self.zoom);
+            self.line_painter.set_thickness(thickness * factor as f32);
+            self.pixel_stats = Self::rasterize_polygons(&self.app_ctx, &mut self.window, &mut self.line_painter, image, self.comparison_alg.as_ref());
+            self.line_painter.set_thickness(thickness);
+
+            line_alg::downsample(image, &mut self.cpu_drawing_image, factor);
+        } else {
+            // Free the supersampled image, it's not used anymore
+            self.supersampled_image = None;
+            Self::clear_image(&mut self.cpu_drawing_image, &mut self.cpu_background);
+            self.app_ctx.grid.draw_into_image(&mut self.cpu_drawing_image, origin, 1.0 / self.zoom);
+            self.line_painter.set_scale(1.0 / self.zoom);
+            self.pixel_stats = Self::rasterize_polygons(&self.app_ctx, &mut self.window, &mut self.line_painter, &mut self.cpu_drawing_image, self.comparison_alg.as_ref());
+        }
+        self.line_painter.set_scale(1.0);
+        self.line_painter.set_origin(sf::Vector2f::new(0., 0.));
+
+        if self.cpu_texture.size() != size && self.cpu_texture.create(size.x, size.y).is_err() {
+            log::error!("Error creating the framebuffer texture");
+            return;
+        }
+        // The texture has the size of the image, so the whole texture is overwritten
+        unsafe { self.cpu_texture.update_from_image(&self.cpu_drawing_image, 0, 0); }
+    }
+
+    fn draw_polygon_entry_egui(ui: &mut egui::Ui, poly: &mut PolygonObject, units: &Units) -> EntryAction {
+        let mut action = EntryAction::None;
+        egui::CollapsingHeader::new(poly.polygon().get_name())
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Delete").clicked() {
+                        action = EntryAction::Delete;
+                    }
+                    if ui.button("Duplicate").on_hover_text("Add a shifted copy of the polygon").clicked() {
+                        action = EntryAction::Duplicate;
+                    }
+                });
+
+                let spikes = poly.polygon().find_spikes(style::SPIKE_ANGLE);
+                if !spikes.is_empty() {
+                    let ids: Vec<String> = spikes.iter().map(|id| id.to_string()).collect();
+                    ui.colored_label(egui::Color32::from_rgb(237, 123, 123), format!("Spikes at vertices {}", ids.join(", ")))
+                        .on_hover_text("Needle-like vertices distort the offset, see the Remove spikes command");
+                }
+
+                ui.label(format!("Area: {}", units.format_area(poly.polygon().area())));
+                ui.label(format!("Perimeter: {}", units.format_length(poly.polygon().perimeter())));
+
+                // Polygon options
+                poly.draw_egui(ui);
+            });
+        action
+    }
+
+    fn draw_commands_egui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Commands:");
+        ui.horizontal_wrapped(|ui| {
+            for command in self.commands.commands() {
+                let mut button = ui.button(command.name());
+                if !command.description().is_empty() {
+                    button = button.on_hover_text(command.description());
                 }
-                self.app_ctx.polygon_obj_factory.draw_ctx(&mut self.window);
+                if button.clicked() {
+                    command.run(&mut self.app_ctx);
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Subdivide:");
+            ui.add(egui::DragValue::new(&mut self.app_ctx.subdivide_iterations).clamp_range(1..=4).suffix("x"));
+        });
+        ui.horizontal(|ui| {
+            // The slider ends at the vertex count of the polygon the command would simplify
+            let count = self.app_ctx.polygon_objs
+                .iter()
+                .find(|poly| poly.selected_points_count() > 0)
+                .map_or(3, |poly| poly.polygon().points_count().max(3));
+            self.app_ctx.simplify_target = self.app_ctx.simplify_target.clamp(3, count);
+            ui.label("Simplify to:");
+            ui.add(egui::Slider::new(&mut self.app_ctx.simplify_target, 3..=count).text("vertices"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Mirror numbering:");
+            ui.selectable_value(&mut self.app_ctx.mirror_winding, MirrorWinding::KeepFirstVertex, "Keep the first vertex")
+                .on_hover_text("Every vertex gets the number of its mirror image, counted the other way around");
+            ui.selectable_value(&mut self.app_ctx.mirror_winding, MirrorWinding::Reverse, "Reverse")
+                .on_hover_text("The numbering is simply reversed, the last vertex becomes the first one");
+        });
+    }
+
+    // Pixels set in the last CPU frame, per polygon and per algorithm
+    fn draw_pixel_stats_egui(&self, ui: &mut egui::Ui) {
+        if self.supersampling > 1 {
+            ui.label(format!("Counted in the {}x supersampled image", self.supersampling));
+        }
+        egui::Grid::new("PixelStats").striped(true).show(ui, |ui| {
+            ui.strong("Polygon");
+            ui.strong("Algorithm");
+            ui.strong("Pixels");
+            ui.end_row();
+            for (name, alg, count) in self.pixel_stats.iter() {
+                ui.label(name);
+                ui.label(alg.name());
+                ui.label(count.to_string());
+                ui.end_row();
+            }
+        });
+
+        // Totals per algorithm, in the order of the first appearance
+        let mut totals: Vec<(LinePainterAlgorithm, usize)> = Vec::new();
+        for (_, alg, count) in self.pixel_stats.iter() {
+            match totals.iter_mut().find(|(a, _)| a == alg) {
+                Some((_, total)) => *total += count,
+                None => totals.push((alg.clone(), *count)),
+            }
+        }
+        for (alg, total) in totals {
+            ui.label(format!("{}: {} pixels", alg.name(), total));
+        }
+    }
+
+    fn draw_line_style_egui(&mut self, ui: &mut egui::Ui) {
+        let mut line_style = self.line_painter.line_style();
+        egui::ComboBox::from_label("Line style")
+            .selected_text(line_style.name())
+            .show_ui(ui, |ui| {
+                let options = [
+                    LineStyle::Solid,
+                    LineStyle::Dashed { on: style::DASH_LENGTH, off: style::DASH_GAP },
+                    LineStyle::Dotted { gap: style::DOT_GAP },
+                ];
+                for option in options {
+                    // Keep the parameters when the same style is picked again
+                    let selected = option.name() == line_style.name();
+                    if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                        line_style = option;
+                    }
+                }
+            });
+        match &mut line_style {
+            LineStyle::Solid => (),
+            LineStyle::Dashed { on, off } => {
+                ui.add(egui::Slider::new(on, 1.0..=50.0).text("Dash length"));
+                ui.add(egui::Slider::new(off, 1.0..=50.0).text("Dash gap"));
+            }
+            LineStyle::Dotted { gap } => {
+                ui.add(egui::Slider::new(gap, 1.0..=50.0).text("Dot gap"));
+            }
+        }
+        self.line_painter.set_line_style(line_style);
+    }
+
+    fn draw_algorithm_combo_egui(ui: &mut egui::Ui, label: &str, alg: &mut LinePainterAlgorithm) {
+        egui::ComboBox::from_label(label)
+            .selected_text(alg.name())
+            .show_ui(ui, |ui| {
+                ui.selectable_value(alg, LinePainterAlgorithm::MidPointLine, "MidPointLine");
+                ui.selectable_value(alg, LinePainterAlgorithm::SymmetricMidPointLine, "SymmetricMidPointLine");
+                ui.selectable_value(alg, LinePainterAlgorithm::SymmetricDoubleStepMidPointLine, "SymmetricDoubleStepMidPointLine");
+                ui.selectable_value(alg, LinePainterAlgorithm::GuptaDoubleStepMidPointLine, "GuptaDoubleStepMidPointLine");
+                ui.selectable_value(alg, LinePainterAlgorithm::WULine, "XiaolinWULine");
+                ui.selectable_value(alg, LinePainterAlgorithm::DDA, "DDA");
+            });
+    }
+
+    // If comparison_alg is set, the right half of the image is rasterized with it
+    // and the left half with the painter's algorithm. Returns the number of pixels
+    // set for every polygon and algorithm.
+    fn rasterize_polygons(
+        app_ctx: &AppContext,
+        window: &mut sf::RenderWindow,
+        line_painter: &mut LinePainter,
+        image: &mut sf::Image,
+        comparison_alg: Option<&LinePainterAlgorithm>,
+    ) -> Vec<(String, LinePainterAlgorithm, usize)> {
+        let mut stats = Vec::new();
+        line_painter.reset_pixels_drawn();
+
+        let mut draw = |line_painter: &mut LinePainter, image: &mut sf::Image| {
+            // The last entry is the polygon being created
+            let mut counts = vec![0; app_ctx.polygon_objs.len() + 1];
+
+            // Shadows and fills first, so they never cover the edges
+            if app_ctx.shadow.enabled {
+                for (id, poly) in app_ctx.polygon_objs.iter().enumerate() {
+                    let before = line_painter.pixels_drawn();
+                    poly.draw_bresenham_shadow(image, line_painter, &app_ctx.shadow);
+                    counts[id] += line_painter.pixels_drawn() - before;
+                }
+            }
+            for (id, poly) in app_ctx.polygon_objs.iter().enumerate() {
+                let before = line_painter.pixels_drawn();
+                poly.draw_bresenham_fill(image, line_painter);
+                counts[id] += line_painter.pixels_drawn() - before;
+            }
+            for (id, poly) in app_ctx.polygon_objs.iter().enumerate() {
+                let before = line_painter.pixels_drawn();
+                poly.draw_bresenham_edges(window, image, line_painter);
+                counts[id] += line_painter.pixels_drawn() - before;
+            }
+            let before = line_painter.pixels_drawn();
+            app_ctx.polygon_obj_factory.draw_bresenham_edges(window, image, line_painter);
+            counts[app_ctx.polygon_objs.len()] = line_painter.pixels_drawn() - before;
+
+            let alg = line_painter.alg();
+            for (id, count) in counts.into_iter().enumerate() {
+                match app_ctx.polygon_objs.get(id) {
+                    Some(poly) => stats.push((poly.polygon().get_name().clone(), alg.clone(), count)),
+                    None if count > 0 => stats.push((String::from("New polygon"), alg.clone(), count)),
+                    None => (),
+                }
+            }
+        };
+
+        let right_alg = match comparison_alg {
+            Some(alg) => alg,
+            None => {
+                draw(line_painter, image);
+                return stats;
             }
         };
+
+        let size = image.size();
+        let half = (size.x / 2) as i32;
+        let left_alg = line_painter.alg();
+
+        line_painter.set_clip_x(Some(0..half));
+        draw(line_painter, image);
+
+        line_painter.set_alg(right_alg.clone());
+        line_painter.set_clip_x(Some(half..size.x as i32));
+        draw(line_painter, image);
+
+        line_painter.set_alg(left_alg);
+        line_painter.set_clip_x(None);
+
+        // Divider
+        for y in 0..size.y {
+            unsafe { image.set_pixel(half as u32, y, style::COMPARISON_DIVIDER_COLOR); }
+        }
+        stats
+    }
+
+    // Copies the background over the image, the background is reallocated when the size changes
+    fn clear_image(image: &mut sf::Image, background: &mut Option<sf::Image>) {
+        let size = image.size();
+        if background.as_ref().map(|bg| bg.size()) != Some(size) {
+            *background = sf::Image::from_color(size.x, size.y, style::BACKGROUND_COLOR);
+        }
+        if let Some(background) = background.as_ref() {
+            image.copy_image(background, 0, 0, sf::IntRect::new(0, 0, size.x as i32, size.y as i32), false);
+        }
+    }
+
+    fn draw_default_style_egui(&mut self, ui: &mut egui::Ui) {
+        let mut default_style = self.app_ctx.polygon_obj_factory.default_style().clone();
+        ui.label("New polygons:");
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgb(&mut default_style.edges_color);
+            ui.label("Edges color");
+        });
+        egui::ComboBox::from_label("Default fill")
+            .selected_text(match default_style.fill_style {
+                FillStyle::None => "None",
+                FillStyle::Solid => "Solid",
+                FillStyle::Texture => "Texture",
+                FillStyle::Hatch => "Hatch",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut default_style.fill_style, FillStyle::None, "None");
+                ui.selectable_value(&mut default_style.fill_style, FillStyle::Solid, "Solid");
+                ui.selectable_value(&mut default_style.fill_style, FillStyle::Hatch, "Hatch");
+            });
+        if default_style.fill_style != FillStyle::None {
+            let [r, g, b, a] = default_style.fill_color;
+            let mut rgba = egui::Color32::from_rgba_unmultiplied(r, g, b, a);
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgba(&mut rgba);
+                ui.label("Fill color");
+            });
+            default_style.fill_color = rgba.to_srgba_unmultiplied();
+        }
+        ui.add(egui::Slider::new(&mut default_style.close_radius, 1.0..=50.0).text("Close radius"))
+            .on_hover_text("Distance from the first vertex at which a new polygon snaps closed");
+        self.app_ctx.polygon_obj_factory.set_default_style(default_style);
+        // Saved once the slider or the color picker is released
+        if !ui.input(|input| input.pointer.any_down()) {
+            self.app_ctx.polygon_obj_factory.save_default_style();
+        }
+    }
+
+    fn draw_grid_options_egui(&mut self, ui: &mut egui::Ui) {
+        let grid = &mut self.app_ctx.grid;
+        ui.checkbox(&mut grid.enabled, "Show grid");
+        ui.checkbox(&mut grid.snap_enabled, "Snap to grid");
+        ui.add(egui::Slider::new(&mut grid.spacing, style::GRID_MIN_SPACING..=200.0).text("Grid spacing"));
+        ui.horizontal(|ui| {
+            ui.label("Grid origin:");
+            ui.add(egui::DragValue::new(&mut grid.origin.x).prefix("x: "));
+            ui.add(egui::DragValue::new(&mut grid.origin.y).prefix("y: "));
+        });
+        ui.label("Shift + O moves the grid origin to the cursor");
+    }
+
+    fn draw_library_egui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Shape library:");
+        if self.shape_library.templates().is_empty() {
+            ui.label("Empty");
+            return;
+        }
+
+        let mut picked: Option<usize> = None;
+        let mut removed: Option<usize> = None;
+        ui.horizontal_wrapped(|ui| {
+            for (id, template) in self.shape_library.templates().iter().enumerate() {
+                let size = egui::Vec2::splat(style::THUMBNAIL_SIZE);
+                let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+
+                // Wireframe thumbnail, templates are normalized to the unit box
+                let points: Vec<egui::Pos2> = template
+                    .points()
+                    .iter()
+                    .map(|p| rect.min + egui::Vec2::new(p.x, p.y) * (style::THUMBNAIL_SIZE - 8.) + egui::Vec2::splat(4.))
+                    .collect();
+                let stroke_color = if response.hovered() {
+                    ui.visuals().strong_text_color()
+                } else {
+                    ui.visuals().text_color()
+                };
+                ui.painter().rect_stroke(rect, 2., ui.visuals().widgets.noninteractive.bg_stroke);
+                ui.painter().add(egui::Shape::closed_line(points, egui::Stroke::new(1., stroke_color)));
+
+                if response.clicked() {
+                    picked = Some(id);
+                }
+                response.context_menu(|ui| {
+                    if ui.button("Remove").clicked() {
+                        removed = Some(id);
+                        ui.close_menu();
+                    }
+                });
+            }
+        });
+
+        if let Some(id) = picked {
+            let template = self.shape_library.templates()[id].clone();
+            let _prev = self.curr_state.take().unwrap().on_cancel_btn(&mut self.app_ctx);
+            self.curr_state = Some(Box::new(StampState::new(template, &mut self.app_ctx)));
+        }
+        if let Some(id) = removed {
+            self.shape_library.remove(id);
+        }
+    }
+
+    fn draw_coords_dialog_egui(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_coords_dialog;
+        let mut built = false;
+        egui::Window::new("Polygon from coordinates")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("One \"x y\" pair per line:");
+                ui.add(egui::TextEdit::multiline(&mut self.coords_input)
+                    .code_editor()
+                    .desired_rows(8));
+
+                if let Some(err) = self.coords_error.as_ref() {
+                    ui.colored_label(egui::Color32::from_rgb(237, 123, 123), err);
+                }
+
+                if ui.button("Create").clicked() {
+                    self.coords_error = match self.build_from_coords() {
+                        Ok(()) => {
+                            built = true;
+                            None
+                        }
+                        Err(err) => Some(err),
+                    };
+                }
+            });
+        self.show_coords_dialog = open && !built;
+    }
+
+    fn build_from_coords(&mut self) -> Result<(), String> {
+        let raw = RawPolygonCoords::parse(&self.coords_input)?;
+        let mut poly = self.app_ctx.polygon_obj_factory.build_from_raw(raw);
+        if poly.polygon().is_self_crossing() {
+            return Err(polygon::PolygonError::WouldSelfIntersect.to_string());
+        }
+        poly.assert_ccw();
+        self.app_ctx.checkpoint();
+        self.app_ctx.polygon_objs.push(poly);
+        Ok(())
+    }
+
+    // Magnified view of the CPU framebuffer around the cursor
+    fn draw_loupe_egui(&mut self, ctx: &egui::Context) {
+        let mouse = self.mouse_pixel;
+        let image = &self.cpu_drawing_image;
+        let radius = style::LOUPE_RADIUS as i32;
+        let cell = style::LOUPE_CELL_SIZE;
+        let cells = (2 * radius + 1) as f32;
+
+        egui::Window::new("Pixel loupe")
+            .open(&mut self.show_loupe)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let (response, painter) = ui.allocate_painter(
+                    egui::Vec2::splat(cells * cell),
+                    egui::Sense::hover(),
+                );
+                let origin = response.rect.min;
+                let size = image.size();
+
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let (x, y) = (mouse.x + dx, mouse.y + dy);
+                        let color = if x >= 0 && y >= 0 && (x as u32) < size.x && (y as u32) < size.y {
+                            let c = unsafe { image.pixel_at(x as u32, y as u32) };
+                            egui::Color32::from_rgb(c.r, c.g, c.b)
+                        } else {
+                            egui::Color32::BLACK
+                        };
+                        let min = origin + egui::vec2((dx + radius) as f32 * cell, (dy + radius) as f32 * cell);
+                        painter.rect_filled(egui::Rect::from_min_size(min, egui::Vec2::splat(cell)), 0.0, color);
+                    }
+                }
+
+                // Pixel grid
+                let stroke = egui::Stroke::new(1.0, egui::Color32::from_gray(60));
+                for i in 0..=(2 * radius + 1) {
+                    let offset = i as f32 * cell;
+                    painter.line_segment([origin + egui::vec2(offset, 0.), origin + egui::vec2(offset, cells * cell)], stroke);
+                    painter.line_segment([origin + egui::vec2(0., offset), origin + egui::vec2(cells * cell, offset)], stroke);
+                }
+
+                // Mark the pixel under the cursor
+                let center = origin + egui::Vec2::splat(radius as f32 * cell);
+                painter.rect_stroke(
+                    egui::Rect::from_min_size(center, egui::Vec2::splat(cell)),
+                    0.0,
+                    egui::Stroke::new(2.0, egui::Color32::YELLOW),
+                );
+
+                ui.label(format!("x: {}, y: {}", mouse.x, mouse.y));
+            });
+    }
+
+    fn draw_status_bar_egui(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("Status bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let mouse = self.mouse_world_pos();
+                ui.label(format!("x: {:.1}, y: {:.1}", mouse.x, mouse.y));
+                ui.separator();
+                ui.label(format!("State: {}", self.curr_state.as_ref().unwrap().state_name()));
+
+                for poly in self.app_ctx.polygon_objs.iter() {
+                    if poly.is_point_hovered() {
+                        ui.separator();
+                        ui.label(format!("{}: point {}", poly.polygon().get_name(), poly.get_hovered_point_id()));
+                        break;
+                    }
+                    if poly.is_line_hovered() {
+                        let (p0, p1) = poly.get_hovered_line_ids();
+                        ui.separator();
+                        ui.label(format!("{}: edge {}-{}", poly.polygon().get_name(), p0, p1));
+                        break;
+                    }
+                }
+            });
+        });
     }
 
     fn render_egui(&mut self, ctx: &egui::Context) {
-        egui::TopBottomPanel::top("Top").show(&ctx, |ui| {
+        egui::TopBottomPanel::top("Top").show(&ctx, |ui| egui::menu::bar(ui, |ui| {
             ui.menu_button("File", |ui| {
                 {
                     if egui::Button::new("Save").sense(egui::Sense {
@@ -339,6 +1952,10 @@ impl Application<'_> {
                         dialog.open();
                         self.file_dialog = Some(dialog);
                     }
+
+                    ui.checkbox(&mut self.export_normalized, "Normalize to unit box")
+                        .on_hover_text("Save the polygons scaled into [0,1]x[0,1], the original transform is stored alongside");
+                    ui.add_enabled(self.export_normalized, egui::Checkbox::new(&mut self.export_stretched, "Stretch (ignore aspect ratio)"));
                 }
                 ui.separator();
                 {
@@ -348,8 +1965,46 @@ impl Application<'_> {
                         self.file_dialog = Some(dialog);
                     }
                 }
+                ui.separator();
+                if ui.button("New from coordinates...").clicked() {
+                    self.show_coords_dialog = true;
+                    ui.close_menu();
+                }
+                if ui.button("Export OBJ...").clicked() {
+                    let mut dialog = egui_file::FileDialog::save_file(None);
+                    dialog.open();
+                    self.obj_export_dialog = Some(dialog);
+                }
+                if ui.button("Import DXF...").clicked() {
+                    let mut dialog = egui_file::FileDialog::open_file(None);
+                    dialog.open();
+                    self.dxf_import_dialog = Some(dialog);
+                }
+                if ui.button("Export DXF...").clicked() {
+                    let mut dialog = egui_file::FileDialog::save_file(None);
+                    dialog.open();
+                    self.dxf_export_dialog = Some(dialog);
+                }
             });
-        });
+            ui.menu_button("Edit", |ui| {
+                if ui.add_enabled(self.app_ctx.history.can_undo(), egui::Button::new("Undo (Ctrl + Z)")).clicked() {
+                    self.run_action(Action::Undo);
+                    ui.close_menu();
+                }
+                if ui.add_enabled(self.app_ctx.history.can_redo(), egui::Button::new("Redo (Ctrl + Y)")).clicked() {
+                    self.run_action(Action::Redo);
+                    ui.close_menu();
+                }
+            });
+            ui.menu_button("Help", |ui| {
+                if ui.button("Keyboard shortcuts (F1)").clicked() {
+                    self.show_shortcuts = true;
+                    ui.close_menu();
+                }
+            });
+        }));
+        self.draw_status_bar_egui(ctx);
+
         // Handle dialog
         if let Some(dialog) = &mut self.file_dialog {
             if dialog.show(ctx).selected() {
@@ -363,30 +2018,146 @@ impl Application<'_> {
                 }
             }
         }
+        if let Some(dialog) = &mut self.obj_export_dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(path) = dialog.path() {
+                    let path = path.to_path_buf();
+                    self.export_obj(&path);
+                }
+            }
+        }
+        if let Some(dialog) = &mut self.dxf_export_dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(path) = dialog.path() {
+                    let path = path.to_path_buf();
+                    self.export_dxf(&path);
+                }
+            }
+        }
+        if let Some(dialog) = &mut self.dxf_import_dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(path) = dialog.path() {
+                    let path = path.to_path_buf();
+                    self.import_dxf(&path);
+                }
+            }
+        }
         egui::Window::new("Options")
             .default_width(300.)
             .show(ctx, |ui| {
                 ui.label("Polygons:");
                 egui::ScrollArea::vertical()
                     .max_height(300.0)
-                    .show(ui, |ui| {
-                        self.app_ctx.polygon_objs.retain_mut(|poly| {
-                            let mut remove_flag = true;
-                            egui::CollapsingHeader::new(poly.polygon().get_name())
+                    .show_viewport(ui, |ui, viewport| {
+                        let mut to_remove: Vec<usize> = Vec::new();
+                        let mut to_duplicate: Option<usize> = None;
+
+                        // Ungrouped polygons first, then groups with their members nested.
+                        // Only the entries inside of the viewport are built, the entries can
+                        // be expanded, so their heights vary and show_rows can't be used.
+                        let collapsed_height = ui.spacing().interact_size.y;
+                        let spacing = ui.spacing().item_spacing.y;
+                        self.sync_entry_heights(collapsed_height);
+                        let top = ui.cursor().top();
+                        for (id, poly) in self.app_ctx.polygon_objs.iter_mut().enumerate() {
+                            if poly.group_id().is_some() {
+                                continue;
+                            }
+
+                            let start = ui.cursor().top();
+                            let height = self.entry_heights[id].1;
+                            if start - top + height < viewport.min.y || start - top > viewport.max.y {
+                                ui.allocate_space(egui::vec2(ui.available_width(), height));
+                                continue;
+                            }
+
+                            match Self::draw_polygon_entry_egui(ui, poly, &self.app_ctx.units) {
+                                EntryAction::Delete => to_remove.push(id),
+                                EntryAction::Duplicate => to_duplicate = Some(id),
+                                EntryAction::None => (),
+                            }
+                            self.entry_heights[id].1 = (ui.cursor().top() - start - spacing).max(0.);
+                        }
+
+                        let mut groups: Vec<usize> = self.app_ctx.polygon_objs
+                            .iter()
+                            .filter_map(|poly| poly.group_id())
+                            .collect();
+                        groups.sort();
+                        groups.dedup();
+
+                        for group_id in groups {
+                            egui::CollapsingHeader::new(format!("Group #{}", group_id))
                                 .default_open(false)
                                 .show(ui, |ui| {
-                                    // Delete button
-                                    if ui.button("Delete").clicked() {
-                                        remove_flag = false;
+                                    for (id, poly) in self.app_ctx.polygon_objs.iter_mut().enumerate() {
+                                        if poly.group_id() != Some(group_id) {
+                                            continue;
+                                        }
+                                        match Self::draw_polygon_entry_egui(ui, poly, &self.app_ctx.units) {
+                                            EntryAction::Delete => to_remove.push(id),
+                                            EntryAction::Duplicate => to_duplicate = Some(id),
+                                            EntryAction::None => (),
+                                        }
                                     }
-
-                                    // Polygon options
-                                    poly.draw_egui(ui);
                                 });
-                            remove_flag
-                        });
+                        }
+
+                        if let Some(id) = to_duplicate {
+                            self.app_ctx.checkpoint();
+                            self.app_ctx.duplicate_polygon(id);
+                        }
+
+                        to_remove.sort();
+                        if !to_remove.is_empty() {
+                            self.app_ctx.checkpoint();
+                        }
+                        for id in to_remove.into_iter().rev() {
+                            self.app_ctx.polygon_objs.remove(id);
+                        }
                     });
 
+                let selected_polygons = self.app_ctx.polygon_objs
+                    .iter()
+                    .filter(|poly| poly.selected_points_count() > 0)
+                    .count();
+                let any_selected_grouped = self.app_ctx.polygon_objs
+                    .iter()
+                    .any(|poly| poly.selected_points_count() > 0 && poly.group_id().is_some());
+                self.draw_measurement_egui(ui);
+                self.draw_units_egui(ui);
+                self.draw_containment_egui(ui);
+                self.draw_commands_egui(ui);
+                ui.horizontal(|ui| {
+                    if ui.button("Find duplicates").clicked() {
+                        let duplicates = self.app_ctx.select_duplicates();
+                        if duplicates.is_empty() {
+                            self.app_ctx.toasts.push("No duplicate polygons");
+                        }
+                        for group in duplicates {
+                            let names: Vec<String> = group
+                                .iter()
+                                .map(|id| self.app_ctx.polygon_objs[*id].polygon().get_name().clone())
+                                .collect();
+                            self.app_ctx.toasts.push(format!("Duplicates: {}", names.join(", ")));
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(selected_polygons >= 2, egui::Button::new("Group"))
+                        .on_hover_text("Group the polygons with selected points")
+                        .clicked() {
+                        self.app_ctx.checkpoint();
+                        self.app_ctx.group_selected();
+                    }
+                    if ui.add_enabled(any_selected_grouped, egui::Button::new("Ungroup"))
+                        .on_hover_text("Dissolve the groups of the selected polygons")
+                        .clicked() {
+                        self.app_ctx.checkpoint();
+                        self.app_ctx.ungroup_selected();
+                    }
+                });
+
 
                 ui.separator();
                 // Pick the drawing method
@@ -403,25 +2174,80 @@ impl Application<'_> {
                 if self.drawing_mode == DrawingMode::CPU {
                     let mut alg = self.line_painter.alg();
                     let mut thickness = self.line_painter.thickness();
-                    egui::ComboBox::from_label("Algorithm")
-                        .selected_text(match alg {
-                            LinePainterAlgorithm::MidPointLine => "MidPointLine",
-                            LinePainterAlgorithm::SymmetricMidPointLine => "SymmetricMidPointLine",
-                            LinePainterAlgorithm::GuptaDoubleStepMidPointLine => "GuptaDoubleStepMidPointLine",
-                            LinePainterAlgorithm::WULine => "XiaolinWULine",
-                        })
-                        .show_ui(ui, |ui| {
-                            ui.selectable_value(&mut alg, LinePainterAlgorithm::MidPointLine, "MidPointLine");
-                            ui.selectable_value(&mut alg, LinePainterAlgorithm::SymmetricMidPointLine, "SymmetricMidPointLine");
-                            ui.selectable_value(&mut alg, LinePainterAlgorithm::GuptaDoubleStepMidPointLine, "GuptaDoubleStepMidPointLine");
-                            ui.selectable_value(&mut alg, LinePainterAlgorithm::WULine, "XiaolinWULine");
-                        });
+                    Self::draw_algorithm_combo_egui(ui, "Algorithm", &mut alg);
+
+                    let mut compare = self.comparison_alg.is_some();
+                    ui.checkbox(&mut compare, "Compare algorithms (split view)");
+                    match (compare, self.comparison_alg.as_mut()) {
+                        (true, Some(right_alg)) => Self::draw_algorithm_combo_egui(ui, "Right half algorithm", right_alg),
+                        (true, None) => self.comparison_alg = Some(LinePainterAlgorithm::WULine),
+                        (false, _) => self.comparison_alg = None,
+                    }
 
                     ui.add(egui::Slider::new(&mut thickness, 1.0..=10.0).text("Thickness"));
+                    self.draw_line_style_egui(ui);
+                    ui.checkbox(&mut self.show_loupe, "Pixel loupe");
+                    egui::ComboBox::from_label("Smoothing quality")
+                        .selected_text(format!("{}x", self.supersampling))
+                        .show_ui(ui, |ui| {
+                            for factor in [1, 2, 4] {
+                                ui.selectable_value(&mut self.supersampling, factor, format!("{}x", factor));
+                            }
+                        });
                     self.line_painter.set_alg(alg);
                     self.line_painter.set_thickness(thickness);
+
+                    egui::CollapsingHeader::new("Pixel stats")
+                        .default_open(false)
+                        .show(ui, |ui| self.draw_pixel_stats_egui(ui));
                 }
                 ui.add(egui::Checkbox::new(&mut self.gpu_antialiasing, "GPU Antialiasing (MSAA 8)"));
+
+                let color = self.app_ctx.snap_indicator.color();
+                let mut rgb = [color.r, color.g, color.b];
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgb(&mut rgb);
+                    ui.label("Snap indicator color");
+                });
+                self.app_ctx.snap_indicator.set_color(sf::Color::rgb(rgb[0], rgb[1], rgb[2]));
+
+                ui.checkbox(&mut self.app_ctx.show_drag_ghost, "Show pre-drag ghost");
+                ui.checkbox(&mut self.app_ctx.edge_snap, "Snap new points to polygon edges")
+                    .on_hover_text("Adjacent polygons share the boundary, hold Alt to place a point freely");
+                ui.checkbox(&mut self.app_ctx.midpoint_snap, "Snap to edge midpoints")
+                    .on_hover_text("Placed and dragged points snap to the midpoints of nearby edges, hold Alt to disable");
+                ui.checkbox(&mut self.app_ctx.insert_shared_vertices, "Insert shared vertices after a drag")
+                    .on_hover_text("An edge passing through a vertex of another polygon gets a vertex there as well");
+                ui.checkbox(&mut self.app_ctx.merge_collinear, "Merge collinear edges after edits")
+                    .on_hover_text("Remove the vertices between two collinear edges after a drag or a point removal, selected points are kept");
+                ui.checkbox(&mut self.app_ctx.indices_only, "Show vertices as indices only")
+                    .on_hover_text("Hide the point circles to declutter dense polygons");
+                self.draw_shadow_egui(ui);
+                ui.checkbox(&mut self.test_point, "Test point")
+                    .on_hover_text("Outline the polygons containing the cursor, the boundary counts as inside");
+                ui.checkbox(&mut self.show_normals, "Show vertex vectors (debug)")
+                    .on_hover_text("Green: normal, blue: previous normal, pink: offset vector, yellow: label direction");
+                ui.horizontal(|ui| {
+                    ui.label(format!("Zoom: {:.0}%", 100. / self.zoom));
+                    if ui.button("Reset view").clicked() {
+                        self.reset_camera();
+                    }
+                });
+
+                ui.separator();
+                self.draw_grid_options_egui(ui);
+                ui.separator();
+
+                self.draw_default_style_egui(ui);
+                ui.separator();
+
+                let mut min_area = self.app_ctx.polygon_obj_factory.min_area();
+                ui.add(egui::Slider::new(&mut min_area, 0.0..=1000.0).text("Min polygon area"));
+                self.app_ctx.polygon_obj_factory.set_min_area(min_area);
+                let mut split_at_crossings = self.app_ctx.polygon_obj_factory.split_at_crossings();
+                ui.checkbox(&mut split_at_crossings, "Split at crossings")
+                    .on_hover_text("While adding a polygon, crossing its own edges cuts off the loop as a separate polygon");
+                self.app_ctx.polygon_obj_factory.set_split_at_crossings(split_at_crossings);
                 if self.gpu_antialiasing {
                     unsafe {
                         gl::glEnable(gl::GL_MULTISAMPLE_ARB);
@@ -433,6 +2259,7 @@ impl Application<'_> {
                 }
                 ui.separator();
 
+                let mut resume: Option<usize> = None;
                 let mut polygon_flag = false;
                 let mut polygon_with_selected_points = 0;
                 for (id, poly) in self.app_ctx.polygon_objs.iter().enumerate() {
@@ -457,7 +2284,39 @@ impl Application<'_> {
 
                 ui.label("Selected polygon:");
                 if polygon_flag {
+                    let poly = &mut self.app_ctx.polygon_objs[polygon_with_selected_points];
+                    if ui.add_enabled(poly.selected_points_count() >= 2, egui::Button::new("Flatten selected points"))
+                        .on_hover_text("Project the selected points onto their best fitting line")
+                        .clicked() {
+                        let before = poly.snapshot();
+                        match poly.flatten_selected_points() {
+                            Ok(()) => self.app_ctx.history.push(Change::Polygon(polygon_with_selected_points, before)),
+                            Err(err) => self.app_ctx.toasts.push(format!("Can't flatten: {}", err)),
+                        }
+                    }
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Pin").on_hover_text("Selected points stay in place during transforms").clicked() {
+                            self.app_ctx.history.push(Change::Polygon(polygon_with_selected_points, poly.snapshot()));
+                            poly.set_selected_points_pinned(true);
+                        }
+                        if ui.button("Unpin").clicked() {
+                            self.app_ctx.history.push(Change::Polygon(polygon_with_selected_points, poly.snapshot()));
+                            poly.set_selected_points_pinned(false);
+                        }
+                    });
+
+                    if ui.button("Continue drawing").on_hover_text("Append points after the last vertex, click the first one to close").clicked() {
+                        resume = Some(polygon_with_selected_points);
+                    }
+
+                    if ui.button("Add to library").clicked() {
+                        let raw = self.app_ctx.polygon_objs[polygon_with_selected_points].get_raw();
+                        self.shape_library.add(raw);
+                    }
+
                     if ui.button("Delete").clicked() {
+                        self.app_ctx.checkpoint();
                         self.app_ctx.polygon_objs.remove(polygon_with_selected_points);
                     } else {
                         self.app_ctx.polygon_objs[polygon_with_selected_points].draw_polygon_options_egui(ui);
@@ -466,6 +2325,11 @@ impl Application<'_> {
                     ui.label("None");
                 }
 
+                if let Some(id) = resume.filter(|id| *id < self.app_ctx.polygon_objs.len()) {
+                    let _prev = self.curr_state.take().unwrap().on_cancel_btn(&mut self.app_ctx);
+                    self.curr_state = Some(Box::new(ResumePolygonState::new(id, &mut self.app_ctx)));
+                }
+
                 ui.separator();
 
                 if ui.button("Add a polygon").clicked() {
@@ -478,6 +2342,10 @@ impl Application<'_> {
 
                 ui.separator();
 
+                self.draw_library_egui(ui);
+
+                ui.separator();
+
                 ui.label(format!("State: {}", self.curr_state.as_ref().unwrap().state_name()));
 
                 if ui.button("Cancel").clicked() {
@@ -485,6 +2353,39 @@ impl Application<'_> {
                 }
             });
 
+        self.app_ctx.toasts.draw_egui(ctx);
+
+        if let Some(text) = self.app_ctx.hud.as_ref() {
+            let mouse = self.mouse_pixel;
+            egui::Area::new("Hud")
+                .fixed_pos(egui::pos2(mouse.x as f32 + 16., mouse.y as f32 + 16.))
+                .interactable(false)
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(text);
+                    });
+                });
+        }
+
+        if self.drawing_mode == DrawingMode::CPU {
+            self.draw_loupe_egui(ctx);
+        }
+
+        self.draw_coords_dialog_egui(ctx);
+
+        egui::Window::new("Keyboard shortcuts")
+            .open(&mut self.show_shortcuts)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts_grid").striped(true).show(ui, |ui| {
+                    for shortcut in shortcuts::SHORTCUTS.iter() {
+                        ui.strong(shortcut.keys);
+                        ui.label(shortcut.description);
+                        ui.end_row();
+                    }
+                });
+            });
+
         self.egui_rects.clear();
         ctx.memory(|mem| {
             if let Some(rect) = mem.area_rect("Options") {
@@ -493,6 +2394,125 @@ impl Application<'_> {
             if let Some(rect) = mem.area_rect("Top") {
                 self.egui_rects.push(rect);
             }
+            if let Some(rect) = mem.area_rect("Status bar") {
+                self.egui_rects.push(rect);
+            }
+            if self.show_shortcuts {
+                if let Some(rect) = mem.area_rect("Keyboard shortcuts") {
+                    self.egui_rects.push(rect);
+                }
+            }
+            if self.show_coords_dialog {
+                if let Some(rect) = mem.area_rect("Polygon from coordinates") {
+                    self.egui_rects.push(rect);
+                }
+            }
+            if self.show_loupe && self.drawing_mode == DrawingMode::CPU {
+                if let Some(rect) = mem.area_rect("Pixel loupe") {
+                    self.egui_rects.push(rect);
+                }
+            }
+            if !self.app_ctx.toasts.is_empty() {
+                if let Some(rect) = mem.area_rect("Toasts") {
+                    self.egui_rects.push(rect);
+                }
+            }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(app_ctx: &mut AppContext, x: f32, y: f32) {
+        let p = sf::Vector2f::new;
+        let mut poly = PolygonObject::from(
+            polygon::Polygon::create(vec![p(x, y), p(x + 100., y), p(x + 100., y + 100.), p(x, y + 100.)])
+        );
+        poly.assert_ccw();
+        app_ctx.polygon_objs.push(poly);
+    }
+
+    #[test]
+    fn find_polygon_follows_removals_and_undo() {
+        let mut app_ctx = AppContext::new(800, 600);
+        square(&mut app_ctx, 0., 0.);
+        square(&mut app_ctx, 200., 0.);
+        let uid = app_ctx.polygon_objs[1].uid();
+
+        app_ctx.duplicate_polygon(1);
+        assert_ne!(app_ctx.polygon_objs[2].uid(), uid);
+
+        app_ctx.checkpoint();
+        app_ctx.polygon_objs.remove(0);
+        assert_eq!(app_ctx.find_polygon(uid), Some(0));
+        app_ctx.polygon_objs.remove(0);
+        assert_eq!(app_ctx.find_polygon(uid), None);
+
+        assert!(app_ctx.undo());
+        assert_eq!(app_ctx.find_polygon(uid), Some(1));
+    }
+
+    #[test]
+    fn registry_subdivides_and_simplifies_the_selected_polygon() {
+        let mut app_ctx = AppContext::new(800, 600);
+        square(&mut app_ctx, 0., 0.);
+        app_ctx.polygon_objs[0].select_all_points();
+        let registry = CommandRegistry::new();
+        let run = |name: &str, app_ctx: &mut AppContext| {
+            registry.commands().iter().find(|command| command.name() == name).unwrap().run(app_ctx);
+        };
+
+        app_ctx.subdivide_iterations = 2;
+        run("Subdivide", &mut app_ctx);
+        assert_eq!(app_ctx.polygon_objs[0].polygon().points_count(), 16);
+
+        app_ctx.simplify_target = 4;
+        run("Simplify", &mut app_ctx);
+        assert_eq!(app_ctx.polygon_objs[0].polygon().points_count(), 4);
+
+        assert!(app_ctx.undo());
+        assert_eq!(app_ctx.polygon_objs[0].polygon().points_count(), 16);
+    }
+
+    #[test]
+    fn near_identical_squares_are_selected_as_duplicates() {
+        let mut app_ctx = AppContext::new(800, 600);
+        square(&mut app_ctx, 0., 0.);
+        // The left edge leans by less than the epsilon, the top left corner has the smallest
+        // x in one square and the bottom left one in the other
+        square(&mut app_ctx, 0., 0.);
+        let bottom_left = (0..4).find(|&id| app_ctx.polygon_objs[1].polygon().get_point_pos(id) == sf::Vector2f::new(0., 100.)).unwrap();
+        app_ctx.polygon_objs[1].set_points_pos(&[(bottom_left as usize, sf::Vector2f::new(-0.2, 100.))]);
+        square(&mut app_ctx, 300., 0.);
+
+        assert_eq!(app_ctx.select_duplicates(), vec![vec![0, 1]]);
+        let selected: Vec<bool> = app_ctx.polygon_objs.iter().map(|poly| poly.selected_points_count() > 0).collect();
+        assert_eq!(selected, [true, true, false]);
+    }
+
+    #[test]
+    fn union_all_merges_overlapping_squares() {
+        let mut app_ctx = AppContext::new(800, 600);
+        square(&mut app_ctx, 0., 0.);
+        square(&mut app_ctx, 50., 50.);
+        square(&mut app_ctx, 300., 300.);
+
+        assert_eq!(app_ctx.union_all(), (2, 0));
+        assert_eq!(app_ctx.polygon_objs.len(), 2);
+        assert!((app_ctx.polygon_objs[1].polygon().area() - 17500.).abs() < 1e-2);
+    }
+
+    #[test]
+    fn union_all_skips_corner_touching_squares() {
+        let mut app_ctx = AppContext::new(800, 600);
+        square(&mut app_ctx, 0., 0.);
+        square(&mut app_ctx, 100., 100.);
+        app_ctx.polygon_objs[0].set_name(String::from("Kept"));
+
+        assert_eq!(app_ctx.union_all(), (0, 0));
+        assert_eq!(app_ctx.polygon_objs.len(), 2);
+        assert_eq!(app_ctx.polygon_objs[0].polygon().get_name(), "Kept");
+    }
+}
@@ -0,0 +1,213 @@
+use crate::app::AppContext;
+use crate::history::Change;
+use crate::style;
+
+/// An operation shown as a button in the "Commands" section of the options window.
+/// Commands are responsible for recording their changes in the history.
+pub trait EditorCommand {
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str {
+        ""
+    }
+
+    fn run(&self, app_ctx: &mut AppContext);
+}
+
+pub struct CommandRegistry {
+    commands: Vec<Box<dyn EditorCommand>>,
+}
+
+impl CommandRegistry {
+    /// Creates a registry with the built-in commands.
+    pub fn new() -> CommandRegistry {
+        let mut registry = CommandRegistry { commands: Vec::new() };
+        registry.register(Box::new(SubdivideCommand));
+        registry.register(Box::new(SimplifyCommand));
+        registry.register(Box::new(RemoveDuplicatesCommand));
+        registry.register(Box::new(UnionAllCommand));
+        registry.register(Box::new(RemoveSpikesCommand));
+        registry.register(Box::new(MirrorCommand { left_right: true }));
+        registry.register(Box::new(MirrorCommand { left_right: false }));
+        registry
+    }
+
+    pub fn register(&mut self, command: Box<dyn EditorCommand>) {
+        self.commands.push(command);
+    }
+
+    pub fn commands(&self) -> &[Box<dyn EditorCommand>] {
+        &self.commands
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> CommandRegistry {
+        CommandRegistry::new()
+    }
+}
+
+fn selected_polygon(app_ctx: &AppContext) -> Option<usize> {
+    app_ctx.polygon_objs.iter().position(|poly| poly.selected_points_count() > 0)
+}
+
+/// The number of passes is taken from AppContext::subdivide_iterations.
+pub struct SubdivideCommand;
+
+impl EditorCommand for SubdivideCommand {
+    fn name(&self) -> &str {
+        "Subdivide"
+    }
+
+    fn description(&self) -> &str {
+        "Insert a midpoint on every edge of the selected polygon"
+    }
+
+    fn run(&self, app_ctx: &mut AppContext) {
+        let id = match selected_polygon(app_ctx) {
+            Some(id) => id,
+            None => return app_ctx.toasts.push("No polygon selected"),
+        };
+        let poly = &mut app_ctx.polygon_objs[id];
+        app_ctx.history.push(Change::Polygon(id, poly.snapshot()));
+        poly.subdivide(app_ctx.subdivide_iterations);
+    }
+}
+
+/// The target vertex count is taken from AppContext::simplify_target.
+pub struct SimplifyCommand;
+
+impl EditorCommand for SimplifyCommand {
+    fn name(&self) -> &str {
+        "Simplify"
+    }
+
+    fn description(&self) -> &str {
+        "Remove the least significant vertices of the selected polygon"
+    }
+
+    fn run(&self, app_ctx: &mut AppContext) {
+        let id = match selected_polygon(app_ctx) {
+            Some(id) => id,
+            None => return app_ctx.toasts.push("No polygon selected"),
+        };
+        let target = app_ctx.simplify_target;
+        let poly = &mut app_ctx.polygon_objs[id];
+        if poly.polygon().points_count() <= target {
+            return app_ctx.toasts.push(format!("The polygon has at most {} vertices already", target));
+        }
+        let before = poly.snapshot();
+        match poly.simplify_to(target) {
+            Ok(()) => app_ctx.history.push(Change::Polygon(id, before)),
+            Err(err) => app_ctx.toasts.push(format!("Can't simplify: {}", err)),
+        }
+    }
+}
+
+/// The vertex order of the result is chosen with AppContext::mirror_winding.
+pub struct MirrorCommand {
+    pub left_right: bool,
+}
+
+impl EditorCommand for MirrorCommand {
+    fn name(&self) -> &str {
+        if self.left_right { "Mirror left-right" } else { "Mirror top-bottom" }
+    }
+
+    fn description(&self) -> &str {
+        if self.left_right {
+            "Flip the selected polygon around its vertical axis"
+        } else {
+            "Flip the selected polygon around its horizontal axis"
+        }
+    }
+
+    fn run(&self, app_ctx: &mut AppContext) {
+        let id = match selected_polygon(app_ctx) {
+            Some(id) => id,
+            None => return app_ctx.toasts.push("No polygon selected"),
+        };
+        let winding = app_ctx.mirror_winding;
+        let poly = &mut app_ctx.polygon_objs[id];
+        let before = poly.snapshot();
+        match poly.mirror(self.left_right, winding) {
+            Ok(()) => app_ctx.history.push(Change::Polygon(id, before)),
+            Err(err) => app_ctx.toasts.push(format!("Can't mirror: {}", err)),
+        }
+    }
+}
+
+pub struct RemoveSpikesCommand;
+
+impl EditorCommand for RemoveSpikesCommand {
+    fn name(&self) -> &str {
+        "Remove spikes"
+    }
+
+    fn description(&self) -> &str {
+        "Remove the needle-like vertices of the selected polygon"
+    }
+
+    fn run(&self, app_ctx: &mut AppContext) {
+        let id = match selected_polygon(app_ctx) {
+            Some(id) => id,
+            None => return app_ctx.toasts.push("No polygon selected"),
+        };
+        let poly = &mut app_ctx.polygon_objs[id];
+        let before = poly.snapshot();
+        match poly.remove_spikes(style::SPIKE_ANGLE) {
+            Ok(0) => app_ctx.toasts.push("No spikes found"),
+            Ok(removed) => {
+                app_ctx.history.push(Change::Polygon(id, before));
+                app_ctx.toasts.push(format!("Removed {} spike(s)", removed));
+            }
+            Err(err) => app_ctx.toasts.push(format!("Can't remove the spikes: {}", err)),
+        }
+    }
+}
+
+pub struct UnionAllCommand;
+
+impl EditorCommand for UnionAllCommand {
+    fn name(&self) -> &str {
+        "Union all"
+    }
+
+    fn description(&self) -> &str {
+        "Merge every group of overlapping polygons into one polygon"
+    }
+
+    fn run(&self, app_ctx: &mut AppContext) {
+        app_ctx.checkpoint();
+        let (merged, holes) = app_ctx.union_all();
+        if merged == 0 {
+            app_ctx.history.discard_last();
+            return app_ctx.toasts.push("No overlapping polygons");
+        }
+        app_ctx.toasts.push(format!("Merged {} polygons, {} polygon(s) left", merged, app_ctx.polygon_objs.len()));
+        if holes > 0 {
+            app_ctx.toasts.push(format!("Dropped {} hole(s), the polygons can't have holes", holes));
+        }
+    }
+}
+
+pub struct RemoveDuplicatesCommand;
+
+impl EditorCommand for RemoveDuplicatesCommand {
+    fn name(&self) -> &str {
+        "Remove duplicates"
+    }
+
+    fn description(&self) -> &str {
+        "Keep only the first polygon of every group of identical polygons"
+    }
+
+    fn run(&self, app_ctx: &mut AppContext) {
+        app_ctx.checkpoint();
+        let removed = app_ctx.remove_duplicates();
+        if removed == 0 {
+            app_ctx.history.discard_last();
+        }
+        app_ctx.toasts.push(format!("Removed {} duplicate polygon(s)", removed));
+    }
+}
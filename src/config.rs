@@ -0,0 +1,60 @@
+use std::fs;
+use serde::{Serialize, Deserialize};
+use serde_json::from_str;
+use crate::app::DrawingMode;
+use crate::line_alg::LinePainterAlgorithm;
+use crate::style;
+
+pub const CONFIG_PATH: &str = "res/config.json";
+
+/// Startup settings, read once when the application is created. Missing fields keep
+/// their built-in values.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub ui_scale: f32,
+    pub window_width: u32,
+    pub window_height: u32,
+    pub drawing_mode: DrawingMode,
+    pub line_algorithm: LinePainterAlgorithm,
+    // MSAA of the GPU rendering
+    pub antialiasing: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            ui_scale: 0.8,
+            window_width: style::WIN_SIZE_X,
+            window_height: style::WIN_SIZE_Y,
+            drawing_mode: DrawingMode::GPU,
+            line_algorithm: LinePainterAlgorithm::MidPointLine,
+            antialiasing: false,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config, a missing or malformed file results in the built-in settings.
+    pub fn load() -> Config {
+        let contents = match fs::read_to_string(CONFIG_PATH) {
+            Ok(contents) => contents,
+            Err(err) => {
+                log::warn!("No config loaded from {} ({}), using the defaults", CONFIG_PATH, err);
+                return Config::default();
+            }
+        };
+
+        match from_str::<Config>(&contents) {
+            Ok(config) if config.window_width == 0 || config.window_height == 0 => {
+                log::warn!("Error in the config: the window size can't be zero, using the defaults");
+                Config::default()
+            }
+            Ok(config) => config,
+            Err(err) => {
+                log::warn!("Error parsing the config, using the defaults: {}", err);
+                Config::default()
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt::Write;
+use crate::polygon::{Polygon, PolygonObject, RawPolygonCoords};
+use super::sf;
+
+/// Polygons read from a DXF file together with the descriptions of the skipped entities.
+pub struct DxfImport {
+    pub polygons: Vec<RawPolygonCoords>,
+    pub warnings: Vec<String>,
+}
+
+// AutoCAD 2000
+const DXF_VERSION: &str = "AC1015";
+
+// Characters not allowed in the DXF layer names
+const LAYER_FORBIDDEN: &[char] = &['<', '>', '/', '\\', '"', ':', ';', '?', '*', '|', '=', '`'];
+
+/// Writes every polygon as a LWPOLYLINE on a layer named after it. The y axis is flipped to
+/// point up, the arcs are written as vertex bulges.
+pub fn polygons_to_dxf(polygons: &[PolygonObject]) -> String {
+    let mut result = String::new();
+    let mut pair = |code: i32, value: &str| {
+        let _ = writeln!(result, "{}\n{}", code, value);
+    };
+
+    // LWPOLYLINE came with R14, the readers assume R12 without the version
+    pair(0, "SECTION");
+    pair(2, "HEADER");
+    pair(9, "$ACADVER");
+    pair(1, DXF_VERSION);
+    pair(0, "ENDSEC");
+
+    pair(0, "SECTION");
+    pair(2, "ENTITIES");
+    for poly in polygons {
+        let polygon = poly.polygon();
+        let layer: String = polygon.get_name().replace(LAYER_FORBIDDEN, "_");
+
+        pair(0, "LWPOLYLINE");
+        pair(100, "AcDbEntity");
+        pair(8, &layer);
+        pair(100, "AcDbPolyline");
+        pair(90, &polygon.points_count().to_string());
+        pair(70, if polygon.is_open() { "0" } else { "1" });
+        for id in 0..polygon.points_count() as isize {
+            let pos = polygon.get_point_pos(id);
+            pair(10, &pos.x.to_string());
+            pair(20, &(-pos.y).to_string());
+            // The flip turns the arcs the other way
+            let bulge = polygon.get_edge_bulge(id);
+            if bulge != 0. {
+                pair(42, &(-bulge).to_string());
+            }
+        }
+    }
+    pair(0, "ENDSEC");
+    pair(0, "EOF");
+
+    result
+}
+
+// LWPOLYLINE being read
+struct Polyline {
+    layer: String,
+    closed: bool,
+    coords: Vec<sf::Vector2f>,
+    bulges: Vec<f32>,
+}
+
+impl Polyline {
+    fn new() -> Polyline {
+        Polyline { layer: String::new(), closed: false, coords: Vec::new(), bulges: Vec::new() }
+    }
+
+    fn finish(mut self, id: usize, import: &mut DxfImport) {
+        // A repeated vertex makes a zero length edge, the arc of the later copy is kept
+        let mut i = 1;
+        while i < self.coords.len() {
+            if self.coords[i] == self.coords[i - 1] {
+                self.coords.remove(i - 1);
+                self.bulges.remove(i - 1);
+            } else {
+                i += 1;
+            }
+        }
+        // Some writers close the outline by repeating the first vertex instead of the flag
+        if self.coords.len() > 1 && self.coords.first() == self.coords.last() {
+            self.coords.pop();
+            self.bulges.pop();
+            self.closed = true;
+        }
+        if self.coords.len() < 3 {
+            import.warnings.push(format!("LWPOLYLINE #{} has less than 3 vertices, skipped", id));
+            return;
+        }
+
+        let mut raw = RawPolygonCoords::from_sf_points(self.coords);
+        if !self.layer.is_empty() {
+            raw.name = Some(self.layer);
+        }
+        if self.bulges.iter().any(|b| *b != 0.) {
+            raw.bulges = self.bulges;
+        }
+        raw.open = !self.closed;
+
+        let mut polygon = Polygon::new();
+        polygon.set_points_from_raw(raw.clone());
+        if polygon.is_self_crossing() {
+            import.warnings.push(format!("LWPOLYLINE #{} is self-crossing, skipped", id));
+            return;
+        }
+        if polygon.area() == 0. {
+            import.warnings.push(format!("LWPOLYLINE #{} has no area, skipped", id));
+            return;
+        }
+        import.polygons.push(raw);
+    }
+}
+
+/// Reads the LWPOLYLINE entities of the ENTITIES section, the layer names become the polygon
+/// names. The other entities are skipped with a warning.
+pub fn parse_dxf(text: &str) -> Result<DxfImport, String> {
+    let mut lines: Vec<&str> = text.lines().map(|line| line.trim()).collect();
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    if !lines.len().is_multiple_of(2) {
+        return Err(String::from("The file isn't made of group code and value pairs"));
+    }
+
+    let mut import = DxfImport { polygons: Vec::new(), warnings: Vec::new() };
+    let mut in_entities = false;
+    let mut section_start = false;
+    let mut polyline: Option<Polyline> = None;
+    let mut polyline_count = 0;
+
+    for (pair_id, pair) in lines.chunks_exact(2).enumerate() {
+        let code: i32 = pair[0]
+            .parse()
+            .map_err(|_| format!("Line {}: \"{}\" is not a group code", 2 * pair_id + 1, pair[0]))?;
+        let value = pair[1];
+        let number = || -> Result<f32, String> {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| format!("Line {}: \"{}\" is not a number", 2 * pair_id + 2, value))
+        };
+
+        if code == 0 {
+            if let Some(finished) = polyline.take() {
+                polyline_count += 1;
+                finished.finish(polyline_count, &mut import);
+            }
+            match value {
+                "SECTION" => section_start = true,
+                "ENDSEC" => in_entities = false,
+                "EOF" => break,
+                "LWPOLYLINE" if in_entities => polyline = Some(Polyline::new()),
+                other if in_entities => import.warnings.push(format!("Unsupported entity {} skipped", other)),
+                _ => (),
+            }
+            continue;
+        }
+        if code == 2 && section_start {
+            in_entities = value == "ENTITIES";
+            section_start = false;
+            continue;
+        }
+
+        if let Some(polyline) = polyline.as_mut() {
+            match code {
+                8 => polyline.layer = value.to_string(),
+                70 => polyline.closed = value.parse::<i32>().is_ok_and(|flags| flags & 1 != 0),
+                10 => {
+                    polyline.coords.push(sf::Vector2f::new(number()?, 0.));
+                    polyline.bulges.push(0.);
+                }
+                // The y axis points up in DXF, the flip turns the arcs the other way
+                20 => {
+                    let y = -number()?;
+                    if let Some(coord) = polyline.coords.last_mut() {
+                        coord.y = y;
+                    }
+                }
+                42 => {
+                    let bulge = -number()?;
+                    if let Some(last) = polyline.bulges.last_mut() {
+                        *last = bulge;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    if let Some(finished) = polyline.take() {
+        polyline_count += 1;
+        finished.finish(polyline_count, &mut import);
+    }
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_polygon(raw: RawPolygonCoords) -> Polygon<'static> {
+        let mut polygon = Polygon::new();
+        polygon.set_points_from_raw(raw);
+        polygon
+    }
+
+    #[test]
+    fn export_starts_with_the_version_header() {
+        let dxf = polygons_to_dxf(&[]);
+        assert!(dxf.starts_with("0\nSECTION\n2\nHEADER\n9\n$ACADVER\n1\nAC1015\n0\nENDSEC\n"));
+        assert!(dxf.ends_with("0\nEOF\n"));
+    }
+
+    #[test]
+    fn round_trip_keeps_points_names_and_arcs() {
+        let p = sf::Vector2f::new;
+        let points = vec![p(0., 0.), p(100., 0.), p(100., 50.5), p(0., 100.)];
+        let mut polygon = Polygon::create(points.clone());
+        polygon.set_name(String::from("Roof: left"));
+        polygon.set_edge_bulge(1, 0.5);
+        let mut open = Polygon::create(vec![p(200., 0.), p(300., 0.), p(250., 80.)]);
+        open.set_open(true);
+
+        let dxf = polygons_to_dxf(&[PolygonObject::from(polygon), PolygonObject::from(open)]);
+        let import = parse_dxf(&dxf).unwrap();
+        assert!(import.warnings.is_empty());
+        assert_eq!(import.polygons.len(), 2);
+
+        assert_eq!(import.polygons[0].name.as_deref(), Some("Roof_ left"));
+        let mut polygons = import.polygons.into_iter().map(to_polygon);
+        let polygon = polygons.next().unwrap();
+        assert!(!polygon.is_open());
+        for (id, pos) in points.iter().enumerate() {
+            assert_eq!(polygon.get_point_pos(id as isize), *pos);
+        }
+        assert_eq!(polygon.get_edge_bulge(0), 0.);
+        assert_eq!(polygon.get_edge_bulge(1), 0.5);
+
+        let open = polygons.next().unwrap();
+        assert!(open.is_open());
+        assert_eq!(open.points_count(), 3);
+    }
+
+    // The circle and the two vertex polyline are both skipped with a warning
+    #[test]
+    fn parse_skips_unsupported_entities() {
+        let dxf = "0\nSECTION\n2\nENTITIES\n\
+                   0\nCIRCLE\n8\n0\n10\n1\n20\n2\n40\n3\n\
+                   0\nLWPOLYLINE\n8\nA\n90\n2\n70\n1\n10\n0\n20\n0\n10\n5\n20\n0\n\
+                   0\nENDSEC\n0\nEOF\n";
+        let import = parse_dxf(dxf).unwrap();
+        assert!(import.polygons.is_empty());
+        assert_eq!(import.warnings.len(), 2);
+    }
+
+    // The outline is closed by repeating the first vertex, the second one is doubled
+    #[test]
+    fn parse_cleans_up_repeated_vertices() {
+        let dxf = "0\nSECTION\n2\nENTITIES\n\
+                   0\nLWPOLYLINE\n8\nA\n90\n6\n70\n0\n\
+                   10\n0\n20\n0\n10\n100\n20\n0\n10\n100\n20\n0\n42\n0.5\n\
+                   10\n100\n20\n100\n10\n0\n20\n100\n10\n0\n20\n0\n\
+                   0\nLWPOLYLINE\n8\nB\n90\n3\n70\n1\n10\n0\n20\n0\n10\n50\n20\n0\n10\n100\n20\n0\n\
+                   0\nENDSEC\n0\nEOF\n";
+        let import = parse_dxf(dxf).unwrap();
+        // The collinear one has no area
+        assert_eq!(import.warnings.len(), 1);
+        assert_eq!(import.polygons.len(), 1);
+
+        let polygon = to_polygon(import.polygons.into_iter().next().unwrap());
+        assert!(!polygon.is_open());
+        assert_eq!(polygon.points_count(), 4);
+        assert_eq!(polygon.get_point_pos(1), sf::Vector2f::new(100., 0.));
+        assert_eq!(polygon.get_edge_bulge(0), 0.);
+        assert_eq!(polygon.get_edge_bulge(1), -0.5);
+        assert_eq!(polygon.get_edge_bulge(3), 0.);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_files() {
+        assert!(parse_dxf("0\nSECTION\n2").is_err());
+        assert!(parse_dxf("x\nSECTION\n").is_err());
+        assert!(parse_dxf("0\nSECTION\n2\nENTITIES\n0\nLWPOLYLINE\n10\nabc\n").is_err());
+    }
+}
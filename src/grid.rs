@@ -0,0 +1,124 @@
+use sfml::graphics::RenderTarget;
+use super::sf;
+use super::style;
+
+/// Background grid. Its nodes lie at origin + (i, j) * spacing.
+pub struct Grid {
+    pub enabled: bool,
+    // Placed and dragged points snap to the grid nodes
+    pub snap_enabled: bool,
+    pub spacing: f32,
+    pub origin: sf::Vector2f,
+}
+
+impl Grid {
+    pub fn new() -> Grid {
+        Grid {
+            enabled: false,
+            snap_enabled: false,
+            spacing: style::GRID_SPACING,
+            origin: sf::Vector2f::new(0., 0.),
+        }
+    }
+
+    /// Returns the grid node closest to pos.
+    pub fn snap(&self, pos: sf::Vector2f) -> sf::Vector2f {
+        let rel = (pos - self.origin) / self.spacing;
+        sf::Vector2f::new(rel.x.round(), rel.y.round()) * self.spacing + self.origin
+    }
+
+    /// Snaps pos to the grid if snapping is enabled.
+    pub fn apply_snap(&self, pos: sf::Vector2f) -> sf::Vector2f {
+        if self.snap_enabled {
+            self.snap(pos)
+        } else {
+            pos
+        }
+    }
+
+    /// Returns the coordinates of the vertical and the horizontal grid lines visible
+    /// in the (min, max) region.
+    pub fn lines(&self, min: sf::Vector2f, max: sf::Vector2f) -> (Vec<f32>, Vec<f32>) {
+        let mut xs = Vec::new();
+        let mut x = min.x + (self.origin.x - min.x).rem_euclid(self.spacing);
+        while x <= max.x {
+            xs.push(x);
+            x += self.spacing;
+        }
+
+        let mut ys = Vec::new();
+        let mut y = min.y + (self.origin.y - min.y).rem_euclid(self.spacing);
+        while y <= max.y {
+            ys.push(y);
+            y += self.spacing;
+        }
+        (xs, ys)
+    }
+
+    /// Draws the lines visible through the current view of the target.
+    pub fn draw(&self, target: &mut dyn RenderTarget) {
+        if !self.enabled {
+            return;
+        }
+
+        let view = target.view();
+        let min = view.center() - view.size() / 2.;
+        let max = view.center() + view.size() / 2.;
+
+        // Too dense to be of any use when zoomed out
+        let pixels_per_unit = target.size().x as f32 / view.size().x;
+        if self.spacing * pixels_per_unit < style::GRID_MIN_SPACING {
+            return;
+        }
+
+        let (xs, ys) = self.lines(min, max);
+
+        let mut vertices: Vec<sf::Vertex> = Vec::with_capacity(2 * (xs.len() + ys.len()));
+        for x in xs {
+            vertices.push(sf::Vertex::with_pos_color(sf::Vector2f::new(x, min.y), style::GRID_COLOR));
+            vertices.push(sf::Vertex::with_pos_color(sf::Vector2f::new(x, max.y), style::GRID_COLOR));
+        }
+        for y in ys {
+            vertices.push(sf::Vertex::with_pos_color(sf::Vector2f::new(min.x, y), style::GRID_COLOR));
+            vertices.push(sf::Vertex::with_pos_color(sf::Vector2f::new(max.x, y), style::GRID_COLOR));
+        }
+        target.draw_primitives(&vertices, sf::PrimitiveType::LINES, &sf::RenderStates::default());
+    }
+
+    /// Rasterizes the lines into the CPU framebuffer, the world point origin is drawn
+    /// at the top left corner of the image and the world is scaled by scale.
+    pub fn draw_into_image(&self, image: &mut sf::Image, origin: sf::Vector2f, scale: f32) {
+        if !self.enabled || self.spacing * scale < style::GRID_MIN_SPACING {
+            return;
+        }
+
+        let size = image.size();
+        let max = origin + sf::Vector2f::new(size.x as f32, size.y as f32) / scale;
+        let (xs, ys) = self.lines(origin, max);
+
+        for x in xs {
+            let x = ((x - origin.x) * scale) as u32;
+            if x >= size.x {
+                continue;
+            }
+            for y in 0..size.y {
+                unsafe { image.set_pixel(x, y, style::GRID_COLOR); }
+            }
+        }
+        for y in ys {
+            let y = ((y - origin.y) * scale) as u32;
+            if y >= size.y {
+                continue;
+            }
+            for x in 0..size.x {
+                unsafe { image.set_pixel(x, y, style::GRID_COLOR); }
+            }
+        }
+    }
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid::new()
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::polygon::{Polygon, PolygonObject};
+use super::style;
+
+/// A single undoable edit, stores the state from before the edit.
+pub enum Change<'a> {
+    // Geometry or constraints of a single polygon
+    Polygon(usize, Polygon<'a>),
+    // Polygons added, removed or several polygons changed at once
+    Document(Vec<PolygonObject<'a>>),
+}
+
+impl<'a> Change<'a> {
+    /// Restores the stored state and returns the change that reverts it.
+    fn apply(self, polygon_objs: &mut Vec<PolygonObject<'a>>) -> Change<'a> {
+        match self {
+            Change::Polygon(id, polygon) => match polygon_objs.get_mut(id) {
+                Some(poly) => Change::Polygon(id, poly.replace_polygon(polygon)),
+                None => Change::Polygon(id, polygon),
+            },
+            Change::Document(objs) => Change::Document(mem::replace(polygon_objs, objs)),
+        }
+    }
+}
+
+/// Undo/redo stacks, the undo stack keeps at most style::HISTORY_SIZE changes.
+pub struct History<'a> {
+    undo: VecDeque<Change<'a>>,
+    redo: Vec<Change<'a>>,
+    // Dropped by the last push, discard_last brings them back
+    dropped_redo: Vec<Change<'a>>,
+    dropped_oldest: Option<Change<'a>>,
+}
+
+impl<'a> History<'a> {
+    pub fn new() -> History<'a> {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            dropped_redo: Vec::new(),
+            dropped_oldest: None,
+        }
+    }
+
+    /// Records a new edit, the redo stack is discarded.
+    pub fn push(&mut self, change: Change<'a>) {
+        self.dropped_redo = mem::take(&mut self.redo);
+        self.undo.push_back(change);
+        self.dropped_oldest = None;
+        if self.undo.len() > style::HISTORY_SIZE {
+            self.dropped_oldest = self.undo.pop_front();
+        }
+    }
+
+    /// Forgets the last recorded edit, used when the edit turned out to be rejected. Should
+    /// follow the push, the redo stack it discarded is restored.
+    pub fn discard_last(&mut self) {
+        self.undo.pop_back();
+        if let Some(change) = self.dropped_oldest.take() {
+            self.undo.push_front(change);
+        }
+        self.redo = mem::take(&mut self.dropped_redo);
+    }
+
+    pub fn undo(&mut self, polygon_objs: &mut Vec<PolygonObject<'a>>) -> bool {
+        self.forget_dropped();
+        match self.undo.pop_back() {
+            Some(change) => {
+                self.redo.push(change.apply(polygon_objs));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, polygon_objs: &mut Vec<PolygonObject<'a>>) -> bool {
+        self.forget_dropped();
+        match self.redo.pop() {
+            Some(change) => {
+                self.undo.push_back(change.apply(polygon_objs));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.forget_dropped();
+    }
+
+    // Only the push right before discard_last can be taken back
+    fn forget_dropped(&mut self) {
+        self.dropped_redo.clear();
+        self.dropped_oldest = None;
+    }
+}
+
+impl<'a> Default for History<'a> {
+    fn default() -> History<'a> {
+        History::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sf;
+
+    fn document(count: usize) -> Vec<PolygonObject<'static>> {
+        let p = sf::Vector2f::new;
+        (0..count)
+            .map(|i| {
+                let x = 200. * i as f32;
+                PolygonObject::from(Polygon::create(vec![p(x, 0.), p(x + 100., 0.), p(x + 100., 100.)]))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn discard_last_keeps_the_redo_stack() {
+        let mut polygon_objs = document(2);
+        let mut history = History::new();
+        history.push(Change::Document(document(1)));
+        assert!(history.undo(&mut polygon_objs));
+        assert_eq!(polygon_objs.len(), 1);
+
+        // A no-op edit is recorded and then taken back
+        history.push(Change::Document(polygon_objs.clone()));
+        assert!(!history.can_redo());
+        history.discard_last();
+
+        assert!(history.redo(&mut polygon_objs));
+        assert_eq!(polygon_objs.len(), 2);
+    }
+}
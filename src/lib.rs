@@ -5,8 +5,25 @@ pub mod sf {
 }
 
 pub mod polygon;
+#[cfg(feature = "gui")]
 pub mod state_machine;
 pub mod style;
 pub mod my_math;
+#[cfg(feature = "gui")]
 pub mod app;
-pub mod line_alg;
\ No newline at end of file
+pub mod line_alg;
+pub mod snap;
+#[cfg(feature = "gui")]
+pub mod toast;
+pub mod library;
+#[cfg(feature = "gui")]
+pub mod shortcuts;
+pub mod grid;
+pub mod units;
+pub mod obj_export;
+pub mod dxf;
+pub mod history;
+#[cfg(feature = "gui")]
+pub mod commands;
+#[cfg(feature = "gui")]
+pub mod config;
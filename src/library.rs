@@ -0,0 +1,52 @@
+use std::fs;
+use serde_json::{from_str, to_string};
+use super::polygon::RawPolygonCoords;
+
+pub const SHAPE_LIBRARY_PATH: &str = "res/shape_library.json";
+
+/// Persistent palette of polygon templates. Templates are stored normalized to the unit
+/// bounding box together with the transform of the polygon they have been created from.
+pub struct ShapeLibrary {
+    templates: Vec<RawPolygonCoords>,
+}
+
+impl ShapeLibrary {
+    /// Loads the library file, a missing or malformed file results in an empty library.
+    pub fn load() -> ShapeLibrary {
+        let templates = match fs::read_to_string(SHAPE_LIBRARY_PATH) {
+            Ok(contents) => from_str(&contents).unwrap_or_else(|err| {
+                log::warn!("Error parsing the shape library: {}", err);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+
+        ShapeLibrary {
+            templates,
+        }
+    }
+
+    pub fn save(&self) {
+        let json_string = to_string(&self.templates).unwrap();
+        if let Err(err) = fs::write(SHAPE_LIBRARY_PATH, json_string) {
+            log::warn!("Error writing the shape library: {}", err);
+        }
+    }
+
+    pub fn templates(&self) -> &Vec<RawPolygonCoords> {
+        &self.templates
+    }
+
+    pub fn add(&mut self, mut raw_polygon: RawPolygonCoords) {
+        // Templates are stamped as standalone polygons
+        raw_polygon.group = None;
+        raw_polygon.name = None;
+        self.templates.push(raw_polygon.normalized(false));
+        self.save();
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        self.templates.remove(id);
+        self.save();
+    }
+}
@@ -1,19 +1,89 @@
 use std::mem;
+use std::ops::Range;
+use serde::{Serialize, Deserialize};
 use crate::my_math::circle_vs_plane_frac;
 use super::sf;
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub enum LinePainterAlgorithm {
     MidPointLine,
     SymmetricMidPointLine,
+    SymmetricDoubleStepMidPointLine,
     GuptaDoubleStepMidPointLine,
     WULine,
+    DDA,
+}
+
+impl LinePainterAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinePainterAlgorithm::MidPointLine => "MidPointLine",
+            LinePainterAlgorithm::SymmetricMidPointLine => "SymmetricMidPointLine",
+            LinePainterAlgorithm::SymmetricDoubleStepMidPointLine => "SymmetricDoubleStepMidPointLine",
+            LinePainterAlgorithm::GuptaDoubleStepMidPointLine => "GuptaDoubleStepMidPointLine",
+            LinePainterAlgorithm::WULine => "XiaolinWULine",
+            LinePainterAlgorithm::DDA => "DDA",
+        }
+    }
+}
+
+/// Lengths are in world units, so the pattern follows the zoom.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineStyle {
+    Solid,
+    Dashed { on: f32, off: f32 },
+    // Dots are as long as the line is thick
+    Dotted { gap: f32 },
+}
+
+impl LineStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineStyle::Solid => "Solid",
+            LineStyle::Dashed { .. } => "Dashed",
+            LineStyle::Dotted { .. } => "Dotted",
+        }
+    }
+}
+
+// Line mapped to the 1/8 quarter handled by the *_line18 algorithms: x is the major axis,
+// dx >= dy >= 0 and the increments restore the directions of the original line
+#[derive(Clone, Copy, Debug)]
+struct Line18 {
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+    dx: i32,
+    dy: i32,
+    incr_x: i32,
+    incr_y: i32,
+}
+
+impl Line18 {
+    fn new(p0: (i32, i32), p1: (i32, i32), d: (i32, i32), incr: (i32, i32)) -> Line18 {
+        Line18 { x0: p0.0, y0: p0.1, x1: p1.0, y1: p1.1, dx: d.0, dy: d.1, incr_x: incr.0, incr_y: incr.1 }
+    }
 }
 
 pub struct LinePainter {
     color: sf::Color,
     thickness: f32,
     alg: LinePainterAlgorithm,
+    // Line endpoints are mapped to (p - origin) * scale, so the image can show any
+    // region of the world at any zoom (and supersampling)
+    origin: sf::Vector2f,
+    scale: f32,
+    // Pixels outside of this column range are not set
+    clip_x: Option<Range<i32>>,
+    line_style: LineStyle,
+    // Start and direction of the line being drawn, the pattern is measured from there
+    pattern_origin: sf::Vector2f,
+    pattern_dir: sf::Vector2f,
+    // Length of the chain drawn before the current line, so the pattern continues across it
+    pattern_phase: f32,
+    // Pixels set since the last reset, used to compare the algorithms
+    pixels_drawn: usize,
 }
 
 impl LinePainter {
@@ -22,6 +92,14 @@ impl LinePainter {
             color,
             thickness,
             alg: LinePainterAlgorithm::MidPointLine,
+            origin: sf::Vector2f::new(0., 0.),
+            scale: 1.0,
+            clip_x: None,
+            line_style: LineStyle::Solid,
+            pattern_origin: sf::Vector2f::new(0., 0.),
+            pattern_dir: sf::Vector2f::new(0., 0.),
+            pattern_phase: 0.,
+            pixels_drawn: 0,
         }
     }
     pub fn set_thickness(&mut self, thickness: f32) {
@@ -36,17 +114,71 @@ impl LinePainter {
     pub fn alg(&self) -> LinePainterAlgorithm {
         self.alg.clone()
     }
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+    pub fn set_origin(&mut self, origin: sf::Vector2f) {
+        self.origin = origin;
+    }
+    pub fn set_clip_x(&mut self, clip_x: Option<Range<i32>>) {
+        self.clip_x = clip_x;
+    }
+    pub fn set_line_style(&mut self, line_style: LineStyle) {
+        self.line_style = line_style;
+    }
+    pub fn line_style(&self) -> LineStyle {
+        self.line_style
+    }
+    pub fn pixels_drawn(&self) -> usize {
+        self.pixels_drawn
+    }
+    pub fn reset_pixels_drawn(&mut self) {
+        self.pixels_drawn = 0;
+    }
+
+    fn in_bounds(&self, x: i32, y: i32, img_target: &sf::Image) -> bool {
+        if let Some(clip) = self.clip_x.as_ref() {
+            if !clip.contains(&x) {
+                return false;
+            }
+        }
+        x < img_target.size().x as i32 && x >= 0 &&
+            y < img_target.size().y as i32 && y >= 0
+    }
+
+    // Whether the pixel falls on the "on" part of the line style, measured by the arc length
+    // of its projection on the line
+    fn in_pattern(&self, x: i32, y: i32) -> bool {
+        let (on, period) = match self.line_style {
+            LineStyle::Solid => return true,
+            LineStyle::Dashed { on, off } => (on, on + off),
+            LineStyle::Dotted { gap } => {
+                let dot = self.thickness.max(1.) / self.scale;
+                (dot, dot + gap)
+            }
+        };
+        if period <= 0. {
+            return true;
+        }
+
+        let pixel = sf::Vector2f::new(x as f32 + 0.5, y as f32 + 0.5) - self.pattern_origin;
+        let length = (pixel.x * self.pattern_dir.x + pixel.y * self.pattern_dir.y) / self.scale + self.pattern_phase;
+        length.rem_euclid(period) < on
+    }
 
-    fn put_pixel(&self, x: i32, y: i32, img_target: &mut sf::Image) {
-        if x < img_target.size().x as i32 && x >= 0 &&
-            y < img_target.size().y as i32 && y >= 0 {
-            unsafe { img_target.set_pixel(x as u32, y as u32, self.color) }
+    fn put_pixel(&self, x: i32, y: i32, img_target: &mut sf::Image) -> bool {
+        if !self.in_bounds(x, y, img_target) || !self.in_pattern(x, y) {
+            return false;
         }
+        unsafe { img_target.set_pixel(x as u32, y as u32, self.color) }
+        true
     }
 
     fn intensify_pixel_with_circle_vs_half_plain_frac(&self, x: i32, y: i32, thickness: f32, distance: f32, img_target: &mut sf::Image) -> bool {
-        if !(x < img_target.size().x as i32 && x >= 0 &&
-            y < img_target.size().y as i32 && y >= 0) {
+        if !self.in_bounds(x, y, img_target) || !self.in_pattern(x, y) {
             return false;
         }
 
@@ -95,10 +227,9 @@ impl LinePainter {
         return true;
     }
 
-    fn intensify_pixel(&self, x: i32, y: i32, intensity: f32, img_target: &mut sf::Image) {
-        if !(x < img_target.size().x as i32 && x >= 0 &&
-            y < img_target.size().y as i32 && y >= 0) {
-            return;
+    fn intensify_pixel(&self, x: i32, y: i32, intensity: f32, img_target: &mut sf::Image) -> bool {
+        if !self.in_bounds(x, y, img_target) || !self.in_pattern(x, y) {
+            return false;
         }
         unsafe {
             let color = img_target.pixel_at(x as u32, y as u32);
@@ -116,16 +247,110 @@ impl LinePainter {
 
             img_target.set_pixel(x as u32, y as u32, new_color);
         }
+        true
+    }
+
+    /// Scanline fill of the polygon (even-odd rule), blended over the image with the given
+    /// opacity. The pixel centers decide the coverage, the edges are not antialiased.
+    pub fn fill_polygon(&mut self, points: &[sf::Vector2f], color: sf::Color, alpha: f32, img_target: &mut sf::Image) {
+        self.fill_polygon_with(points, alpha, img_target, |_| color);
+    }
+
+    /// As fill_polygon, the color of every pixel is looked up at the position of its center
+    /// before the mapping onto the image.
+    pub fn fill_polygon_with(
+        &mut self,
+        points: &[sf::Vector2f],
+        alpha: f32,
+        img_target: &mut sf::Image,
+        color_at: impl Fn(sf::Vector2f) -> sf::Color,
+    ) {
+        if points.len() < 3 {
+            return;
+        }
+        let points: Vec<sf::Vector2f> = points.iter().map(|p| (*p - self.origin) * self.scale).collect();
+        let min_y = points.iter().map(|p| p.y).fold(f32::MAX, f32::min).floor().max(0.) as i32;
+        let max_y = points.iter().map(|p| p.y).fold(f32::MIN, f32::max).ceil().min(img_target.size().y as f32) as i32;
+
+        let mut crossings: Vec<f32> = Vec::new();
+        for y in min_y..max_y {
+            let center_y = y as f32 + 0.5;
+            crossings.clear();
+            for i in 0..points.len() {
+                let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                if (a.y > center_y) != (b.y > center_y) {
+                    crossings.push(a.x + (center_y - a.y) / (b.y - a.y) * (b.x - a.x));
+                }
+            }
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            for span in crossings.chunks_exact(2) {
+                // Pixels whose centers lie inside of the span
+                let start = (span[0] - 0.5).ceil() as i32;
+                let end = (span[1] - 0.5).floor() as i32;
+                for x in start..=end {
+                    if !self.in_bounds(x, y, img_target) {
+                        continue;
+                    }
+                    let color = color_at(sf::Vector2f::new(x as f32 + 0.5, center_y) / self.scale + self.origin);
+                    unsafe {
+                        let old = img_target.pixel_at(x as u32, y as u32);
+                        let blend = |new: u8, old: u8| (new as f32 * alpha + old as f32 * (1. - alpha)) as u8;
+                        img_target.set_pixel(x as u32, y as u32, sf::Color::rgb(
+                            blend(color.r, old.r),
+                            blend(color.g, old.g),
+                            blend(color.b, old.b),
+                        ));
+                    }
+                    self.pixels_drawn += 1;
+                }
+            }
+        }
     }
 
-    pub fn draw_line(&mut self, mut p0: sf::Vector2f, mut p1: sf::Vector2f, color: sf::Color, img_target: &mut sf::Image) {
+    pub fn draw_line(&mut self, p0: sf::Vector2f, p1: sf::Vector2f, color: sf::Color, img_target: &mut sf::Image) {
+        self.pattern_phase = 0.;
+        self.draw_segment(p0, p1, color, img_target);
+    }
+
+    /// Draws the polyline through the points. The line style pattern runs on across the
+    /// joints, so the short segments of the sampled arcs don't restart it.
+    pub fn draw_chain(&mut self, points: &[sf::Vector2f], color: sf::Color, img_target: &mut sf::Image) {
+        let mut phase = 0.;
+        for segment in points.windows(2) {
+            self.pattern_phase = phase;
+            self.draw_segment(segment[0], segment[1], color, img_target);
+            let d = segment[1] - segment[0];
+            phase += (d.x * d.x + d.y * d.y).sqrt();
+        }
+        self.pattern_phase = 0.;
+    }
+
+    fn draw_segment(&mut self, p0: sf::Vector2f, p1: sf::Vector2f, color: sf::Color, img_target: &mut sf::Image) {
         self.color = color;
+        let p0 = (p0 - self.origin) * self.scale;
+        let p1 = (p1 - self.origin) * self.scale;
+        let len = ((p1.x - p0.x).powi(2) + (p1.y - p0.y).powi(2)).sqrt();
+        self.pattern_origin = p0;
+        self.pattern_dir = if len > 0. { (p1 - p0) / len } else { sf::Vector2f::new(0., 0.) };
         if self.alg == LinePainterAlgorithm::WULine {
-            self.xiaolin_wu_antialiased_line(p0, p1, |x, y, i| self.intensify_pixel(x, y, i, img_target));
+            let mut count = 0;
+            self.xiaolin_wu_antialiased_line(p0, p1, |x, y, i| {
+                if self.intensify_pixel(x, y, i, img_target) {
+                    count += 1;
+                }
+            });
+            self.pixels_drawn += count;
             return;
         }
         let mut p0 = sf::Vector2i::new(p0.x as i32, p0.y as i32);
         let mut p1 = sf::Vector2i::new(p1.x as i32, p1.y as i32);
+        if self.alg == LinePainterAlgorithm::DDA {
+            let mut count = 0;
+            self.dda_line(p0, p1, |x, y| if self.put_pixel(x, y, img_target) { count += 1; });
+            self.pixels_drawn += count;
+            return;
+        }
 
         // This simplification skips 4 cases out of 8
         if p1.x < p0.x {
@@ -136,53 +361,59 @@ impl LinePainter {
 
         if d.y <= 0 {
             if d.x.abs() >= d.y.abs() {
-                self.run_bresenham_alg18(p0.x, p0.y, p1.x, p1.y, d.x, -d.y, 1, -1, false, img_target);
+                self.pixels_drawn += self.run_bresenham_alg18(Line18::new((p0.x, p0.y), (p1.x, p1.y), (d.x, -d.y), (1, -1)), false, img_target);
             } else {
-                self.run_bresenham_alg18(p0.y, p0.x, p1.y, p1.x, -d.y, d.x, -1, 1, true, img_target);
+                self.pixels_drawn += self.run_bresenham_alg18(Line18::new((p0.y, p0.x), (p1.y, p1.x), (-d.y, d.x), (-1, 1)), true, img_target);
             }
         } else {
             if d.x.abs() >= d.y.abs() {
-                self.run_bresenham_alg18(p0.x, p0.y, p1.x, p1.y, d.x, d.y, 1, 1, false, img_target);
+                self.pixels_drawn += self.run_bresenham_alg18(Line18::new((p0.x, p0.y), (p1.x, p1.y), (d.x, d.y), (1, 1)), false, img_target);
             } else {
-                self.run_bresenham_alg18(p0.y, p0.x, p1.y, p1.x, d.y, d.x, 1, 1, true, img_target);
+                self.pixels_drawn += self.run_bresenham_alg18(Line18::new((p0.y, p0.x), (p1.y, p1.x), (d.y, d.x), (1, 1)), true, img_target);
             }
         }
     }
     fn run_bresenham_alg18(&self,
-                           x0: i32, y0: i32,
-                           x1: i32, y1: i32,
-                           dx: i32, dy: i32,
-                           incr_x: i32, incr_y: i32,
+                           line: Line18,
                            rev_func_input: bool,
-                           img_target: &mut sf::Image)
+                           img_target: &mut sf::Image) -> usize
     {
+        // Number of the pixels actually set
+        let mut count = 0;
         if rev_func_input {
             match self.alg {
-                LinePainterAlgorithm::MidPointLine => self.mid_point_line18(x0, y0, x1, y1, dx, dy, incr_x, incr_y, |x, y| self.put_pixel(y, x, img_target)),
-                LinePainterAlgorithm::SymmetricMidPointLine => self.symmetric_mid_point_line18(x0, y0, x1, y1, dx, dy, incr_x, incr_y, |x, y| self.put_pixel(y, x, img_target)),
-                LinePainterAlgorithm::GuptaDoubleStepMidPointLine => self.gupta_sproull_antialiased_thick_line18(x0, y0, x1, y1, dx, dy, incr_x, incr_y, |x, y, d| self.intensify_pixel_with_circle_vs_half_plain_frac(y, x, self.thickness, d, img_target)),
+                LinePainterAlgorithm::MidPointLine => self.mid_point_line18(line, |x, y| if self.put_pixel(y, x, img_target) { count += 1; }),
+                LinePainterAlgorithm::SymmetricMidPointLine => self.symmetric_mid_point_line18(line, |x, y| if self.put_pixel(y, x, img_target) { count += 1; }),
+                LinePainterAlgorithm::SymmetricDoubleStepMidPointLine => self.symmetric_double_step_mid_point_line18(line, |x, y| if self.put_pixel(y, x, img_target) { count += 1; }),
+                LinePainterAlgorithm::GuptaDoubleStepMidPointLine => self.gupta_sproull_antialiased_thick_line18(line, |x, y, d| {
+                    let valid = self.intensify_pixel_with_circle_vs_half_plain_frac(y, x, self.thickness, d, img_target);
+                    count += valid as usize;
+                    valid
+                }),
                 _ => ()
             }
-            return;
+            return count;
         }
         match self.alg {
-            LinePainterAlgorithm::MidPointLine => self.mid_point_line18(x0, y0, x1, y1, dx, dy, incr_x, incr_y, |x, y| self.put_pixel(x, y, img_target)),
-            LinePainterAlgorithm::SymmetricMidPointLine => self.symmetric_mid_point_line18(x0, y0, x1, y1, dx, dy, incr_x, incr_y, |x, y| self.put_pixel(x, y, img_target)),
-            LinePainterAlgorithm::GuptaDoubleStepMidPointLine => self.gupta_sproull_antialiased_thick_line18(x0, y0, x1, y1, dx, dy, incr_x, incr_y, |x, y, d| self.intensify_pixel_with_circle_vs_half_plain_frac(x, y, self.thickness, d, img_target)),
+            LinePainterAlgorithm::MidPointLine => self.mid_point_line18(line, |x, y| if self.put_pixel(x, y, img_target) { count += 1; }),
+            LinePainterAlgorithm::SymmetricMidPointLine => self.symmetric_mid_point_line18(line, |x, y| if self.put_pixel(x, y, img_target) { count += 1; }),
+            LinePainterAlgorithm::SymmetricDoubleStepMidPointLine => self.symmetric_double_step_mid_point_line18(line, |x, y| if self.put_pixel(x, y, img_target) { count += 1; }),
+            LinePainterAlgorithm::GuptaDoubleStepMidPointLine => self.gupta_sproull_antialiased_thick_line18(line, |x, y, d| {
+                let valid = self.intensify_pixel_with_circle_vs_half_plain_frac(x, y, self.thickness, d, img_target);
+                count += valid as usize;
+                valid
+            }),
             _ => ()
         }
+        count
     }
 
     // Works only for 1/8 quarter
-    fn mid_point_line18<F>(&self,
-                           mut x0: i32, mut y0: i32,
-                           x1: i32, _y1: i32,
-                           dx: i32, dy: i32,
-                           incr_x: i32, incr_y: i32,
-                           mut put_pixel_func: F,
-    ) where
+    fn mid_point_line18<F>(&self, line: Line18, mut put_pixel_func: F)
+    where
         F: FnMut(i32, i32),
     {
+        let Line18 { mut x0, mut y0, x1, dx, dy, incr_x, incr_y, .. } = line;
         let mut d = 2 * dy - dx;
         let incrd_e = 2 * dy;
         let incrd_ne = 2 * dy - 2 * dx;
@@ -207,15 +438,11 @@ impl LinePainter {
     }
 
     // Works only for 1/8 quarter
-    fn symmetric_mid_point_line18<F>(&self,
-                                     mut x0: i32, mut y0: i32,
-                                     mut x1: i32, mut y1: i32,
-                                     dx: i32, dy: i32,
-                                     incr_x: i32, incr_y: i32,
-                                     mut put_pixel_func: F,
-    ) where
+    fn symmetric_mid_point_line18<F>(&self, line: Line18, mut put_pixel_func: F)
+    where
         F: FnMut(i32, i32),
     {
+        let Line18 { mut x0, mut y0, mut x1, mut y1, dx, dy, incr_x, incr_y } = line;
         let mut d = 2 * dy - dx;
         let incrd_e = 2 * dy;
         let incrd_ne = 2 * dy - 2 * dx;
@@ -244,64 +471,105 @@ impl LinePainter {
         }
     }
 
-    // TODO: fix this function
-    fn symmetric_double_step_mid_point_line18<F>(&self,
-                                                 mut x0: i32, mut y0: i32,
-                                                 mut x1: i32, mut y1: i32,
-                                                 dx: i32, dy: i32,
-                                                 incr_x: i32, incr_y: i32,
-                                                 mut put_pixel_func: F,
-    ) where
+    // Works only for 1/8 quarter. Draws two pixels per step from both ends of the line, with
+    // a single decision per step. Sets the same pixels as mid_point_line18 (the end point is
+    // left out as well).
+    fn symmetric_double_step_mid_point_line18<F>(&self, line: Line18, mut put_pixel_func: F)
+    where
         F: FnMut(i32, i32),
     {
-        let xend = (dx - 1) / 4;
-        let pix_left = (dx - 1) % 4;
+        let Line18 { mut x0, mut y0, mut x1, mut y1, dx, dy, incr_x, incr_y } = line;
+        let thickness = self.thickness as i32;
+        let mut put_column = |x: i32, y: i32| {
+            for i in 0..thickness {
+                put_pixel_func(x, y + i);
+                put_pixel_func(x, y - i)
+            }
+        };
+
+        if dx <= 0 {
+            return;
+        }
+        put_column(x0, y0);
+
+        // The decision variable of two mid-point steps, it's the same for both ends
         let incr1 = 4 * dy;
         let incr2 = 4 * dy - 2 * dx;
+        let incr3 = 4 * dy - 4 * dx;
         let mut d = 4 * dy - dx;
+        let mut d_back = d;
 
-        put_pixel_func(x0, y0);
-        put_pixel_func(x1, y1);
-        for _i in 0..(xend - 1) {
-            x0 += incr_x;
-            x1 -= incr_x;
+        // Pixels between the end points
+        let mut left = dx - 1;
+        while left >= 4 {
+            // From the start, ties go towards incr_y like in mid_point_line18
             if d < 0 {
-                put_pixel_func(x0 + 1, y0);
-                put_pixel_func(x0 + 2, y0);
-                put_pixel_func(x1 - 1, y1);
-                put_pixel_func(x1 - 2, y1);
+                put_column(x0 + incr_x, y0);
+                put_column(x0 + 2 * incr_x, y0);
                 d += incr1;
-            } else if (d - 2 * dy) < 0 {
-                put_pixel_func(x0 + 1, y0);
-                put_pixel_func(x0 + 2, y0 + 1);
-                put_pixel_func(x1 - 1, y1);
-                put_pixel_func(x1 - 2, y1 - 1);
+            } else if d < 2 * dy {
+                put_column(x0 + incr_x, y0);
                 y0 += incr_y;
-                y1 -= incr_y;
+                put_column(x0 + 2 * incr_x, y0);
+                d += incr2;
+            } else if d < 2 * dx {
+                y0 += incr_y;
+                put_column(x0 + incr_x, y0);
+                put_column(x0 + 2 * incr_x, y0);
                 d += incr2;
             } else {
-                put_pixel_func(x0 + 1, y0 + 1);
-                put_pixel_func(x0 + 2, y0 + 2);
-                put_pixel_func(x1 - 1, y1 - 1);
-                put_pixel_func(x1 - 2, y1 - 2);
+                put_column(x0 + incr_x, y0 + incr_y);
                 y0 += 2 * incr_y;
+                put_column(x0 + 2 * incr_x, y0);
+                d += incr3;
+            }
+            x0 += 2 * incr_x;
+
+            // From the end, ties go the other way, so both halves round the same way
+            if d_back <= 0 {
+                put_column(x1 - incr_x, y1);
+                put_column(x1 - 2 * incr_x, y1);
+                d_back += incr1;
+            } else if d_back <= 2 * dy {
+                put_column(x1 - incr_x, y1);
+                y1 -= incr_y;
+                put_column(x1 - 2 * incr_x, y1);
+                d_back += incr2;
+            } else if d_back <= 2 * dx {
+                y1 -= incr_y;
+                put_column(x1 - incr_x, y1);
+                put_column(x1 - 2 * incr_x, y1);
+                d_back += incr2;
+            } else {
+                put_column(x1 - incr_x, y1 - incr_y);
                 y1 -= 2 * incr_y;
-                d += incr2;
+                put_column(x1 - 2 * incr_x, y1);
+                d_back += incr3;
+            }
+            x1 -= 2 * incr_x;
+
+            left -= 4;
+        }
+
+        // Up to 3 pixels are left in the middle, finish them with single mid-point steps
+        let mut d = d - 2 * dy;
+        for _ in 0..left {
+            if d < 0 {
+                d += 2 * dy;
+            } else {
+                d += 2 * dy - 2 * dx;
+                y0 += incr_y;
             }
+            x0 += incr_x;
+            put_column(x0, y0);
         }
-        x0 += 2 * incr_x;
-        x1 -= 2 * incr_y;
     }
 
-    fn gupta_sproull_antialiased_thick_line18<F>(&self,
-                                                 mut x0: i32, mut y0: i32,
-                                                 mut x1: i32, mut y1: i32,
-                                                 dx: i32, dy: i32,
-                                                 incr_x: i32, incr_y: i32,
-                                                 mut intensify_pixel_func: F,
-    ) where
+    fn gupta_sproull_antialiased_thick_line18<F>(&self, line: Line18, mut intensify_pixel_func: F)
+    where
         F: FnMut(i32, i32, f32) -> bool,
     {
+        let Line18 { mut x0, mut y0, x1, dx, dy, incr_x, incr_y, .. } = line;
         // Bresenham
         let mut d = 2 * dy - dx;
         let incrd_e = 2 * dy;
@@ -350,6 +618,42 @@ impl LinePainter {
         }
     }
 
+    // Works for all octants, the end point is left out like in mid_point_line18
+    fn dda_line<F>(&self,
+                   p0: sf::Vector2i,
+                   p1: sf::Vector2i,
+                   mut put_pixel_func: F,
+    ) where
+        F: FnMut(i32, i32),
+    {
+        let d = p1 - p0;
+        let steps = d.x.abs().max(d.y.abs());
+        if steps == 0 {
+            return;
+        }
+        let incr_x = d.x as f32 / steps as f32;
+        let incr_y = d.y as f32 / steps as f32;
+        // The thickness is stacked across the major axis
+        let steep = d.y.abs() > d.x.abs();
+
+        let mut x = p0.x as f32;
+        let mut y = p0.y as f32;
+        for _ in 0..steps {
+            let (px, py) = (x.round() as i32, y.round() as i32);
+            for i in 0..(self.thickness as i32) {
+                if steep {
+                    put_pixel_func(px + i, py);
+                    put_pixel_func(px - i, py);
+                } else {
+                    put_pixel_func(px, py + i);
+                    put_pixel_func(px, py - i);
+                }
+            }
+            x += incr_x;
+            y += incr_y;
+        }
+    }
+
     fn xiaolin_wu_antialiased_line<F>(&self,
                                       mut p0: sf::Vector2f,
                                       mut p1: sf::Vector2f,
@@ -377,19 +681,125 @@ impl LinePainter {
             m = delta.y / delta.x;
         }
 
+        // Every column is covered by a span of this length along the minor axis. The rows
+        // above 1px are measured perpendicular to the line, so thick lines keep their
+        // width at any slope, while the 1px line stays the classic Wu line.
+        let span = 1. + (self.thickness - 1.).max(0.) * (1. + m * m).sqrt();
+        let half = (span - 1.) / 2.;
+
         let mut y: f32 = p0.y;
-        if steep {
-            for x in (p0.x as i32)..(p1.x as i32) {
-                intensify_pixel_func(y as i32, x, (1. - y.fract()));
-                intensify_pixel_func(y as i32 + 1, x, (y.fract()));
-                y += m;
+        for x in (p0.x as i32)..(p1.x as i32) {
+            let top = y - half;
+            let bottom = y + 1. + half;
+            for k in (top.floor() as i32)..(bottom.ceil() as i32) {
+                // The part of the pixel [k, k + 1] covered by the span
+                let coverage = (bottom.min(k as f32 + 1.) - top.max(k as f32)).clamp(0., 1.);
+                if coverage <= 0. {
+                    continue;
+                }
+                if steep {
+                    intensify_pixel_func(k, x, coverage);
+                } else {
+                    intensify_pixel_func(x, k, coverage);
+                }
             }
-        } else {
-            for x in (p0.x as i32)..(p1.x as i32) {
-                intensify_pixel_func(x, y as i32, (1. - y.fract()));
-                intensify_pixel_func(x, y as i32 + 1, (y.fract()));
-                y += m;
+            y += m;
+        }
+    }
+}
+
+/// Averages every factor x factor block of src into a single pixel of dst (box filter).
+pub fn downsample(src: &sf::Image, dst: &mut sf::Image, factor: u32) {
+    let size = dst.size();
+    let src_size = src.size();
+    if src_size.x < size.x * factor || src_size.y < size.y * factor {
+        return;
+    }
+
+    let samples = factor * factor;
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
+            for sy in 0..factor {
+                for sx in 0..factor {
+                    let color = unsafe { src.pixel_at(x * factor + sx, y * factor + sy) };
+                    r += color.r as u32;
+                    g += color.g as u32;
+                    b += color.b as u32;
+                }
             }
+            let color = sf::Color::rgb((r / samples) as u8, (g / samples) as u8, (b / samples) as u8);
+            unsafe { dst.set_pixel(x, y, color); }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use super::*;
+
+    fn pixels<F>(draw: F) -> BTreeSet<(i32, i32)>
+    where
+        F: FnOnce(&mut dyn FnMut(i32, i32)),
+    {
+        let mut result = BTreeSet::new();
+        draw(&mut |x, y| { result.insert((x, y)); });
+        result
+    }
+
+    #[test]
+    fn double_step_matches_mid_point() {
+        let painter = LinePainter::new(sf::Color::WHITE, 1.);
+        for dx in 0..=24 {
+            for dy in 0..=dx {
+                for incr in [(1, 1), (1, -1), (-1, 1)] {
+                    let line = Line18::new((3, 5), (3 + incr.0 * dx, 5 + incr.1 * dy), (dx, dy), incr);
+                    let plain = pixels(|put| painter.mid_point_line18(line, put));
+                    let double = pixels(|put| painter.symmetric_double_step_mid_point_line18(line, put));
+                    assert_eq!(plain, double, "dx = {}, dy = {}, incr = {:?}", dx, dy, incr);
+                }
+            }
+        }
+    }
+
+    // Columns of the set pixels of the row y
+    fn row(image: &sf::Image, y: u32) -> Vec<u32> {
+        (0..image.size().x).filter(|&x| unsafe { image.pixel_at(x, y) } != sf::Color::BLACK).collect()
+    }
+
+    #[test]
+    fn fill_looks_colors_up_before_the_mapping() {
+        let mut painter = LinePainter::new(sf::Color::WHITE, 1.);
+        painter.set_origin(sf::Vector2f::new(100., 0.));
+        painter.set_scale(2.);
+
+        // The left half of the square is red, it covers the left half of the image
+        let square = [(100., 0.), (108., 0.), (108., 8.), (100., 8.)].map(|(x, y)| sf::Vector2f::new(x, y));
+        let mut image = sf::Image::new(16, 16);
+        painter.fill_polygon_with(&square, 1., &mut image, |pos| {
+            if pos.x < 104. { sf::Color::RED } else { sf::Color::GREEN }
+        });
+        unsafe {
+            assert_eq!(image.pixel_at(7, 8), sf::Color::RED);
+            assert_eq!(image.pixel_at(8, 8), sf::Color::GREEN);
+        }
+    }
+
+    #[test]
+    fn dash_pattern_continues_along_a_chain() {
+        let mut painter = LinePainter::new(sf::Color::WHITE, 1.);
+        painter.set_line_style(LineStyle::Dashed { on: 10., off: 6. });
+
+        let mut line = sf::Image::new(64, 8);
+        painter.draw_line(sf::Vector2f::new(0., 4.), sf::Vector2f::new(48., 4.), sf::Color::WHITE, &mut line);
+
+        // The same line cut into segments shorter than a dash
+        let points: Vec<sf::Vector2f> = (0..=12).map(|i| sf::Vector2f::new(4. * i as f32, 4.)).collect();
+        let mut chain = sf::Image::new(64, 8);
+        painter.draw_chain(&points, sf::Color::WHITE, &mut chain);
+
+        assert!(row(&line, 4).len() < 48);
+        assert_eq!(row(&chain, 4), row(&line, 4));
+    }
+}
@@ -1,6 +1,10 @@
 use polygon_editor::app::Application;
+use polygon_editor::config::Config;
 
 fn main() {
-    let mut app = Application::new();
+    // Only warnings and errors by default, RUST_LOG=debug shows the input events as well
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let mut app = Application::new(Config::load());
     app.run();
 }
@@ -38,6 +38,58 @@ pub fn cross2(vec1: &sf::Vector2f, vec2: &sf::Vector2f) -> f32 {
     return (vec1.x * vec2.y) - (vec1.y * vec2.x);
 }
 
+/// Rotates the point around the pivot, angle is in radians.
+pub fn rotate_around(point: &sf::Vector2f, pivot: &sf::Vector2f, angle: f32) -> sf::Vector2f {
+    let (sin, cos) = angle.sin_cos();
+    let d = *point - *pivot;
+    *pivot + sf::Vector2f::new(d.x * cos - d.y * sin, d.x * sin + d.y * cos)
+}
+
+/// Offset direction at a corner given the unit normals of its edges, scaled so that the
+/// offset edges meet at its tip. The miter grows without bounds as the edges fold back onto
+/// each other, it is limited to limit times the offset.
+pub fn miter_vec(prev_normal: &sf::Vector2f, normal: &sf::Vector2f, limit: f32) -> sf::Vector2f {
+    let bisector = *prev_normal + *normal;
+    if vec_len(&bisector) <= f32::EPSILON {
+        // Folded exactly, the bisector is undefined
+        return *normal;
+    }
+    let half_cos = ((1. + dot_prod(prev_normal, normal)) / 2.).sqrt();
+    vec_norm(&bisector) / half_cos.max(1. / limit)
+}
+
+/// Convex hull of the points (Andrew's monotone chain), consecutive hull points turn with
+/// a positive cross2. Inputs with less than 3 points or all of them collinear are
+/// returned unchanged.
+pub fn convex_hull(points: &[sf::Vector2f]) -> Vec<sf::Vector2f> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+
+    let turn = |a: &sf::Vector2f, b: &sf::Vector2f, c: &sf::Vector2f| cross2(&(*b - *a), &(*c - *a));
+    let mut hull: Vec<sf::Vector2f> = Vec::with_capacity(sorted.len() + 1);
+    // Lower chain, then the upper one, collinear points are dropped
+    for pass in [&sorted[..], &sorted.iter().rev().copied().collect::<Vec<_>>()[..]] {
+        let start = hull.len();
+        for p in pass {
+            while hull.len() >= start + 2 && turn(&hull[hull.len() - 2], &hull[hull.len() - 1], p) <= 0. {
+                hull.pop();
+            }
+            hull.push(*p);
+        }
+        // The last point of a chain starts the other one
+        hull.pop();
+    }
+
+    if hull.len() < 3 {
+        return points.to_vec();
+    }
+    hull
+}
+
 pub fn is_ccw(points: &[sf::Vector2f]) -> bool {
     let mut sum: f32 = 0.;
     for i in 0..(points.len() - 1) {
@@ -57,4 +109,194 @@ pub fn circle_vs_plane_frac(distance: f32, radius: f32) -> f32 {
     return 0.5 - distance *
         (radius * radius - distance * distance).sqrt() / (std::f32::consts::PI * radius * radius) -
         1. / std::f32::consts::PI * (distance / radius).asin();
-}
\ No newline at end of file
+}
+
+/// Area of the polygon (shoelace formula), positive if the consecutive edges turn with
+/// a positive cross2, negative for the opposite winding.
+pub fn signed_area(points: &[sf::Vector2f]) -> f32 {
+    let mut sum: f32 = 0.;
+    for i in 0..points.len() {
+        sum += cross2(&points[i], &points[(i + 1) % points.len()]);
+    }
+    sum / 2.
+}
+
+/// Returns true if p lies inside of the abc triangle or on its boundary.
+pub fn is_point_in_triangle(p: &sf::Vector2f, a: &sf::Vector2f, b: &sf::Vector2f, c: &sf::Vector2f) -> bool {
+    let d1 = cross2(&(*b - *a), &(*p - *a));
+    let d2 = cross2(&(*c - *b), &(*p - *b));
+    let d3 = cross2(&(*a - *c), &(*p - *c));
+
+    let has_neg = d1 < 0. || d2 < 0. || d3 < 0.;
+    let has_pos = d1 > 0. || d2 > 0. || d3 > 0.;
+    !(has_neg && has_pos)
+}
+
+/// Ear clipping triangulation of a simple polygon, works for both windings.
+/// Returns triangles as triples of indices into "points". For self-crossing
+/// input the result may be partial.
+pub fn ear_clipping(points: &[sf::Vector2f]) -> Vec<[usize; 3]> {
+    let mut result: Vec<[usize; 3]> = Vec::new();
+    let orientation = signed_area(points).signum();
+    if points.len() < 3 || orientation == 0. {
+        return result;
+    }
+
+    let mut ids: Vec<usize> = (0..points.len()).collect();
+    while ids.len() > 3 {
+        let n = ids.len();
+        let mut ear: Option<usize> = None;
+        for i in 0..n {
+            let prev = ids[(i + n - 1) % n];
+            let curr = ids[i];
+            let next = ids[(i + 1) % n];
+            let (a, b, c) = (points[prev], points[curr], points[next]);
+
+            // Reflex vertex can't be an ear
+            if cross2(&(b - a), &(c - b)) * orientation <= 0. {
+                continue;
+            }
+
+            // No other vertex can lie inside of the ear
+            let is_ear = !ids.iter().any(|&j| {
+                j != prev && j != curr && j != next && is_point_in_triangle(&points[j], &a, &b, &c)
+            });
+
+            if is_ear {
+                result.push([prev, curr, next]);
+                ear = Some(i);
+                break;
+            }
+        }
+
+        match ear {
+            Some(i) => { ids.remove(i); }
+            // Degenerate or self-crossing polygon
+            None => return result,
+        }
+    }
+    result.push([ids[0], ids[1], ids[2]]);
+
+    result
+}
+
+/// Fits a line through the points using total least squares, so vertical lines are handled
+/// as well. Returns a point on the line (centroid) and the normalized line direction.
+pub fn fit_line(points: &[sf::Vector2f]) -> Option<(sf::Vector2f, sf::Vector2f)> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let mut center = sf::Vector2f::new(0., 0.);
+    for p in points.iter() {
+        center += *p;
+    }
+    center /= points.len() as f32;
+
+    // Covariance matrix
+    let (mut sxx, mut syy, mut sxy) = (0., 0., 0.);
+    for p in points.iter() {
+        let d = *p - center;
+        sxx += d.x * d.x;
+        syy += d.y * d.y;
+        sxy += d.x * d.y;
+    }
+    if sxx == 0. && syy == 0. {
+        return None;
+    }
+
+    // Direction of the principal eigenvector
+    let angle = 0.5 * (2. * sxy).atan2(sxx - syy);
+    Some((center, sf::Vector2f::new(angle.cos(), angle.sin())))
+}
+
+/// Visvalingam-Whyatt simplification of a closed polygon: repeatedly removes the point
+/// whose triangle with its neighbours has the smallest area. Returns the ids of the kept
+/// points in their original order.
+pub fn visvalingam(points: &[sf::Vector2f], target: usize) -> Vec<usize> {
+    let mut ids: Vec<usize> = (0..points.len()).collect();
+    let target = target.max(3);
+
+    while ids.len() > target {
+        let n = ids.len();
+        let mut min_id = 0;
+        let mut min_area = f32::MAX;
+        for i in 0..n {
+            let triangle = [points[ids[(i + n - 1) % n]], points[ids[i]], points[ids[(i + 1) % n]]];
+            let area = signed_area(&triangle).abs();
+            if area < min_area {
+                min_area = area;
+                min_id = i;
+            }
+        }
+        ids.remove(min_id);
+    }
+
+    ids
+}
+
+/// Polygon outline independent of the start point and the winding: counter-clockwise
+/// (in screen coordinates) and starting at the lexicographically smallest point. The x
+/// coordinates closer than epsilon count as equal, so outlines matched with that epsilon
+/// start at the same point.
+pub fn canonical_outline(points: &[sf::Vector2f], epsilon: f32) -> Vec<sf::Vector2f> {
+    let mut result = points.to_vec();
+    if signed_area(&result) < 0. {
+        result.reverse();
+    }
+
+    let start = (0..result.len())
+        .min_by(|a, b| {
+            let (pa, pb) = (result[*a], result[*b]);
+            if (pa.x - pb.x).abs() <= epsilon {
+                pa.y.total_cmp(&pb.y)
+            } else {
+                pa.x.total_cmp(&pb.x)
+            }
+        })
+        .unwrap_or(0);
+    result.rotate_left(start);
+    result
+}
+
+pub fn closest_on_segment(p: &sf::Vector2f, a: &sf::Vector2f, b: &sf::Vector2f) -> sf::Vector2f {
+    let ab = *b - *a;
+    let len2 = vec_len2(&ab);
+    if len2 == 0. {
+        return *a;
+    }
+    let t = (dot_prod(&(*p - *a), &ab) / len2).clamp(0., 1.);
+    *a + ab * t
+}
+
+/// Closest pair of points of the a0a1 and b0b1 segments. If the segments
+/// intersect both points are the intersection point.
+pub fn segments_closest_points(
+    a0: &sf::Vector2f, a1: &sf::Vector2f,
+    b0: &sf::Vector2f, b1: &sf::Vector2f,
+) -> (sf::Vector2f, sf::Vector2f) {
+    let da = *a1 - *a0;
+    let db = *b1 - *b0;
+    let denom = cross2(&da, &db);
+    if denom != 0. {
+        let t = cross2(&(*b0 - *a0), &db) / denom;
+        let u = cross2(&(*b0 - *a0), &da) / denom;
+        if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+            let x = *a0 + da * t;
+            return (x, x);
+        }
+    }
+
+    // Otherwise one of the closest points is an endpoint
+    let candidates = [
+        (*a0, closest_on_segment(a0, b0, b1)),
+        (*a1, closest_on_segment(a1, b0, b1)),
+        (closest_on_segment(b0, a0, a1), *b0),
+        (closest_on_segment(b1, a0, a1), *b1),
+    ];
+    candidates
+        .iter()
+        .copied()
+        .min_by(|(p0, q0), (p1, q1)| distance2(p0, q0).partial_cmp(&distance2(p1, q1)).unwrap())
+        .unwrap()
+}
@@ -0,0 +1,45 @@
+use std::fmt::Write;
+use crate::my_math;
+use crate::polygon::PolygonObject;
+use super::sf;
+
+/// Writes the triangulated polygons as a Wavefront OBJ mesh. Every polygon is a separate
+/// object sharing one vertex list, the y axis is flipped to point up.
+pub fn polygons_to_obj(polygons: &[PolygonObject]) -> String {
+    let mut result = String::from("# Polygon editor export\n");
+    let mut vertex_offset = 1;
+
+    for poly in polygons {
+        let polygon = poly.polygon();
+        let positions: Vec<sf::Vector2f> = (0..polygon.points_count() as isize)
+            .map(|id| {
+                let pos = polygon.get_point_pos(id);
+                sf::Vector2f::new(pos.x, -pos.y)
+            })
+            .collect();
+
+        let _ = writeln!(result, "o {}", polygon.get_name().replace(' ', "_"));
+        for pos in positions.iter() {
+            let _ = writeln!(result, "v {} {} 0", pos.x, pos.y);
+        }
+
+        for mut tri in polygon.triangulate() {
+            // Faces should be counter-clockwise in the flipped coordinates
+            let tri_points = [positions[tri[0]], positions[tri[1]], positions[tri[2]]];
+            if my_math::signed_area(&tri_points) < 0. {
+                tri.swap(1, 2);
+            }
+            let _ = writeln!(
+                result,
+                "f {} {} {}",
+                tri[0] + vertex_offset,
+                tri[1] + vertex_offset,
+                tri[2] + vertex_offset
+            );
+        }
+
+        vertex_offset += positions.len();
+    }
+
+    result
+}
@@ -1,9 +1,13 @@
-use std::io;
+use std::fmt;
+use std::fs;
 use std::collections::HashSet;
+#[cfg(feature = "gui")]
 use egui_sfml::egui;
-use sfml::graphics::{CircleShape, Drawable, RcFont, RcTexture, RenderTarget, Shape, Transformable};
+use sfml::graphics::{Drawable, RcFont, RcTexture, RenderTarget, Shape, Transformable};
+use sfml::SfBox;
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use geo::LineIntersection;
 use crate::my_math::{is_right_turn};
 use crate::style;
@@ -11,47 +15,413 @@ use crate::my_math;
 use crate::sf;
 use crate::my_math::cross2;
 use serde::{Serialize, Deserialize};
-use crate::line_alg::LinePainter;
+use serde_json::{from_str, to_string};
+use crate::line_alg::{LinePainter, LineStyle};
+
+/// Errors returned by the fallible polygon operations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolygonError {
+    // The operation would leave less than 3 points
+    TooFewPoints,
+    // The operation would make the polygon self-crossing
+    WouldSelfIntersect,
+    // The input has no meaningful extent (e.g. coincident points)
+    Degenerate,
+    // The operation would make an edge shorter than style::POLY_EDGE_MIN_LEN
+    EdgeTooShort,
+}
+
+impl fmt::Display for PolygonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolygonError::TooFewPoints => write!(f, "Not enough points"),
+            PolygonError::WouldSelfIntersect => write!(f, "The polygon would become self-crossing"),
+            PolygonError::Degenerate => write!(f, "Degenerate geometry"),
+            PolygonError::EdgeTooShort => write!(f, "An edge would become too short"),
+        }
+    }
+}
+
+impl std::error::Error for PolygonError {}
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct RawCoord {
     x: f32,
     y: f32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pinned: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RawFill {
+    pub style: FillStyle,
+    pub color: [u8; 4],
+    pub texture_path: Option<String>,
+    pub texture_scale: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hatch: Option<RawHatch>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RawHatch {
+    // In degrees, counter-clockwise from the x axis
+    pub angle: f32,
+    pub spacing: f32,
+}
+
+/// Maps normalized coordinates back onto the original ones: original = coord * scale + origin.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RawTransform {
+    pub origin: [f32; 2],
+    pub scale: [f32; 2],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RawPolygonCoords {
     pub coords: Vec<RawCoord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill: Option<RawFill>,
+    // Present if the coords have been normalized to the [0,1]x[0,1] bounding box
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<RawTransform>,
+    // Id of the group the polygon belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<usize>,
+    // Constraint of the edge starting at the coord with the same index, empty if there are none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<EdgeConstraint>,
+    // Bulge of the edge starting at the coord with the same index, empty if there are no arcs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bulges: Vec<f32>,
+    // Fixed interior angle in radians at the coord with the same index, empty if there are none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub angles: Vec<Option<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // The closing edge is hidden
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub open: bool,
+    // Missing in the older files, the default style is used then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edges_color: Option<[u8; 3]>,
 }
 
 impl RawPolygonCoords {
     pub fn new(coords: Vec<RawCoord>) -> RawPolygonCoords {
         RawPolygonCoords {
             coords,
+            fill: None,
+            transform: None,
+            group: None,
+            constraints: Vec::new(),
+            bulges: Vec::new(),
+            angles: Vec::new(),
+            name: None,
+            open: false,
+            edges_color: None,
         }
     }
 
     pub fn from_sf_points(points: Vec<sf::Vector2f>) -> RawPolygonCoords {
-        let coords = points.iter().map(|p| RawCoord { x: p.x, y: p.y }).collect();
+        let coords = points.iter().map(|p| RawCoord { x: p.x, y: p.y, pinned: false }).collect();
         RawPolygonCoords {
             coords,
+            fill: None,
+            transform: None,
+            group: None,
+            constraints: Vec::new(),
+            bulges: Vec::new(),
+            angles: Vec::new(),
+            name: None,
+            open: false,
+            edges_color: None,
         }
     }
 
     fn from_points(points: Vec<Point>) -> RawPolygonCoords {
-        let coords = points.iter().map(|p| RawCoord { x: p.pos.x, y: p.pos.y }).collect();
+        let coords = points.iter().map(|p| RawCoord { x: p.pos.x, y: p.pos.y, pinned: p.pinned }).collect();
         RawPolygonCoords {
             coords,
+            fill: None,
+            transform: None,
+            group: None,
+            constraints: Vec::new(),
+            bulges: Vec::new(),
+            angles: Vec::new(),
+            name: None,
+            open: false,
+            edges_color: None,
+        }
+    }
+
+    /// Parses one "x y" pair per line. Commas, semicolons and tabs are accepted as
+    /// separators as well, so spreadsheet and CSV clipboard contents can be pasted.
+    pub fn parse(text: &str) -> Result<RawPolygonCoords, String> {
+        let mut points = Vec::new();
+        for (line_id, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let numbers: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                .filter(|s| !s.is_empty())
+                .collect();
+            if numbers.len() != 2 {
+                return Err(format!("Line {}: expected two numbers", line_id + 1));
+            }
+            match (numbers[0].parse::<f32>(), numbers[1].parse::<f32>()) {
+                (Ok(x), Ok(y)) if x.is_finite() && y.is_finite() => points.push(sf::Vector2f::new(x, y)),
+                _ => return Err(format!("Line {}: \"{}\" is not a valid coordinate pair", line_id + 1, line)),
+            }
+        }
+
+        if points.len() < 3 {
+            return Err(PolygonError::TooFewPoints.to_string());
+        }
+        Ok(RawPolygonCoords::from_sf_points(points))
+    }
+
+    pub fn points(&self) -> Vec<sf::Vector2f> {
+        self.coords.iter().map(|c| sf::Vector2f::new(c.x, c.y)).collect()
+    }
+
+    /// Fits the coords into the [0,1]x[0,1] bounding box and stores the inverse transform.
+    /// Aspect ratio is preserved unless "stretch" is set.
+    pub fn normalized(mut self, stretch: bool) -> RawPolygonCoords {
+        self = self.denormalized();
+        if self.coords.is_empty() {
+            return self;
+        }
+
+        let mut min = [f32::INFINITY, f32::INFINITY];
+        let mut max = [f32::NEG_INFINITY, f32::NEG_INFINITY];
+        for coord in self.coords.iter() {
+            min = [min[0].min(coord.x), min[1].min(coord.y)];
+            max = [max[0].max(coord.x), max[1].max(coord.y)];
+        }
+
+        let mut scale = [max[0] - min[0], max[1] - min[1]];
+        if !stretch {
+            let size = scale[0].max(scale[1]);
+            scale = [size, size];
+        }
+        // Degenerate bounding boxes are only translated
+        for s in scale.iter_mut() {
+            if *s == 0. {
+                *s = 1.;
+            }
+        }
+
+        for coord in self.coords.iter_mut() {
+            coord.x = (coord.x - min[0]) / scale[0];
+            coord.y = (coord.y - min[1]) / scale[1];
+        }
+        self.transform = Some(RawTransform { origin: min, scale });
+        self
+    }
+
+    /// Applies the stored transform (if any) bringing the coords back to the original space.
+    pub fn denormalized(mut self) -> RawPolygonCoords {
+        if let Some(transform) = self.transform.take() {
+            for coord in self.coords.iter_mut() {
+                coord.x = coord.x * transform.scale[0] + transform.origin[0];
+                coord.y = coord.y * transform.scale[1] + transform.origin[1];
+            }
+        }
+        self
+    }
+
+    /// Denormalized copy translated so that the bounding box of the coords is centered at
+    /// the given point.
+    pub fn centered_at(self, center: sf::Vector2f) -> RawPolygonCoords {
+        let mut raw = self.denormalized();
+        if raw.coords.is_empty() {
+            return raw;
+        }
+
+        let mut min = [f32::INFINITY, f32::INFINITY];
+        let mut max = [f32::NEG_INFINITY, f32::NEG_INFINITY];
+        for coord in raw.coords.iter() {
+            min = [min[0].min(coord.x), min[1].min(coord.y)];
+            max = [max[0].max(coord.x), max[1].max(coord.y)];
+        }
+        let shift = [center.x - (min[0] + max[0]) / 2., center.y - (min[1] + max[1]) / 2.];
+        for coord in raw.coords.iter_mut() {
+            coord.x += shift[0];
+            coord.y += shift[1];
+        }
+        raw
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum FillStyle {
+    None,
+    Solid,
+    // Tiled texture, loaded from Polygon::fill_texture_path
+    Texture,
+    // Parallel lines clipped to the interior
+    Hatch,
+}
+
+pub const DEFAULT_STYLE_PATH: &str = "res/default_style.json";
+
+/// Soft shadow drawn behind the filled polygons. The blur is approximated by translucent
+/// copies of the silhouette spread around the offset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropShadow {
+    pub enabled: bool,
+    pub offset: sf::Vector2f,
+    // Opacity of the shadow core, in [0, 1]
+    pub opacity: f32,
+    // Radius of the spread of the copies, 0 gives a hard shadow
+    pub softness: f32,
+}
+
+impl DropShadow {
+    pub fn new() -> DropShadow {
+        DropShadow {
+            enabled: false,
+            offset: sf::Vector2f::new(style::SHADOW_OFFSET, style::SHADOW_OFFSET),
+            opacity: style::SHADOW_OPACITY,
+            softness: style::SHADOW_SOFTNESS,
+        }
+    }
+
+    // Offsets of the copies with the opacity of a single copy
+    fn layers(&self) -> (Vec<sf::Vector2f>, f32) {
+        if self.softness <= 0. {
+            return (vec![self.offset], self.opacity);
+        }
+        let mut offsets = vec![self.offset];
+        for i in 0..style::SHADOW_LAYERS {
+            let angle = i as f32 / style::SHADOW_LAYERS as f32 * std::f32::consts::TAU;
+            offsets.push(self.offset + sf::Vector2f::new(angle.cos(), angle.sin()) * self.softness);
+        }
+        // The copies overlap in the core, together they reach the requested opacity there
+        let alpha = 1. - (1. - self.opacity).powf(1. / offsets.len() as f32);
+        (offsets, alpha)
+    }
+}
+
+impl Default for DropShadow {
+    fn default() -> DropShadow {
+        DropShadow::new()
+    }
+}
+
+/// User configurable look of the newly created polygons and the way they are drawn.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PolygonStyle {
+    pub edges_color: [u8; 3],
+    pub fill_style: FillStyle,
+    pub fill_color: [u8; 4],
+    // Distance from the first vertex at which the new polygon snaps closed
+    #[serde(default = "default_close_radius")]
+    pub close_radius: f32,
+}
+
+fn default_close_radius() -> f32 {
+    style::POLY_CLOSE_RADIUS
+}
+
+impl PolygonStyle {
+    pub fn new() -> PolygonStyle {
+        PolygonStyle {
+            edges_color: [style::LINES_COLOR.r, style::LINES_COLOR.g, style::LINES_COLOR.b],
+            fill_style: FillStyle::None,
+            fill_color: [style::FILL_COLOR.r, style::FILL_COLOR.g, style::FILL_COLOR.b, style::FILL_COLOR.a],
+            close_radius: style::POLY_CLOSE_RADIUS,
+        }
+    }
+
+    /// Loads the saved defaults, a missing or malformed file results in the built-in style.
+    pub fn load() -> PolygonStyle {
+        match fs::read_to_string(DEFAULT_STYLE_PATH) {
+            Ok(contents) => from_str(&contents).unwrap_or_else(|err| {
+                log::warn!("Error parsing the default polygon style: {}", err);
+                PolygonStyle::new()
+            }),
+            Err(_) => PolygonStyle::new(),
         }
     }
+
+    pub fn save(&self) {
+        let json_string = to_string(self).unwrap();
+        if let Err(err) = fs::write(DEFAULT_STYLE_PATH, json_string) {
+            log::warn!("Error writing the default polygon style: {}", err);
+        }
+    }
+
+    fn edges_color(&self) -> sf::Color {
+        sf::Color::rgb(self.edges_color[0], self.edges_color[1], self.edges_color[2])
+    }
+
+    fn apply(&self, polygon: &mut Polygon) {
+        polygon.set_edges_color(self.edges_color());
+        let [r, g, b, a] = self.fill_color;
+        polygon.set_fill_color(sf::Color::rgba(r, g, b, a));
+        polygon.set_fill_style(self.fill_style.clone());
+    }
+}
+
+impl Default for PolygonStyle {
+    fn default() -> PolygonStyle {
+        PolygonStyle::new()
+    }
 }
 
-#[derive(Clone)]
-#[derive(PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum EdgeConstraint {
     None,
     Horizontal,
     Vertical,
+    // Length of the edge captured when the constraint was set
+    FixedLength(f32),
+}
+
+impl EdgeConstraint {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdgeConstraint::None => "None",
+            EdgeConstraint::Horizontal => "Horizontal",
+            EdgeConstraint::Vertical => "Vertical",
+            EdgeConstraint::FixedLength(_) => "Fixed length",
+        }
+    }
+}
+
+/// Shape of the offset polygon around the convex corners.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum JoinStyle {
+    // The offset edges are extended until they meet, up to the miter limit
+    Miter,
+    // Arc around the corner
+    Round,
+    // The corner is cut by a single segment
+    Bevel,
+}
+
+impl JoinStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JoinStyle::Miter => "Miter",
+            JoinStyle::Round => "Round",
+            JoinStyle::Bevel => "Bevel",
+        }
+    }
+}
+
+/// Vertex order of a mirrored polygon. A reflection turns a counter-clockwise polygon
+/// clockwise, so the order is reversed either way, this only decides where it starts.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MirrorWinding {
+    // The first vertex stays the first one, the numbering looks like a mirror image
+    KeepFirstVertex,
+    // Plain reversal (as done by assert_ccw), the last vertex becomes the first one
+    Reverse,
 }
 
 struct Point<'a> {
@@ -59,12 +429,19 @@ struct Point<'a> {
     point_circle: sf::CircleShape<'a>,
     selection_circle: sf::CircleShape<'a>,
     is_selected: bool,
+    // Pinned points are skipped by the transforms
+    pinned: bool,
 
     // Defines edge constraint of en edge created by this point and the next point in a proper
     // polygon points vector.
     // None by default, None means that a polygon that point is the part of is not proper or
     // that there is no constraint on that edge.
     edge_constraint: EdgeConstraint,
+    // The edge starting at this point is a circular arc if non-zero. As in DXF, the bulge is
+    // tan of a quarter of the arc angle, positive for counter-clockwise arcs (y up).
+    bulge: f32,
+    // Interior angle (in radians) kept when the neighbors are moved
+    angle_constraint: Option<f32>,
 
     direction: sf::Vector2f,
     normal: sf::Vector2f,
@@ -89,7 +466,10 @@ impl<'a> Point<'a> {
             pos,
             selection_circle,
             is_selected: false,
+            pinned: false,
             edge_constraint: EdgeConstraint::None,
+            bulge: 0.,
+            angle_constraint: None,
             direction: sf::Vector2f::new(0., 0.),
             normal: sf::Vector2f::new(0., 0.),
             prev_normal: sf::Vector2f::new(0., 0.),
@@ -118,8 +498,7 @@ impl<'a> Point<'a> {
 
         self.normal = v12_perp;
         self.prev_normal = v01_perp;
-        self.offset_vec = my_math::vec_norm(&(v01_perp + v12_perp)) /
-            ((1. + v01_perp.dot(v12_perp)) / 2.).sqrt();
+        self.offset_vec = my_math::miter_vec(&v01_perp, &v12_perp, style::MAX_MITER_RATIO);
 
         if cross2(&v01, &v12) < 0. {
             self.direction = my_math::vec_norm(&(v01_perp + v12_perp));
@@ -128,6 +507,16 @@ impl<'a> Point<'a> {
         }
     }
 
+    pub fn set_pinned(&mut self, pinned: bool) {
+        self.pinned = pinned;
+        if pinned {
+            self.point_circle.set_outline_color(style::PINNED_POINT_COLOR);
+            self.point_circle.set_outline_thickness(style::PINNED_POINT_OUTLINE);
+        } else {
+            self.point_circle.set_outline_thickness(0.);
+        }
+    }
+
     pub fn draw_selection_circle(&self, target: &mut dyn RenderTarget) {
         target.draw(&self.selection_circle);
     }
@@ -143,7 +532,10 @@ impl<'a> Clone for Point<'a> {
             point_circle: self.point_circle.clone(),
             selection_circle: self.selection_circle.clone(),
             is_selected: self.is_selected.clone(),
+            pinned: self.pinned,
             edge_constraint: self.edge_constraint.clone(),
+            bulge: self.bulge,
+            angle_constraint: self.angle_constraint,
             direction: self.direction.clone(),
             normal: self.normal.clone(),
             prev_normal: self.prev_normal.clone(),
@@ -157,16 +549,36 @@ pub struct Polygon<'a> {
     lines_vb: sf::VertexBuffer,
     edges_color: sf::Color,
     show_last_line: bool,
+    // Finished polygon displayed as an open chain. It's a display option only, the area, the
+    // fill and the offset still treat the polygon as closed.
+    open: bool,
 
     edge_constraint_sprites: Vec<sf::RcSprite>,
     points_labels: Vec<sf::RcText>,
 
     nametag: Option<sf::RcText>,
 
+    // Fill
+    fill_style: FillStyle,
+    fill_color: sf::Color,
+    fill_texture_path: Option<String>,
+    // Size of a single texture tile in pixels, relative to the texture size
+    fill_texture_scale: f32,
+    fill_texture: Option<Rc<SfBox<sf::Texture>>>,
+    // Copy of the texture pixels for the CPU rasterizer
+    fill_image: Option<Rc<sf::Image>>,
+    hatch_angle: f32,
+    hatch_spacing: f32,
+    fill_vb: sf::VertexBuffer,
+
     name: String,
     // Resources references
     constraint_texture: Option<Rc<RcTexture>>,
     font: Option<Rc<RcFont>>,
+
+    // Number of times the lines vertex buffer was regenerated
+    #[cfg(test)]
+    lines_vb_builds: usize,
 }
 
 impl<'a> Polygon<'a> {
@@ -176,27 +588,141 @@ impl<'a> Polygon<'a> {
             lines_vb: sf::VertexBuffer::new(sf::PrimitiveType::LINE_STRIP, 0, sf::VertexBufferUsage::DYNAMIC),
             edges_color: style::LINES_COLOR,
             show_last_line: true,
+            open: false,
             edge_constraint_sprites: Vec::new(),
             points_labels: Vec::new(),
             constraint_texture: None,
             font: None,
             nametag: None,
+            fill_style: FillStyle::None,
+            fill_color: style::FILL_COLOR,
+            fill_texture_path: None,
+            fill_texture_scale: 1.0,
+            fill_texture: None,
+            fill_image: None,
+            hatch_angle: style::HATCH_ANGLE,
+            hatch_spacing: style::HATCH_SPACING,
+            fill_vb: sf::VertexBuffer::new(sf::PrimitiveType::TRIANGLES, 0, sf::VertexBufferUsage::DYNAMIC),
             name: "Polygon".to_string(),
+            #[cfg(test)]
+            lines_vb_builds: 0,
         }
     }
 
     pub fn set_points_from_raw(&mut self, raw_polygon: RawPolygonCoords) {
-        self.points = raw_polygon.coords.iter().map(|coord| Point::new(sf::Vector2f::new(coord.x, coord.y))).collect();
+        let raw_polygon = raw_polygon.denormalized();
+        self.open = raw_polygon.open;
+        self.points = raw_polygon.coords
+            .iter()
+            .map(|coord| {
+                let mut point = Point::new(sf::Vector2f::new(coord.x, coord.y));
+                point.set_pinned(coord.pinned);
+                point
+            })
+            .collect();
+        // Constraints not matching the coords are dropped
+        if raw_polygon.constraints.len() == self.points.len() {
+            for (point, constraint) in self.points.iter_mut().zip(raw_polygon.constraints) {
+                point.edge_constraint = constraint;
+            }
+        }
+        if raw_polygon.bulges.len() == self.points.len() {
+            for (point, bulge) in self.points.iter_mut().zip(raw_polygon.bulges) {
+                point.bulge = bulge;
+            }
+        }
+        if raw_polygon.angles.len() == self.points.len() {
+            for (point, angle) in self.points.iter_mut().zip(raw_polygon.angles) {
+                point.angle_constraint = angle;
+            }
+        }
+        if let Some(fill) = raw_polygon.fill {
+            self.fill_color = sf::Color::rgba(fill.color[0], fill.color[1], fill.color[2], fill.color[3]);
+            self.fill_texture_scale = fill.texture_scale;
+            if let Some(path) = fill.texture_path {
+                self.load_fill_texture(&path);
+            }
+            if let Some(hatch) = fill.hatch {
+                self.hatch_angle = hatch.angle;
+                self.hatch_spacing = hatch.spacing;
+            }
+            self.fill_style = fill.style;
+        }
+        if let Some([r, g, b]) = raw_polygon.edges_color {
+            self.edges_color = sf::Color::rgb(r, g, b);
+        }
         self.generate_lines_vb();
         self.update_normals();
         self.update_labels();
     }
 
     pub fn get_raw(&self) -> RawPolygonCoords {
+        let mut fill = None;
+        if self.fill_style != FillStyle::None {
+            fill = Some(RawFill {
+                style: self.fill_style.clone(),
+                color: [self.fill_color.r, self.fill_color.g, self.fill_color.b, self.fill_color.a],
+                texture_path: self.fill_texture_path.clone(),
+                texture_scale: self.fill_texture_scale,
+                hatch: match self.fill_style {
+                    FillStyle::Hatch => Some(RawHatch { angle: self.hatch_angle, spacing: self.hatch_spacing }),
+                    _ => None,
+                },
+            });
+        }
+
+        let mut constraints = Vec::new();
+        if self.points.iter().any(|p| p.edge_constraint != EdgeConstraint::None) {
+            constraints = self.points.iter().map(|p| p.edge_constraint.clone()).collect();
+        }
+        let mut bulges = Vec::new();
+        if self.has_arcs() {
+            bulges = self.points.iter().map(|p| p.bulge).collect();
+        }
+        let mut angles = Vec::new();
+        if self.points.iter().any(|p| p.angle_constraint.is_some()) {
+            angles = self.points.iter().map(|p| p.angle_constraint).collect();
+        }
+
         RawPolygonCoords {
-            coords: self.points.iter().map(|p| RawCoord { x: p.pos.x, y: p.pos.y }).collect()
+            coords: self.points.iter().map(|p| RawCoord { x: p.pos.x, y: p.pos.y, pinned: p.pinned }).collect(),
+            fill,
+            transform: None,
+            group: None,
+            constraints,
+            bulges,
+            angles,
+            name: Some(self.name.clone()),
+            open: self.open,
+            edges_color: Some([self.edges_color.r, self.edges_color.g, self.edges_color.b]),
+        }
+    }
+    /// Rotates every point about the center, the angle is in radians. The constraints are
+    /// left to the caller.
+    pub fn rotate_around(&mut self, center: sf::Vector2f, radians: f32) {
+        for point in self.points.iter_mut() {
+            let pos = my_math::rotate_around(&point.pos, &center, radians);
+            point.update_pos(pos);
+        }
+        self.generate_lines_vb();
+        self.update_normals();
+        self.update_labels();
+        self.generate_fill_vb();
+    }
+
+    /// Scales every point from find_center by the factor. The constraints are left to the caller.
+    pub fn scale_about_center(&mut self, factor: f32) {
+        let center = self.find_center();
+        for point in self.points.iter_mut() {
+            let pos = center + (point.pos - center) * factor;
+            point.update_pos(pos);
         }
+        self.generate_lines_vb();
+        self.update_normals();
+        self.update_labels();
+        self.generate_fill_vb();
     }
+
     pub fn find_center(&self) -> sf::Vector2f {
         let mut result = sf::Vector2f::new(0., 0.);
         for point in self.points.iter() {
@@ -260,7 +786,7 @@ impl<'a> Polygon<'a> {
 
             for id in 0..self.points_count() {
                 let pos = self.get_point_pos(id as isize);
-                let vec = self.points[id].direction * 26.0;
+                let vec = self.points[id].direction * style::POINT_LABEL_DISTANCE;
                 self.points_labels[id].set_position(pos + vec);
             }
             let p = self.find_center();
@@ -293,141 +819,648 @@ impl<'a> Polygon<'a> {
         result
     }
 
+    /// Creates polygon from the given points, validating them first. Coincident consecutive
+    /// points are merged, self-crossing input or input enclosing less than min_area (such as
+    /// collinear points) is rejected and the winding is made counter-clockwise.
+    pub fn try_create(points: Vec<sf::Vector2f>, min_area: f32) -> Result<Polygon<'a>, PolygonError> {
+        let mut points = points;
+        points.dedup();
+        while points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() < 3 {
+            return Err(PolygonError::TooFewPoints);
+        }
+
+        let mut result = Polygon::create(points);
+        if result.is_self_crossing() {
+            return Err(PolygonError::WouldSelfIntersect);
+        }
+        if result.area() < min_area {
+            return Err(PolygonError::Degenerate);
+        }
+        result.assert_ccw();
+
+        Ok(result)
+    }
+
     fn generate_lines_vb(&mut self) {
         if self.points_count() == 0 {
             return;
         }
 
-        let mut vertices: Vec<sf::Vertex> = self.points
+        let vertices: Vec<sf::Vertex> = self.edges_chain(self.show_last_line)
             .iter()
-            .map(|p| sf::Vertex::new(
-                p.pos.clone(),
+            .map(|pos| sf::Vertex::new(
+                *pos,
                 self.edges_color,
                 sf::Vector2f::new(0., 0.),
             ))
             .collect();
 
-        let mut len = self.points_count();
-        if self.show_last_line {
-            vertices.push(sf::Vertex::new(self.points[0].pos, self.edges_color, sf::Vector2f::new(0.0, 0.0)));
-            len += 1;
-        }
-
         self.lines_vb = sf::VertexBuffer::new(
             sf::PrimitiveType::LINE_STRIP,
-            len as u32,
+            vertices.len() as u32,
             sf::VertexBufferUsage::DYNAMIC,
         );
         self.lines_vb.update(&vertices, 0);
+        #[cfg(test)]
+        {
+            self.lines_vb_builds += 1;
+        }
+        self.generate_fill_vb();
     }
 
-    pub fn show_last_line(&mut self, flag: bool) {
-        if self.show_last_line == flag {
+    fn generate_fill_vb(&mut self) {
+        if self.fill_style == FillStyle::None {
             return;
         }
-        self.show_last_line = flag;
-        self.generate_lines_vb();
-    }
 
-    pub fn points_count(&self) -> usize {
-        self.points.len()
-    }
+        if self.fill_style == FillStyle::Hatch {
+            let vertices: Vec<sf::Vertex> = self.hatch_segments()
+                .iter()
+                .flat_map(|(p0, p1)| [*p0, *p1])
+                .map(|pos| sf::Vertex::with_pos_color(pos, self.fill_color))
+                .collect();
+            self.fill_vb = sf::VertexBuffer::new(
+                sf::PrimitiveType::LINES,
+                vertices.len() as u32,
+                sf::VertexBufferUsage::DYNAMIC,
+            );
+            self.fill_vb.update(&vertices, 0);
+            return;
+        }
 
-    /// Makes id cyclic.
-    pub fn fix_index(&self, id: isize) -> usize {
-        return (id.rem_euclid(self.points_count() as isize)) as usize;
+        let positions = self.outline();
+        let triangles = my_math::ear_clipping(&positions);
+        let color = match self.fill_style {
+            FillStyle::Texture => sf::Color::WHITE,
+            _ => self.fill_color,
+        };
+
+        // Texture coordinates are derived from the vertex positions, the texture is repeated
+        let vertices: Vec<sf::Vertex> = triangles
+            .iter()
+            .flatten()
+            .map(|id| sf::Vertex::new(
+                positions[*id],
+                color,
+                positions[*id] / self.fill_texture_scale,
+            ))
+            .collect();
+
+        self.fill_vb = sf::VertexBuffer::new(
+            sf::PrimitiveType::TRIANGLES,
+            vertices.len() as u32,
+            sf::VertexBufferUsage::DYNAMIC,
+        );
+        self.fill_vb.update(&vertices, 0);
     }
 
-    /// Returns point's position, id is cyclic.
-    pub fn get_point_pos(&self, id: isize) -> sf::Vector2f {
-        self.points[self.fix_index(id)].pos
+    /// Hatching lines clipped to the interior (even-odd rule). The lines are anchored
+    /// to the origin, so the pattern doesn't slide when the polygon is moved.
+    pub fn hatch_segments(&self) -> Vec<(sf::Vector2f, sf::Vector2f)> {
+        let mut result = Vec::new();
+        if self.points.len() < 3 || self.hatch_spacing <= 0. {
+            return result;
+        }
+
+        // Screen y axis points down
+        let angle = self.hatch_angle.to_radians();
+        let dir = sf::Vector2f::new(angle.cos(), -angle.sin());
+        let normal = sf::Vector2f::new(-dir.y, dir.x);
+
+        let outline = self.outline();
+        let projections: Vec<f32> = outline.iter().map(|p| my_math::dot_prod(p, &normal)).collect();
+        let min = projections.iter().cloned().fold(f32::MAX, f32::min);
+        let max = projections.iter().cloned().fold(f32::MIN, f32::max);
+
+        let mut t = (min / self.hatch_spacing).ceil() * self.hatch_spacing;
+        while t <= max {
+            let mut crossings: Vec<f32> = Vec::new();
+            for i in 0..outline.len() {
+                let j = (i + 1) % outline.len();
+                let (sp, sq) = (projections[i] - t, projections[j] - t);
+                // Half-open test, so a line through a vertex is counted once
+                if (sp < 0.) != (sq < 0.) {
+                    let (p, q) = (outline[i], outline[j]);
+                    let x = p + (q - p) * (sp / (sp - sq));
+                    crossings.push(my_math::dot_prod(&x, &dir));
+                }
+            }
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            let base = normal * t;
+            for pair in crossings.chunks_exact(2) {
+                result.push((base + dir * pair[0], base + dir * pair[1]));
+            }
+            t += self.hatch_spacing;
+        }
+
+        result
     }
-    pub fn get_offset_vec(&self, id: isize) -> sf::Vector2f { self.points[self.fix_index(id)].offset_vec }
 
-    pub fn get_edge_constraint(&self, id: isize) -> EdgeConstraint {
-        self.points[self.fix_index(id)].edge_constraint.clone()
+    pub fn hatch_angle(&self) -> f32 {
+        self.hatch_angle
     }
-    pub fn set_edge_contsraint(&mut self, id: isize, constraint: EdgeConstraint) {
-        let id = self.fix_index(id);
-        self.points[id].edge_constraint = constraint;
+
+    pub fn set_hatch_angle(&mut self, angle: f32) {
+        if self.hatch_angle == angle {
+            return;
+        }
+        self.hatch_angle = angle;
+        self.generate_fill_vb();
     }
-    pub fn push_point_with_pos(&mut self, point_pos: sf::Vector2f) {
-        self.points.push(Point::new(point_pos));
-        self.generate_lines_vb();
-        self.update_normals();
-        self.update_labels();
+
+    pub fn hatch_spacing(&self) -> f32 {
+        self.hatch_spacing
     }
 
-    /// Inserts at "id" index. "id" is cyclic.
-    pub fn insert_point_with_pos(&mut self, id: isize, point_pos: sf::Vector2f) {
-        self.points.insert(self.fix_index(id), Point::new(point_pos));
-        self.generate_lines_vb();
-        self.update_normals();
-        self.update_labels();
+    pub fn set_hatch_spacing(&mut self, spacing: f32) {
+        if self.hatch_spacing == spacing || spacing < style::HATCH_MIN_SPACING {
+            return;
+        }
+        self.hatch_spacing = spacing;
+        self.generate_fill_vb();
     }
 
-    /// Removes a point with the given id
-    pub fn remove_point(&mut self, id: isize) {
-        self.points.remove(self.fix_index(id));
-        self.generate_lines_vb();
-        self.update_normals();
-        self.update_labels();
+    pub fn fill_style(&self) -> FillStyle {
+        self.fill_style.clone()
     }
 
+    pub fn set_fill_style(&mut self, fill_style: FillStyle) {
+        if self.fill_style == fill_style {
+            return;
+        }
+        self.fill_style = fill_style;
+        self.generate_fill_vb();
+    }
 
-    fn update_vertex(&mut self, point_pos: sf::Vector2f, color: sf::Color, index: isize) {
-        let index = self.fix_index(index);
+    pub fn fill_color(&self) -> sf::Color {
+        self.fill_color
+    }
 
-        if self.show_last_line && index == 0 {
-            // Update points
-            self.points[0].update_pos(point_pos);
+    pub fn set_fill_color(&mut self, fill_color: sf::Color) {
+        if self.fill_color == fill_color {
+            return;
+        }
+        self.fill_color = fill_color;
+        self.generate_fill_vb();
+    }
 
-            // Update first in vbo
-            self.lines_vb.update(&[sf::Vertex::new(
-                point_pos,
-                color,
-                sf::Vector2f::new(0.0, 0.0))], 0);
+    pub fn fill_texture_path(&self) -> Option<&String> {
+        self.fill_texture_path.as_ref()
+    }
 
-            // Update last in vbo
-            self.lines_vb.update(&[sf::Vertex::new(
-                point_pos,
-                color,
-                sf::Vector2f::new(0.0, 0.0))], self.points.len() as u32);
-        } else {
-            self.points[index].update_pos(point_pos);
+    pub fn fill_texture_scale(&self) -> f32 {
+        self.fill_texture_scale
+    }
 
-            // Update in vbo
-            self.lines_vb.update(&[sf::Vertex::new(
-                point_pos,
-                color,
-                sf::Vector2f::new(0.0, 0.0))], index as u32);
+    pub fn set_fill_texture_scale(&mut self, scale: f32) {
+        if self.fill_texture_scale == scale || scale <= 0. {
+            return;
         }
-        self.update_normals();
-        self.update_labels();
+        self.fill_texture_scale = scale;
+        self.generate_fill_vb();
     }
 
-    fn update_last_vertex(&mut self, point_pos: sf::Vector2f, color: sf::Color) {
-        self.update_vertex(point_pos, color, self.points_count() as isize - 1)
+    /// Loads a tiled fill texture. On failure the previous texture is kept.
+    pub fn load_fill_texture(&mut self, path: &str) -> bool {
+        match sf::Texture::from_file(path) {
+            Ok(mut texture) => {
+                texture.set_repeated(true);
+                self.fill_texture = Some(Rc::new(texture));
+                self.fill_image = sf::Image::from_file(path).map(Rc::new);
+                self.fill_texture_path = Some(path.to_string());
+                true
+            }
+            Err(_) => {
+                log::warn!("Couldn't load the fill texture: {}", path);
+                false
+            }
+        }
     }
 
-    pub fn update_point_pos(&mut self, point_pos: sf::Vector2f, index: isize) {
-        self.update_vertex(point_pos, self.edges_color, index)
+    pub fn is_open(&self) -> bool {
+        self.open
     }
 
-    pub fn update_last_point_pos(&mut self, point_pos: sf::Vector2f) {
-        self.update_point_pos(point_pos, self.points_count() as isize - 1)
+    /// Hides (or shows back) the closing edge of a finished polygon.
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.show_last_line(!open);
     }
 
-    pub fn set_edges_color(&mut self, edges_color: sf::Color) {
-        if edges_color == self.edges_color {
+    pub fn show_last_line(&mut self, flag: bool) {
+        if self.show_last_line == flag {
             return;
         }
-
-        self.edges_color = edges_color;
+        self.show_last_line = flag;
         self.generate_lines_vb();
     }
 
-    pub fn is_proper(&self) -> bool {
+    pub fn points_count(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Makes id cyclic.
+    pub fn fix_index(&self, id: isize) -> usize {
+        return (id.rem_euclid(self.points_count() as isize)) as usize;
+    }
+
+    /// Returns point's position, id is cyclic.
+    pub fn get_point_pos(&self, id: isize) -> sf::Vector2f {
+        self.points[self.fix_index(id)].pos
+    }
+    pub fn get_offset_vec(&self, id: isize) -> sf::Vector2f { self.points[self.fix_index(id)].offset_vec }
+    pub fn get_normal(&self, id: isize) -> sf::Vector2f { self.points[self.fix_index(id)].normal }
+    pub fn get_prev_normal(&self, id: isize) -> sf::Vector2f { self.points[self.fix_index(id)].prev_normal }
+
+    /// Closest pair of points of the boundaries of the two polygons, the first point
+    /// lies on self. The points are equal if the polygons overlap, that is if the boundaries
+    /// intersect or one of the polygons contains the other.
+    pub fn closest_points_to(&self, other: &Polygon) -> Option<(sf::Vector2f, sf::Vector2f)> {
+        let mut result: Option<(sf::Vector2f, sf::Vector2f)> = None;
+        let mut min_dist = f32::MAX;
+        for i in 0..self.points_count() as isize {
+            let (a0, a1) = (self.get_point_pos(i), self.get_point_pos(i + 1));
+            for j in 0..other.points_count() as isize {
+                let (b0, b1) = (other.get_point_pos(j), other.get_point_pos(j + 1));
+                let (p, q) = my_math::segments_closest_points(&a0, &a1, &b0, &b1);
+                let dist = my_math::distance2(&p, &q);
+                if dist < min_dist {
+                    min_dist = dist;
+                    result = Some((p, q));
+                }
+            }
+        }
+        if self.contains_polygon(other) || other.contains_polygon(self) {
+            return result.map(|(_, q)| (q, q));
+        }
+        result
+    }
+
+    /// Projects pos onto the edge starting at the point "id" (id is cyclic). Returns the
+    /// parametric position of the projection (0..1) and the distance between pos and the edge.
+    /// None if the projection doesn't fall onto the edge.
+    pub fn project_on_edge(&self, id: isize, pos: sf::Vector2f) -> Option<(f32, f32)> {
+        // Arcs are projected onto their sampled segments, t runs along the whole edge
+        let path = self.edge_path(id);
+        let segments = path.len() - 1;
+        let mut result: Option<(f32, f32)> = None;
+        for k in 0..segments {
+            let v01 = path[k + 1] - path[k];
+            let v0m = pos - path[k];
+
+            let len2 = my_math::vec_len2(&v01);
+            if len2 == 0.0 {
+                continue;
+            }
+
+            // Only the ends of the edge bound the projection, the joints between the segments don't
+            let t = my_math::dot_prod(&v01, &v0m) / len2;
+            if (k == 0 && t < 0.) || (k + 1 == segments && t > 1.) {
+                continue;
+            }
+            let t = t.clamp(0., 1.);
+
+            let dist = my_math::vec_len(&(v0m - v01 * t));
+            if result.is_none_or(|(_, min_dist)| dist < min_dist) {
+                result = Some(((k as f32 + t) / segments as f32, dist));
+            }
+        }
+        result
+    }
+
+    /// Position at t (0..1) along the drawn edge starting at id, arcs included.
+    pub fn edge_point(&self, id: isize, t: f32) -> sf::Vector2f {
+        let path = self.edge_path(id);
+        let segments = path.len() - 1;
+        let scaled = t.clamp(0., 1.) * segments as f32;
+        let k = (scaled as usize).min(segments - 1);
+        path[k] + (path[k + 1] - path[k]) * (scaled - k as f32)
+    }
+
+    // The sampled edge together with its end
+    pub(crate) fn edge_path(&self, id: isize) -> Vec<sf::Vector2f> {
+        let mut path = self.edge_samples(id);
+        path.push(self.get_point_pos(id + 1));
+        path
+    }
+
+    pub fn get_edge_bulge(&self, id: isize) -> f32 {
+        self.points[self.fix_index(id)].bulge
+    }
+
+    /// Turns the edge starting at id into an arc, 0 makes it straight again.
+    pub fn set_edge_bulge(&mut self, id: isize, bulge: f32) {
+        let id = self.fix_index(id);
+        self.points[id].bulge = bulge;
+        self.generate_lines_vb();
+    }
+
+    /// Sets the bulges of all of the edges at once, the buffers are rebuilt once.
+    pub fn set_edge_bulges(&mut self, bulges: &[f32]) {
+        for (point, bulge) in self.points.iter_mut().zip(bulges.iter()) {
+            point.bulge = *bulge;
+        }
+        self.generate_lines_vb();
+    }
+
+    pub fn has_arcs(&self) -> bool {
+        self.points.iter().any(|p| p.bulge != 0.)
+    }
+
+    /// The start of the edge followed by the points sampled along it, the end is left out.
+    /// Straight edges give just the start.
+    pub fn edge_samples(&self, id: isize) -> Vec<sf::Vector2f> {
+        let id = self.fix_index(id);
+        let p0 = self.points[id].pos;
+        let p1 = self.get_point_pos(id as isize + 1);
+        let bulge = self.points[id].bulge;
+        let chord = my_math::distance(&p0, &p1);
+        if bulge == 0. || chord == 0. {
+            return vec![p0];
+        }
+
+        let angle = 4. * bulge.atan();
+        let radius = chord / (2. * (angle / 2.).sin());
+        let sagitta = bulge * chord / 2.;
+        // Counter-clockwise arcs (y up) bulge to the right of the chord
+        let right = sf::Vector2f::new(p1.y - p0.y, p0.x - p1.x) / chord;
+        let center = (p0 + p1) / 2. + right * (sagitta - radius);
+
+        let arc_length = (radius * angle).abs();
+        let segments = ((arc_length / style::ARC_SEGMENT_LENGTH).ceil() as usize).clamp(2, style::MAX_ARC_SEGMENTS);
+        (0..segments)
+            .map(|i| my_math::rotate_around(&p0, &center, angle * i as f32 / segments as f32))
+            .collect()
+    }
+
+    /// Positions of the drawn edges with the arcs sampled. The first point is repeated at the
+    /// end if the chain is closed.
+    pub fn edges_chain(&self, closed: bool) -> Vec<sf::Vector2f> {
+        let count = self.points_count();
+        let mut result = Vec::new();
+        for i in 0..count {
+            if i + 1 == count && !closed {
+                result.push(self.points[i].pos);
+            } else {
+                result.extend(self.edge_samples(i as isize));
+            }
+        }
+        if closed && count > 0 {
+            result.push(self.points[0].pos);
+        }
+        result
+    }
+
+    /// The closed outline with the arcs sampled, without the repeated first point.
+    pub fn outline(&self) -> Vec<sf::Vector2f> {
+        let mut result = self.edges_chain(true);
+        result.pop();
+        result
+    }
+
+    pub fn get_edge_constraint(&self, id: isize) -> EdgeConstraint {
+        self.points[self.fix_index(id)].edge_constraint.clone()
+    }
+    pub fn set_edge_contsraint(&mut self, id: isize, constraint: EdgeConstraint) {
+        let id = self.fix_index(id);
+        self.points[id].edge_constraint = constraint;
+    }
+    pub fn push_point_with_pos(&mut self, point_pos: sf::Vector2f) {
+        self.points.push(Point::new(point_pos));
+        self.generate_lines_vb();
+        self.update_normals();
+        self.update_labels();
+    }
+
+    /// Inserts at "id" index. "id" is cyclic.
+    pub fn insert_point_with_pos(&mut self, id: isize, point_pos: sf::Vector2f) {
+        self.straighten_split_edge(id);
+        self.points.insert(self.fix_index(id), Point::new(point_pos));
+        self.generate_lines_vb();
+        self.update_normals();
+        self.update_labels();
+    }
+
+    /// Inserts the points in order starting at "id" index, the buffers, normals and labels
+    /// are updated once at the end. "id" is cyclic.
+    pub fn insert_points_with_pos(&mut self, id: isize, points_pos: &[sf::Vector2f]) {
+        self.straighten_split_edge(id);
+        let id = self.fix_index(id);
+        for (i, &pos) in points_pos.iter().enumerate() {
+            self.points.insert(id + i, Point::new(pos));
+        }
+        self.generate_lines_vb();
+        self.update_normals();
+        self.update_labels();
+    }
+
+    // An arc doesn't survive splitting or merging of its edge, the parts are straight
+    fn straighten_split_edge(&mut self, id: isize) {
+        if self.points_count() > 0 {
+            let prev = self.fix_index(id - 1);
+            self.points[prev].bulge = 0.;
+        }
+    }
+
+    /// Removes a point with the given id
+    pub fn remove_point(&mut self, id: isize) {
+        self.straighten_split_edge(id);
+        self.points.remove(self.fix_index(id));
+        self.generate_lines_vb();
+        self.update_normals();
+        self.update_labels();
+    }
+
+    fn update_vertex(&mut self, point_pos: sf::Vector2f, color: sf::Color, index: isize) {
+        let index = self.fix_index(index);
+
+        if self.has_arcs() {
+            // The samples of the neighboring arcs move as well
+            self.points[index].update_pos(point_pos);
+            self.generate_lines_vb();
+        } else if self.show_last_line && index == 0 {
+            // Update points
+            self.points[0].update_pos(point_pos);
+
+            // Update first in vbo
+            self.lines_vb.update(&[sf::Vertex::new(
+                point_pos,
+                color,
+                sf::Vector2f::new(0.0, 0.0))], 0);
+
+            // Update last in vbo
+            self.lines_vb.update(&[sf::Vertex::new(
+                point_pos,
+                color,
+                sf::Vector2f::new(0.0, 0.0))], self.points.len() as u32);
+        } else {
+            self.points[index].update_pos(point_pos);
+
+            // Update in vbo
+            self.lines_vb.update(&[sf::Vertex::new(
+                point_pos,
+                color,
+                sf::Vector2f::new(0.0, 0.0))], index as u32);
+        }
+        self.update_normals();
+        self.update_labels();
+        self.generate_fill_vb();
+    }
+
+    fn update_last_vertex(&mut self, point_pos: sf::Vector2f, color: sf::Color) {
+        self.update_vertex(point_pos, color, self.points_count() as isize - 1)
+    }
+
+    pub fn update_point_pos(&mut self, point_pos: sf::Vector2f, index: isize) {
+        self.update_vertex(point_pos, self.edges_color, index)
+    }
+
+    pub fn update_last_point_pos(&mut self, point_pos: sf::Vector2f) {
+        self.update_point_pos(point_pos, self.points_count() as isize - 1)
+    }
+
+    pub fn edges_color(&self) -> sf::Color {
+        self.edges_color
+    }
+
+    /// Regenerates the lines vertex buffer only if the color actually changes.
+    pub fn set_edges_color(&mut self, edges_color: sf::Color) {
+        if edges_color == self.edges_color {
+            return;
+        }
+
+        self.edges_color = edges_color;
+        self.generate_lines_vb();
+    }
+
+    /// Ear clipping triangulation as triangle corners. Empty unless the polygon is proper
+    /// and simple, the corners follow the counter-clockwise order kept by assert_ccw.
+    pub fn triangles(&self) -> Vec<[sf::Vector2f; 3]> {
+        if !self.is_proper() || self.is_self_crossing() {
+            return Vec::new();
+        }
+        self.triangulate()
+            .iter()
+            .map(|tri| tri.map(|id| self.points[id].pos))
+            .collect()
+    }
+
+    /// Triangles of the polygon as triples of point ids.
+    pub fn triangulate(&self) -> Vec<[usize; 3]> {
+        let positions: Vec<sf::Vector2f> = self.points.iter().map(|p| p.pos).collect();
+        my_math::ear_clipping(&positions)
+    }
+
+    /// Returns the absolute area of the polygon (shoelace formula).
+    pub fn area(&self) -> f32 {
+        let positions: Vec<sf::Vector2f> = self.points.iter().map(|p| p.pos).collect();
+        my_math::signed_area(&positions).abs()
+    }
+
+    /// Length of the closed outline.
+    pub fn perimeter(&self) -> f32 {
+        (0..self.points_count() as isize)
+            .map(|i| my_math::distance(&self.get_point_pos(i), &self.get_point_pos(i + 1)))
+            .sum()
+    }
+
+    /// Axis aligned bounding box of the points.
+    pub fn bounds(&self) -> sf::FloatRect {
+        let mut min = sf::Vector2f::new(f32::MAX, f32::MAX);
+        let mut max = sf::Vector2f::new(f32::MIN, f32::MIN);
+        for point in self.points.iter() {
+            min.x = min.x.min(point.pos.x);
+            min.y = min.y.min(point.pos.y);
+            max.x = max.x.max(point.pos.x);
+            max.y = max.y.max(point.pos.y);
+        }
+        if self.points.is_empty() {
+            return sf::FloatRect::new(0., 0., 0., 0.);
+        }
+        sf::FloatRect::new(min.x, min.y, max.x - min.x, max.y - min.y)
+    }
+
+    /// Winding number test. The points on an edge or a vertex, within
+    /// style::POINT_ON_EDGE_TOLERANCE, are always inside.
+    pub fn contains_point(&self, pos: sf::Vector2f) -> bool {
+        let mut winding = 0;
+        for i in 0..self.points_count() as isize {
+            let (a, b) = (self.get_point_pos(i), self.get_point_pos(i + 1));
+            let edge = b - a;
+            let side = cross2(&edge, &(pos - a));
+
+            // On the boundary
+            let length2 = my_math::dot_prod(&edge, &edge);
+            let t = my_math::dot_prod(&edge, &(pos - a));
+            if side.abs() <= style::POINT_ON_EDGE_TOLERANCE * length2.sqrt() && t >= 0. && t <= length2 {
+                return true;
+            }
+            if length2 == 0. && my_math::distance(&a, &pos) <= style::POINT_ON_EDGE_TOLERANCE {
+                return true;
+            }
+
+            // Upward edges crossing the ray with the point on their left count +1, downward -1
+            if a.y <= pos.y {
+                if b.y > pos.y && side > 0. {
+                    winding += 1;
+                }
+            } else if b.y <= pos.y && side < 0. {
+                winding -= 1;
+            }
+        }
+        winding != 0
+    }
+
+    /// Whether any edge of self properly crosses any edge of the other polygon.
+    pub fn crosses(&self, other: &Polygon) -> bool {
+        for i in 0..self.points_count() as isize {
+            let line1 = geo::geometry::Line::new(
+                geo::coord! {x: self.get_point_pos(i).x, y: self.get_point_pos(i).y},
+                geo::coord! {x: self.get_point_pos(i + 1).x, y: self.get_point_pos(i + 1).y},
+            );
+            for j in 0..other.points_count() as isize {
+                let line2 = geo::geometry::Line::new(
+                    geo::coord! {x: other.get_point_pos(j).x, y: other.get_point_pos(j).y},
+                    geo::coord! {x: other.get_point_pos(j + 1).x, y: other.get_point_pos(j + 1).y},
+                );
+                if let Some(LineIntersection::SinglePoint { is_proper: true, .. }) =
+                    geo::algorithm::line_intersection::line_intersection(line1, line2) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// The closed polygon as geo geometry, for the boolean operations.
+    pub fn to_geo(&self) -> geo::Polygon<f64> {
+        let coords: Vec<geo::Coord<f64>> = self.points
+            .iter()
+            .map(|p| geo::coord! {x: p.pos.x as f64, y: p.pos.y as f64})
+            .collect();
+        geo::Polygon::new(geo::LineString::new(coords), Vec::new())
+    }
+
+    /// Whether the other polygon lies entirely inside self: all of its vertices are inside
+    /// and the edges don't cross. The bounding boxes are compared first.
+    pub fn contains_polygon(&self, other: &Polygon) -> bool {
+        if !self.is_proper() || !other.is_proper() {
+            return false;
+        }
+        let (outer, inner) = (self.bounds(), other.bounds());
+        if inner.left < outer.left || inner.top < outer.top ||
+            inner.left + inner.width > outer.left + outer.width ||
+            inner.top + inner.height > outer.top + outer.height {
+            return false;
+        }
+        other.points.iter().all(|p| self.contains_point(p.pos)) && !self.crosses(other)
+    }
+
+    pub fn is_proper(&self) -> bool {
         if self.points.len() < 3 {
             return false;
         }
@@ -495,6 +1528,9 @@ impl<'a> Polygon<'a> {
         hash_map
     }
     pub fn is_self_crossing(&self) -> bool {
+        if self.has_arcs() {
+            return self.is_outline_self_crossing();
+        }
         for i in 0..self.points_count() as isize {
             let line1 = geo::geometry::Line::new(
                 geo::coord! {x: self.get_point_pos(i).x, y: self.get_point_pos(i).y},
@@ -525,6 +1561,26 @@ impl<'a> Polygon<'a> {
         false
     }
 
+    // Crossing test of the sampled arcs, the segments neighboring in the outline are skipped
+    fn is_outline_self_crossing(&self) -> bool {
+        let mut outline = self.edges_chain(true);
+        outline.pop();
+        let count = outline.len();
+        let segment = |i: usize| geo::geometry::Line::new(
+            geo::coord! {x: outline[i].x, y: outline[i].y},
+            geo::coord! {x: outline[(i + 1) % count].x, y: outline[(i + 1) % count].y},
+        );
+        for i in 0..count {
+            let end = if i == 0 { count - 1 } else { count };
+            for j in (i + 2)..end {
+                if geo::algorithm::line_intersection::line_intersection(segment(i), segment(j)).is_some() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
     pub fn assert_ccw(&mut self) -> bool {
         assert_eq!(self.is_proper(), true);
 
@@ -535,26 +1591,43 @@ impl<'a> Polygon<'a> {
         }
 
         if sum <= 0. {
-            self.points.reverse();
-            // Remap constraints
-            let constraints_cpy: Vec<EdgeConstraint> =
-                self.points.iter().map(|p| p.edge_constraint.clone()).collect();
-            for i in 0..self.points_count() as isize {
-                self.set_edge_contsraint(i, EdgeConstraint::None);
-                let next = self.fix_index(i + 1);
-                self.set_edge_contsraint(i, constraints_cpy[next].clone());
-            }
-
-
-            self.generate_lines_vb();
-            self.update_normals();
-            self.update_labels();
+            self.reverse_points(false);
             return true;
         }
 
         false
     }
 
+    /// Reverses the order of the points, the edge constraints are remapped so they stay
+    /// on the same edges. If keep_first is set, the first point stays the first one.
+    pub fn reverse_points(&mut self, keep_first: bool) {
+        let n = self.points_count();
+        if n == 0 {
+            return;
+        }
+        let constraints: Vec<EdgeConstraint> =
+            self.points.iter().map(|p| p.edge_constraint.clone()).collect();
+        let bulges: Vec<f32> = self.points.iter().map(|p| p.bulge).collect();
+
+        self.points.reverse();
+        let last = if keep_first {
+            self.points.rotate_right(1);
+            0
+        } else {
+            n - 1
+        };
+        // The edge i goes from the old point (last - i) to the old point (last - i - 1) now
+        for i in 0..n {
+            self.points[i].edge_constraint = constraints[(last + 2 * n - i - 1) % n].clone();
+            // Walked backwards the arc turns the other way
+            self.points[i].bulge = -bulges[(last + 2 * n - i - 1) % n];
+        }
+
+        self.generate_lines_vb();
+        self.update_normals();
+        self.update_labels();
+    }
+
     pub fn first_point_pos(&self) -> Option<sf::Vector2f> {
         if self.points_count() > 0 {
             return Some(self.points[0].pos);
@@ -571,42 +1644,231 @@ impl<'a> Polygon<'a> {
         self.lines_vb.draw(target, &Default::default());
     }
 
+    pub fn draw_fill(&self, target: &mut dyn sf::RenderTarget) {
+        match self.fill_style {
+            FillStyle::None => (),
+            FillStyle::Solid | FillStyle::Hatch => self.fill_vb.draw(target, &Default::default()),
+            FillStyle::Texture => {
+                if let Some(texture) = self.fill_texture.as_ref() {
+                    let mut states = sf::RenderStates::default();
+                    states.texture = Some(texture);
+                    self.fill_vb.draw(target, &states);
+                }
+            }
+        }
+    }
+
     pub fn draw_points(&self, target: &mut dyn sf::RenderTarget) {
         for point in &self.points {
             point.draw_point_circle(target);
         }
     }
 
-    pub fn draw_point_selection(&self, id: isize, target: &mut dyn RenderTarget) {
-        self.points[self.fix_index(id)].draw_selection_circle(target);
-    }
-
-    pub fn draw_labels(&self, target: &mut dyn RenderTarget) {
-        for (id, sprite) in self.edge_constraint_sprites.iter().enumerate() {
-            if self.points[id].edge_constraint != EdgeConstraint::None {
-                target.draw(sprite);
+    /// Debug overlay: draws the vectors computed in update_normals as arrows from every vertex.
+    /// The offset vector keeps its length (it grows at sharp corners), the rest are unit vectors.
+    pub fn draw_normals(&self, target: &mut dyn RenderTarget) {
+        fn push_arrow(vertices: &mut Vec<sf::Vertex>, from: sf::Vector2f, vec: sf::Vector2f, color: sf::Color) {
+            let len = my_math::vec_len(&vec);
+            if !len.is_finite() || len == 0. {
+                return;
             }
+            let to = from + vec * style::DEBUG_VECTOR_LENGTH;
+            let back = -vec / len * style::DEBUG_ARROW_HEAD;
+            for angle in [0.5f32, -0.5] {
+                let head = my_math::rotate_around(&(to + back), &to, angle);
+                vertices.push(sf::Vertex::with_pos_color(to, color));
+                vertices.push(sf::Vertex::with_pos_color(head, color));
+            }
+            vertices.push(sf::Vertex::with_pos_color(from, color));
+            vertices.push(sf::Vertex::with_pos_color(to, color));
         }
 
-        for point in self.points_labels.iter() {
-            target.draw(point);
+        let mut vertices: Vec<sf::Vertex> = Vec::new();
+        for point in self.points.iter() {
+            push_arrow(&mut vertices, point.pos, point.normal, style::NORMAL_COLOR);
+            push_arrow(&mut vertices, point.pos, point.prev_normal, style::PREV_NORMAL_COLOR);
+            push_arrow(&mut vertices, point.pos, point.offset_vec, style::OFFSET_VEC_COLOR);
+            push_arrow(&mut vertices, point.pos, point.direction, style::DIRECTION_COLOR);
         }
+        target.draw_primitives(&vertices, sf::PrimitiveType::LINES, &sf::RenderStates::default());
+    }
 
-        if self.nametag.is_some() {
-            target.draw(self.nametag.as_ref().unwrap());
+    /// Draws the index labels at the vertex positions.
+    pub fn draw_point_indices(&self, target: &mut dyn RenderTarget) {
+        for (id, label) in self.points_labels.iter().enumerate() {
+            let offset = self.points[id].direction * style::POINT_LABEL_DISTANCE;
+            let mut states = sf::RenderStates::default();
+            states.transform.translate(-offset.x, -offset.y);
+            target.draw_with_renderstates(label, &states);
         }
     }
 
+    pub fn is_point_pinned(&self, id: isize) -> bool {
+        self.points[self.fix_index(id)].pinned
+    }
+    pub fn set_point_pinned(&mut self, id: isize, pinned: bool) {
+        let id = self.fix_index(id);
+        self.points[id].set_pinned(pinned);
+    }
 
-    pub fn draw_edges_bresenham(&self, img_target: &mut sf::Image, line_painter: &mut LinePainter) {
-        let mut end = self.points_count();
-        if !self.show_last_line {
-            end -= 1;
+    pub fn get_angle_constraint(&self, id: isize) -> Option<f32> {
+        self.points[self.fix_index(id)].angle_constraint
+    }
+    pub fn set_angle_constraint(&mut self, id: isize, angle: Option<f32>) {
+        let id = self.fix_index(id);
+        self.points[id].angle_constraint = angle;
+    }
+
+    /// Interior angle at the point in radians, in the [0, 2π) range.
+    pub fn interior_angle(&self, id: isize) -> f32 {
+        let pos = self.get_point_pos(id);
+        let to_prev = self.get_point_pos(id - 1) - pos;
+        let to_next = self.get_point_pos(id + 1) - pos;
+        let angle = cross2(&to_prev, &to_next).atan2(my_math::dot_prod(&to_prev, &to_next))
+            .rem_euclid(std::f32::consts::TAU);
+        // The angle from the previous to the next edge is the exterior one for positive orientation
+        if self.is_positively_oriented() {
+            (std::f32::consts::TAU - angle).rem_euclid(std::f32::consts::TAU)
+        } else {
+            angle
+        }
+    }
+
+    /// Vertices whose interior angle is closer than max_angle (radians) to 0 or to a full
+    /// turn: needles sticking out of the polygon or into it.
+    pub fn find_spikes(&self, max_angle: f32) -> Vec<usize> {
+        if !self.is_proper() {
+            return Vec::new();
+        }
+        (0..self.points_count())
+            .filter(|&i| {
+                let angle = self.interior_angle(i as isize);
+                angle < max_angle || angle > std::f32::consts::TAU - max_angle
+            })
+            .collect()
+    }
+
+    /// Rotates the next (or the previous) neighbor around the point, so the interior angle at
+    /// the point becomes the given one. The length of the rotated edge is kept.
+    pub fn set_interior_angle(&mut self, id: isize, angle: f32, rotate_next: bool) {
+        let pos = self.get_point_pos(id);
+        let angle = if self.is_positively_oriented() {
+            std::f32::consts::TAU - angle
+        } else {
+            angle
+        };
+
+        let (fixed, free_id, angle) = if rotate_next {
+            (self.get_point_pos(id - 1), id + 1, angle)
+        } else {
+            (self.get_point_pos(id + 1), id - 1, -angle)
+        };
+        let free = self.get_point_pos(free_id);
+        if fixed == pos {
+            return;
+        }
+
+        let dir = my_math::vec_norm(&(my_math::rotate_around(&fixed, &pos, angle) - pos));
+        self.update_point_pos(pos + dir * my_math::distance(&pos, &free), free_id);
+    }
+
+    fn is_positively_oriented(&self) -> bool {
+        let positions: Vec<sf::Vector2f> = self.points.iter().map(|p| p.pos).collect();
+        my_math::signed_area(&positions) > 0.
+    }
+
+    pub fn draw_point_selection(&self, id: isize, target: &mut dyn RenderTarget) {
+        self.points[self.fix_index(id)].draw_selection_circle(target);
+    }
+
+    /// Draws the constraint icons, the index labels and the nametag. With indices_only the
+    /// labels are placed right at the vertices, since the point circles are hidden.
+    pub fn draw_labels(&self, target: &mut dyn RenderTarget, indices_only: bool) {
+        for (id, sprite) in self.edge_constraint_sprites.iter().enumerate() {
+            if self.points[id].edge_constraint != EdgeConstraint::None {
+                target.draw(sprite);
+            }
         }
-        for i in 0..end as isize {
-            line_painter.draw_line(self.get_point_pos(i), self.get_point_pos(i + 1), self.edges_color, img_target);
+
+        if indices_only {
+            self.draw_point_indices(target);
+        } else {
+            for point in self.points_labels.iter() {
+                target.draw(point);
+            }
+        }
+
+        if self.nametag.is_some() {
+            target.draw(self.nametag.as_ref().unwrap());
+        }
+    }
+
+
+    /// Draws the drop shadow of a filled polygon.
+    pub fn draw_shadow(&self, target: &mut dyn sf::RenderTarget, shadow: &DropShadow) {
+        if self.fill_style == FillStyle::None {
+            return;
+        }
+        let triangles = self.triangles();
+        let (offsets, alpha) = shadow.layers();
+        let color = sf::Color::rgba(style::SHADOW_COLOR.r, style::SHADOW_COLOR.g, style::SHADOW_COLOR.b, (alpha * 255.) as u8);
+        let vertices: Vec<sf::Vertex> = offsets
+            .iter()
+            .flat_map(|offset| triangles.iter().flatten().map(move |pos| sf::Vertex::with_pos_color(*pos + *offset, color)))
+            .collect();
+        target.draw_primitives(&vertices, sf::PrimitiveType::TRIANGLES, &sf::RenderStates::DEFAULT);
+    }
+
+    /// Rasterizes the drop shadow of a filled polygon.
+    pub fn draw_shadow_bresenham(&self, img_target: &mut sf::Image, line_painter: &mut LinePainter, shadow: &DropShadow) {
+        if self.fill_style == FillStyle::None || !self.is_proper() || self.is_self_crossing() {
+            return;
+        }
+        let (offsets, alpha) = shadow.layers();
+        for offset in offsets {
+            let points: Vec<sf::Vector2f> = self.points.iter().map(|p| p.pos + offset).collect();
+            line_painter.fill_polygon(&points, style::SHADOW_COLOR, alpha, img_target);
+        }
+    }
+
+    /// CPU counterpart of draw_fill, the texture is tiled the same way as on the GPU.
+    pub fn draw_fill_bresenham(&self, img_target: &mut sf::Image, line_painter: &mut LinePainter) {
+        match self.fill_style {
+            FillStyle::None => (),
+            FillStyle::Solid => {
+                let alpha = self.fill_color.a as f32 / 255.;
+                line_painter.fill_polygon(&self.outline(), self.fill_color, alpha, img_target);
+            }
+            FillStyle::Texture => {
+                let Some(image) = self.fill_image.as_ref() else {
+                    return;
+                };
+                let size = image.size();
+                if size.x == 0 || size.y == 0 {
+                    return;
+                }
+                line_painter.fill_polygon_with(&self.outline(), 1., img_target, |pos| {
+                    let texel = pos / self.fill_texture_scale;
+                    let x = (texel.x.floor() as i64).rem_euclid(size.x as i64) as u32;
+                    let y = (texel.y.floor() as i64).rem_euclid(size.y as i64) as u32;
+                    unsafe { image.pixel_at(x, y) }
+                });
+            }
+            FillStyle::Hatch => {
+                // The line style is meant for the edges
+                let line_style = line_painter.line_style();
+                line_painter.set_line_style(LineStyle::Solid);
+                for (p0, p1) in self.hatch_segments() {
+                    line_painter.draw_line(p0, p1, self.fill_color, img_target);
+                }
+                line_painter.set_line_style(line_style);
+            }
         }
     }
+
+    pub fn draw_edges_bresenham(&self, img_target: &mut sf::Image, line_painter: &mut LinePainter) {
+        line_painter.draw_chain(&self.edges_chain(self.show_last_line), self.edges_color, img_target);
+    }
 }
 
 impl<'a> Clone for Polygon<'a> {
@@ -627,14 +1889,75 @@ impl<'a> Clone for Polygon<'a> {
             lines_vb: self.lines_vb.clone(),
             edges_color: self.edges_color.clone(),
             show_last_line: self.show_last_line.clone(),
+            open: self.open,
             edge_constraint_sprites: self.edge_constraint_sprites.clone(),
             points_labels: self.points_labels.clone(),
             constraint_texture: new_txt,
             font: new_font,
             nametag: self.nametag.clone(),
+            fill_style: self.fill_style.clone(),
+            fill_color: self.fill_color,
+            fill_texture_path: self.fill_texture_path.clone(),
+            fill_texture_scale: self.fill_texture_scale,
+            fill_texture: self.fill_texture.clone(),
+            fill_image: self.fill_image.clone(),
+            hatch_angle: self.hatch_angle,
+            hatch_spacing: self.hatch_spacing,
+            fill_vb: self.fill_vb.clone(),
             name: self.name.clone(),
+            #[cfg(test)]
+            lines_vb_builds: self.lines_vb_builds,
+        }
+    }
+}
+
+/// Splits the chain would need so that the segment from its last point to "to" crosses none
+/// of its edges: every proper crossing, nearest first, cuts off the loop behind it. Each split
+/// is the crossed edge index and the crossing point, the indices refer to the chain already
+/// cut by the previous splits. None if the segment touches an edge in any other way.
+fn plan_splits(chain: &[sf::Vector2f], to: sf::Vector2f) -> Option<Vec<(usize, sf::Vector2f)>> {
+    let mut chain = chain.to_vec();
+    let mut splits = Vec::new();
+    while chain.len() >= 3 {
+        let from = chain[chain.len() - 1];
+        let line1 = geo::geometry::Line::new(
+            geo::coord! {x: from.x, y: from.y},
+            geo::coord! {x: to.x, y: to.y},
+        );
+
+        // The last edge shares the start of the segment
+        let mut nearest: Option<(usize, sf::Vector2f)> = None;
+        for i in 0..chain.len() - 2 {
+            let line2 = geo::geometry::Line::new(
+                geo::coord! {x: chain[i].x, y: chain[i].y},
+                geo::coord! {x: chain[i + 1].x, y: chain[i + 1].y},
+            );
+            match geo::algorithm::line_intersection::line_intersection(line1, line2) {
+                None => (),
+                Some(LineIntersection::SinglePoint { intersection, is_proper: true }) => {
+                    let pos = sf::Vector2f::new(intersection.x, intersection.y);
+                    let closer = match nearest {
+                        Some((_, prev)) => my_math::distance(&from, &pos) < my_math::distance(&from, &prev),
+                        None => true,
+                    };
+                    if closer {
+                        nearest = Some((i, pos));
+                    }
+                }
+                Some(_) => return None,
+            }
+        }
+
+        match nearest {
+            Some((i, pos)) => {
+                splits.push((i, pos));
+                chain.truncate(i + 1);
+                chain.push(pos);
+            }
+            None => break,
         }
     }
+    Some(splits)
 }
 
 pub struct PolygonObjectFactory<'s> {
@@ -650,6 +1973,21 @@ pub struct PolygonObjectFactory<'s> {
     // PolygonBuilder events
     is_line_intersecting: bool,
     entered_correct_vertex_region: bool,
+    // Splits done by the next click, only in the split at crossings mode
+    pending_splits: Vec<(usize, sf::Vector2f)>,
+    // Loops cut off by the splits, picked up by the state
+    split_off: Vec<PolygonObject<'s>>,
+
+    // Settings
+    min_area: f32,
+    // Crossing the chain cuts off the loop as a separate polygon instead of being refused
+    split_at_crossings: bool,
+    default_style: PolygonStyle,
+    // The default style differs from the saved one
+    default_style_changed: bool,
+
+    // Reason of the last rejected polygon completion
+    rejection: Option<String>,
 
     // Resources
     constraint_texture: Rc<sf::RcTexture>,
@@ -662,9 +2000,11 @@ impl<'a> PolygonObjectFactory<'a> {
     }
 
     pub fn new() -> PolygonObjectFactory<'a> {
-        let mut helper_circle = sf::CircleShape::new(style::POINT_DETECTION_RADIUS, 30);
+        let default_style = PolygonStyle::load();
+        // Shows the close region around the first vertex
+        let mut helper_circle = sf::CircleShape::new(default_style.close_radius, 30);
         helper_circle.set_fill_color(style::POINT_DETECTION_COLOR_CORRECT);
-        helper_circle.set_origin(sf::Vector2f::new(style::POINT_DETECTION_RADIUS, style::POINT_DETECTION_RADIUS));
+        helper_circle.set_origin(sf::Vector2f::new(default_style.close_radius, default_style.close_radius));
 
         let mut new_point_circle = sf::CircleShape::new(style::POINT_DETECTION_RADIUS, 30);
         new_point_circle.set_fill_color(style::POINTS_COLOR);
@@ -676,6 +2016,13 @@ impl<'a> PolygonObjectFactory<'a> {
             is_line_intersecting: false,
             curr_id: 0,
             entered_correct_vertex_region: false,
+            pending_splits: Vec::new(),
+            split_off: Vec::new(),
+            min_area: style::POLY_MIN_AREA,
+            split_at_crossings: false,
+            default_style,
+            default_style_changed: false,
+            rejection: None,
             helper_circle,
             new_line: sf::VertexBuffer::new(sf::PrimitiveType::LINES, 2, sf::VertexBufferUsage::DYNAMIC),
             new_line_points: [sf::Vector2f::new(0., 0.), sf::Vector2f::new(0., 0.)],
@@ -728,6 +2075,58 @@ impl<'a> PolygonObjectFactory<'a> {
     fn clear_draw_flags(&mut self) {
         self.entered_correct_vertex_region = false;
         self.is_line_intersecting = false;
+        self.pending_splits.clear();
+    }
+
+    /// Cuts the loops planned by update off the chain, the chain continues from the last
+    /// crossing point. Loops smaller than the min area are dropped.
+    // Positions of the chain once the pending splits are done
+    fn chain_after_splits(&self) -> Vec<sf::Vector2f> {
+        let poly = self.polygon.as_ref().unwrap();
+        let mut chain: Vec<sf::Vector2f> = (0..poly.points_count() as isize)
+            .map(|id| poly.get_point_pos(id))
+            .collect();
+        for (i, pos) in self.pending_splits.iter() {
+            chain.truncate(i + 1);
+            chain.push(*pos);
+        }
+        chain
+    }
+
+    fn split_loops(&mut self) {
+        for (i, pos) in std::mem::take(&mut self.pending_splits) {
+            let poly = self.polygon.as_mut().unwrap();
+            let count = poly.points_count();
+            let mut loop_points = vec![pos];
+            loop_points.extend((i + 1..count).map(|id| poly.get_point_pos(id as isize)));
+
+            while poly.points_count() > i + 1 {
+                poly.remove_point(poly.points_count() as isize - 1);
+            }
+            poly.push_point_with_pos(pos);
+
+            let mut loop_poly = Polygon::new();
+            loop_poly.set_points_from_raw(RawPolygonCoords::from_sf_points(loop_points));
+            let area = loop_poly.area();
+            if area < self.min_area {
+                self.rejection = Some(format!(
+                    "Dropped a loop, its area is too small ({:.1} < {:.1})", area, self.min_area
+                ));
+                continue;
+            }
+            let name = self.next_name();
+            loop_poly.set_name(name);
+            loop_poly.set_label_resources(&self.constraint_texture, &self.font);
+            loop_poly.show_last_line(true);
+            loop_poly.assert_ccw();
+            self.default_style.apply(&mut loop_poly);
+            self.split_off.push(PolygonObject::from(loop_poly));
+        }
+    }
+
+    /// Polygons cut off the chain by the last click in the split at crossings mode.
+    pub fn take_split_polygons(&mut self) -> Vec<PolygonObject<'a>> {
+        std::mem::take(&mut self.split_off)
     }
 
     pub fn clear(&mut self) {
@@ -737,20 +2136,31 @@ impl<'a> PolygonObjectFactory<'a> {
     }
 
     pub fn add_or_build(&mut self, add_pos: sf::Vector2f) -> Option<PolygonObject<'a>> {
+        // The last update may have seen another position, the click decides the splits
+        self.update(0., add_pos);
         if self.is_line_intersecting {
             return None;
         }
 
         if self.polygon.is_some() {
-            // Assert minimal length of the new edge
+            // Assert minimal length of the new edge, against the chain left by the pending
+            // splits, so a rejected click doesn't cut anything off
             if !self.entered_correct_vertex_region {
-                for i in 1..self.polygon.as_ref().unwrap().points_count() {
-                    if my_math::distance(&add_pos, &self.polygon.as_ref().unwrap().get_point_pos(i as isize)) <= style::POLY_EDGE_MIN_LEN {
-                        return None;
-                    }
+                let chain = self.chain_after_splits();
+                if chain.iter().skip(1).any(|pos| my_math::distance(&add_pos, pos) <= style::POLY_EDGE_MIN_LEN) {
+                    return None;
                 }
             } else {
                 if self.polygon.as_ref().unwrap().points_count() >= 3 {
+                    // Degenerate polygons break the offset and the triangulation
+                    let area = self.polygon.as_ref().unwrap().area();
+                    if area < self.min_area {
+                        self.rejection = Some(format!(
+                            "Polygon area is too small ({:.1} < {:.1})", area, self.min_area
+                        ));
+                        return None;
+                    }
+
                     // If this condition is met, adding a new polygon is finished
 
                     self.update_line(sf::Vector2f::new(0.0, 0.0), sf::Vector2::new(0.0, 0.0));
@@ -763,6 +2173,7 @@ impl<'a> PolygonObjectFactory<'a> {
                     // Build the PolygonObject
                     self.polygon.as_mut().unwrap().assert_ccw();
                     self.polygon.as_mut().unwrap().show_last_line(true);
+                    self.default_style.apply(self.polygon.as_mut().unwrap());
                     let poly = std::mem::replace(&mut self.polygon, None);
                     return Some(PolygonObject::from(poly.unwrap().to_owned()));
                 }
@@ -771,21 +2182,82 @@ impl<'a> PolygonObjectFactory<'a> {
                 return None;
             }
         }
+        if !self.pending_splits.is_empty() {
+            self.split_loops();
+        }
         self.add(add_pos);
 
         None
     }
 
+    pub fn min_area(&self) -> f32 {
+        self.min_area
+    }
+
+    pub fn set_min_area(&mut self, min_area: f32) {
+        self.min_area = min_area;
+    }
+
+    pub fn split_at_crossings(&self) -> bool {
+        self.split_at_crossings
+    }
+
+    pub fn set_split_at_crossings(&mut self, split_at_crossings: bool) {
+        self.split_at_crossings = split_at_crossings;
+    }
+
+    pub fn default_style(&self) -> &PolygonStyle {
+        &self.default_style
+    }
+    /// The style is written to the disk by save_default_style, not on every change.
+    pub fn set_default_style(&mut self, default_style: PolygonStyle) {
+        if default_style == self.default_style {
+            return;
+        }
+        let radius = default_style.close_radius;
+        self.helper_circle.set_radius(radius);
+        self.helper_circle.set_origin(sf::Vector2f::new(radius, radius));
+        self.default_style = default_style;
+        self.default_style_changed = true;
+    }
+    /// Saves the default style if it changed since the last save.
+    pub fn save_default_style(&mut self) {
+        if self.default_style_changed {
+            self.default_style.save();
+            self.default_style_changed = false;
+        }
+    }
+
+    /// Returns the reason why the last polygon completion was rejected, if any.
+    pub fn take_rejection(&mut self) -> Option<String> {
+        self.rejection.take()
+    }
+
+    /// Name for a polygon created outside of the factory, e.g. a pasted copy.
+    pub fn next_name(&mut self) -> String {
+        let name = format!("Polygon #{}", self.curr_id);
+        self.curr_id += 1;
+        name
+    }
+
     pub fn build_from_raw(&mut self, raw_polygon: RawPolygonCoords) -> PolygonObject<'a> {
+        let group = raw_polygon.group;
+        let name = raw_polygon.name.clone();
+        let has_edges_color = raw_polygon.edges_color.is_some();
         let mut poly = Polygon::new();
         poly.set_points_from_raw(raw_polygon);
-        poly.set_name(format!("Polygon #{}", self.curr_id));
+        poly.set_name(name.unwrap_or_else(|| format!("Polygon #{}", self.curr_id)));
         poly.set_label_resources(&self.constraint_texture, &self.font);
-        poly.show_last_line(true);
+        poly.show_last_line(!poly.is_open());
+        if !has_edges_color {
+            poly.set_edges_color(self.default_style.edges_color());
+        }
 
         self.curr_id += 1;
 
-        PolygonObject::from(poly)
+        let mut poly_obj = PolygonObject::from(poly);
+        poly_obj.set_group_id(group);
+        poly_obj
     }
 
     pub fn update(&mut self, _dt: f32, mouse_pos: sf::Vector2f) {
@@ -800,7 +2272,7 @@ impl<'a> PolygonObjectFactory<'a> {
 
             let mut is_magnet_set: bool = false;
 
-            if my_math::distance(&first, &m_pos) <= style::POINT_DETECTION_RADIUS {
+            if my_math::distance(&first, &m_pos) <= self.default_style.close_radius {
                 if poly.points_count() >= 3 {
                     // Show the circle helper to complete the polygon creation
                     self.helper_circle.set_fill_color(style::POINT_DETECTION_COLOR_CORRECT);
@@ -821,13 +2293,22 @@ impl<'a> PolygonObjectFactory<'a> {
 
             // Detect new line intersections
             self.is_line_intersecting = false;
+            self.pending_splits.clear();
             let line1 = geo::geometry::Line::new(
                 geo::coord! {x: last.x, y: last.y},
                 geo::coord! {x: m_pos.x, y: m_pos.y},
             );
 
-            // Detect point intersections with the other lines
-            if poly.points_count() >= 3 && !is_magnet_set {
+            if poly.points_count() >= 3 && !is_magnet_set && self.split_at_crossings {
+                let chain: Vec<sf::Vector2f> = (0..poly.points_count() as isize)
+                    .map(|id| poly.get_point_pos(id))
+                    .collect();
+                match plan_splits(&chain, m_pos) {
+                    Some(splits) => self.pending_splits = splits,
+                    None => self.is_line_intersecting = true,
+                }
+            } else if poly.points_count() >= 3 && !is_magnet_set {
+                // Detect point intersections with the other lines
                 for i in 0..(poly.points_count() - 2) as isize {
                     let line2 = geo::geometry::Line::new(
                         geo::coord! {x: poly.get_point_pos(i).x, y: poly.get_point_pos(i).y},
@@ -845,10 +2326,16 @@ impl<'a> PolygonObjectFactory<'a> {
                 }
             }
 
-            if self.is_line_intersecting {
-                poly.set_edges_color(style::LINES_COLOR_INCORRECT);
+            // Called every frame, the color changes only when the intersection state does
+            let edges_color = if self.is_line_intersecting {
+                style::LINES_COLOR_INCORRECT
+            } else if !self.pending_splits.is_empty() {
+                style::LINES_COLOR_SPLIT
             } else {
-                poly.set_edges_color(style::LINES_COLOR);
+                style::LINES_COLOR
+            };
+            if poly.edges_color() != edges_color {
+                poly.set_edges_color(edges_color);
             }
 
             // Update line helper
@@ -863,12 +2350,52 @@ impl<'a> PolygonObjectFactory<'a> {
         self.polygon.as_ref()
     }
 
-    pub fn draw_ctx(&self, target: &mut dyn RenderTarget) {
+    /// Live description of the segment being placed, its end reflects the active snapping.
+    pub fn segment_readout(&self, mouse_pos: sf::Vector2f) -> String {
+        let poly = match self.polygon.as_ref() {
+            Some(poly) => poly,
+            None => return format!("({:.1}, {:.1})", mouse_pos.x, mouse_pos.y),
+        };
+
+        let [start, end] = self.new_line_points;
+        let d = end - start;
+        // Screen y axis points down, the angle is measured counter-clockwise as usual
+        let angle = (-d.y).atan2(d.x).to_degrees();
+        let mut readout = format!(
+            "Vertex #{}: ({:.1}, {:.1})\nNext: ({:.1}, {:.1})\nLength: {:.1}\nAngle: {:.1}°",
+            poly.points_count() - 1, start.x, start.y, end.x, end.y, my_math::vec_len(&d), angle
+        );
+        if !self.pending_splits.is_empty() {
+            readout += &format!("\nClick splits off {} loop(s)", self.pending_splits.len());
+        }
+        readout
+    }
+
+    /// Returns the position the next point snaps to, if any.
+    pub fn snap_target(&self) -> Option<sf::Vector2f> {
+        if !self.entered_correct_vertex_region {
+            return None;
+        }
+        self.polygon.as_ref().and_then(|poly| poly.first_point_pos())
+    }
+
+    pub fn draw_ctx(&self, target: &mut dyn RenderTarget, indices_only: bool) {
         if let Some(poly) = self.polygon.as_ref() {
-            poly.draw_points(target);
+            if indices_only {
+                poly.draw_point_indices(target);
+            } else {
+                poly.draw_points(target);
+            }
         }
 
         target.draw(&self.new_point_circle);
+        for (_, pos) in self.pending_splits.iter() {
+            let mut marker = sf::CircleShape::new(style::POINT_RADIUS, 20);
+            marker.set_fill_color(style::LINES_COLOR_SPLIT);
+            marker.set_origin(sf::Vector2f::new(style::POINT_RADIUS, style::POINT_RADIUS));
+            marker.set_position(*pos);
+            target.draw(&marker);
+        }
         if self.entered_correct_vertex_region {
             target.draw(&self.helper_circle);
         }
@@ -889,8 +2416,14 @@ impl<'a> PolygonObjectFactory<'a> {
     }
 }
 
+// Source of the PolygonObject uids
+static NEXT_UID: AtomicUsize = AtomicUsize::new(0);
+
 pub struct PolygonObject<'a> {
     polygon: Polygon<'a>,
+    // Identifies the polygon across reorders, removals and undo. Clones (the history
+    // snapshots) keep it, duplicates get a new one.
+    uid: usize,
 
     // Selection
     selection: HashSet<usize>,
@@ -899,12 +2432,30 @@ pub struct PolygonObject<'a> {
 
     // Draw Offset 
     show_offset: bool,
+    // Draw the diagonals of the ear clipping triangulation
+    show_triangulation: bool,
     naive_offset: bool,
     offset_size: f32,
+    join_style: JoinStyle,
+    // Miter joins longer than that many offsets are clamped
+    miter_limit: f32,
+    // Nested offsets at multiples of the band spacing
+    show_bands: bool,
+    band_count: usize,
+    band_spacing: f32,
+    band_polygons: Vec<Polygon<'a>>,
     offset_polygon: Polygon<'a>,
+    // Draw the convex hull of the points
+    show_hull: bool,
+    hull_polygon: Polygon<'a>,
+    // Set when the geometry has changed, the offset is rebuilt in refresh_offset
+    offset_dirty: bool,
+
+    // Dimmed copy of the polygon captured at the beginning of a drag
+    ghost: Option<Polygon<'a>>,
 
     // Point hover
-    hover_circle: CircleShape<'a>,
+    hover_circle: sf::CircleShape<'a>,
     is_point_hovered: bool,
     hovered_point_id: usize,
 
@@ -912,12 +2463,69 @@ pub struct PolygonObject<'a> {
     is_line_hovered: bool,
     // First point of the line is considered to be line_id
     hovered_line_id: usize,
-    hover_quad: sf::ConvexShape<'a>,
+    hover_strip: Vec<sf::Vertex>,
 
     // Insert/remove
     can_insert: bool,
-    insert_circle: CircleShape<'a>,
+    insert_circle: sf::CircleShape<'a>,
     insert_pos: sf::Vector2f,
+
+    // Polygons sharing the group id are selected and transformed together
+    group_id: Option<usize>,
+    // Geometry from the last load or save, restored by revert_to_baseline
+    baseline: Option<RawPolygonCoords>,
+
+    // Egui
+    texture_path_input: String,
+    // Parameter of the precise insert, either t in [0, 1] or a distance from the edge start
+    insert_param: f32,
+    insert_by_distance: bool,
+    // Number of the evenly spaced vertices inserted at once
+    insert_count: usize,
+    // Angle of the Rotate button, in degrees
+    rotate_angle: f32,
+    // Factor of the Scale button and the reason of its last refusal
+    scale_factor: f32,
+    #[cfg(feature = "gui")]
+    scale_error: Option<PolygonError>,
+
+    // State from before an edit made in the egui panel, picked up by the history
+    pending_change: Option<Polygon<'a>>,
+    // Dragging a coordinate field is recorded once, when the value first changes
+    #[cfg(feature = "gui")]
+    point_drag_recorded: bool,
+}
+
+impl<'a> Clone for PolygonObject<'a> {
+    fn clone(&self) -> Self {
+        let mut result = PolygonObject::from(self.polygon.clone());
+        result.uid = self.uid;
+        result.selection = self.selection.clone();
+        result.show_hover = self.show_hover;
+        result.show_offset = self.show_offset;
+        result.show_triangulation = self.show_triangulation;
+        result.naive_offset = self.naive_offset;
+        result.join_style = self.join_style;
+        result.miter_limit = self.miter_limit;
+        result.show_bands = self.show_bands;
+        result.band_count = self.band_count;
+        result.band_spacing = self.band_spacing;
+        result.band_polygons = self.band_polygons.clone();
+        result.offset_size = self.offset_size;
+        result.offset_polygon = self.offset_polygon.clone();
+        result.show_hull = self.show_hull;
+        result.hull_polygon = self.hull_polygon.clone();
+        result.hover_circle.set_fill_color(self.hover_circle.fill_color());
+        result.group_id = self.group_id;
+        result.baseline = self.baseline.clone();
+        result.texture_path_input = self.texture_path_input.clone();
+        result.insert_param = self.insert_param;
+        result.insert_by_distance = self.insert_by_distance;
+        result.insert_count = self.insert_count;
+        result.rotate_angle = self.rotate_angle;
+        result.scale_factor = self.scale_factor;
+        result
+    }
 }
 
 impl<'a> PolygonObject<'a> {
@@ -926,9 +2534,6 @@ impl<'a> PolygonObject<'a> {
         hover_circle.set_fill_color(style::POINTS_COLOR);
         hover_circle.set_origin(sf::Vector2f::new(style::POINT_DETECTION_RADIUS, style::POINT_DETECTION_RADIUS));
 
-        let mut hover_quad = sf::ConvexShape::new(4);
-        hover_quad.set_fill_color(style::POINTS_COLOR);
-
         let mut insert_circle = sf::CircleShape::new(style::POINT_DETECTION_RADIUS, 20);
         insert_circle.set_fill_color(style::POINT_DETECTION_COLOR_CORRECT);
         insert_circle.set_origin(sf::Vector2f::new(style::POINT_DETECTION_RADIUS, style::POINT_DETECTION_RADIUS));
@@ -939,6 +2544,7 @@ impl<'a> PolygonObject<'a> {
 
         PolygonObject {
             polygon,
+            uid: NEXT_UID.fetch_add(1, Ordering::Relaxed),
             selection: HashSet::new(),
             show_hover: false,
             is_point_hovered: false,
@@ -946,23 +2552,121 @@ impl<'a> PolygonObject<'a> {
             hover_circle,
             insert_circle,
             can_insert: false,
-            hover_quad,
+            hover_strip: Vec::new(),
             hovered_line_id: 0,
             is_line_hovered: false,
             insert_pos: sf::Vector2f::new(0.0, 0.0),
             show_offset: false,
+            show_triangulation: false,
             naive_offset: false,
+            join_style: JoinStyle::Miter,
+            miter_limit: style::MAX_MITER_RATIO,
+            show_bands: false,
+            band_count: 3,
+            band_spacing: style::OFFSET_BAND_SPACING,
+            band_polygons: Vec::new(),
             offset_size: 50.0,
             offset_polygon: Polygon::new(),
+            show_hull: false,
+            hull_polygon: Polygon::new(),
+            offset_dirty: false,
+            ghost: None,
+            group_id: None,
+            baseline: None,
+            texture_path_input: String::new(),
+            insert_param: 0.5,
+            insert_by_distance: false,
+            insert_count: 1,
+            rotate_angle: 90.,
+            scale_factor: 2.,
+            #[cfg(feature = "gui")]
+            scale_error: None,
+            pending_change: None,
+            #[cfg(feature = "gui")]
+            point_drag_recorded: false,
+        }
+    }
+
+    /// Copy of the underlying polygon, recorded in the history before an edit.
+    pub fn uid(&self) -> usize {
+        self.uid
+    }
+
+    pub fn snapshot(&self) -> Polygon<'a> {
+        self.polygon.clone()
+    }
+
+    /// Returns the polygon from before the last edit made in the egui panel.
+    pub fn take_pending_change(&mut self) -> Option<Polygon<'a>> {
+        self.pending_change.take()
+    }
+
+    /// Remembers the current geometry as the one restored by revert_to_baseline.
+    pub fn mark_baseline(&mut self) {
+        let mut raw = self.polygon.get_raw();
+        // Only the geometry is reverted
+        raw.fill = None;
+        raw.name = None;
+        raw.edges_color = None;
+        self.baseline = Some(raw);
+    }
+
+    pub fn differs_from_baseline(&self) -> bool {
+        match &self.baseline {
+            Some(baseline) => {
+                let raw = self.polygon.get_raw();
+                raw.coords != baseline.coords || raw.constraints != baseline.constraints ||
+                    raw.bulges != baseline.bulges || raw.angles != baseline.angles
+            }
+            None => false,
+        }
+    }
+
+    /// Restores the geometry from the last load or save, the change is picked up by the history.
+    pub fn revert_to_baseline(&mut self) {
+        if let Some(baseline) = self.baseline.clone() {
+            let mut reverted = self.polygon.clone();
+            reverted.set_points_from_raw(baseline);
+            self.pending_change = Some(self.replace_polygon(reverted));
+        }
+    }
+
+    /// Swaps the underlying polygon, used by undo/redo. Returns the previous one.
+    pub fn replace_polygon(&mut self, polygon: Polygon<'a>) -> Polygon<'a> {
+        self.deselect_all_points();
+        let mut old = std::mem::replace(&mut self.polygon, polygon);
+        for id in 0..self.polygon.points_count() as isize {
+            self.polygon.deselect_point(id);
+        }
+        for id in 0..old.points_count() as isize {
+            old.deselect_point(id);
         }
+        self.is_point_hovered = false;
+        self.is_line_hovered = false;
+        self.can_insert = false;
+        self.ghost = None;
+        self.update_offset();
+        old
     }
 
     pub fn get_raw(&self) -> RawPolygonCoords {
-        self.polygon.get_raw()
+        let mut raw = self.polygon.get_raw();
+        raw.group = self.group_id;
+        raw
+    }
+
+    pub fn group_id(&self) -> Option<usize> {
+        self.group_id
+    }
+    pub fn set_group_id(&mut self, group_id: Option<usize>) {
+        self.group_id = group_id;
     }
     pub fn polygon(&self) -> &Polygon {
         &self.polygon
     }
+    pub fn set_name(&mut self, name: String) {
+        self.polygon.set_name(name);
+    }
 
     pub fn can_insert(&self) -> bool {
         self.can_insert
@@ -972,22 +2676,171 @@ impl<'a> PolygonObject<'a> {
         self.insert_pos
     }
 
-    pub fn insert_point(&mut self, id: isize, pos: sf::Vector2f) {
+    /// Removes the vertices lying on the segment between their neighbors (within "tolerance"),
+    /// merging the two edges into one. Selected, pinned and angle constrained points are kept,
+    /// as well as the ones close to any of the "keep" positions. Returns the number of removed
+    /// vertices.
+    pub fn merge_collinear_edges(&mut self, tolerance: f32, keep: &[sf::Vector2f]) -> usize {
+        let mut removed = 0;
+        let mut id = 0;
+        while id < self.polygon.points_count() && self.polygon.points_count() > 3 {
+            let i = id as isize;
+            let prev = self.polygon.get_point_pos(i - 1);
+            let pos = self.polygon.get_point_pos(i);
+            let next = self.polygon.get_point_pos(i + 1);
+
+            let redundant = !self.selection.contains(&id) &&
+                !self.polygon.is_point_pinned(i) &&
+                self.polygon.get_angle_constraint(i).is_none() &&
+                !keep.iter().any(|p| my_math::distance(p, &pos) <= style::SHARED_VERTEX_TOLERANCE) &&
+                // The point has to lie between the neighbors, spikes are not merged
+                my_math::dot_prod(&(prev - pos), &(next - pos)) < 0. &&
+                my_math::distance(&my_math::closest_on_segment(&pos, &prev, &next), &pos) <= tolerance;
+            if !redundant {
+                id += 1;
+                continue;
+            }
+
+            // The merged edge keeps the direction constraint only if both edges had it, the
+            // fixed length of either half doesn't hold for the merged edge
+            let constraint = self.polygon.get_edge_constraint(i - 1);
+            if constraint != self.polygon.get_edge_constraint(i) ||
+                matches!(constraint, EdgeConstraint::FixedLength(_)) {
+                self.polygon.set_edge_contsraint(i - 1, EdgeConstraint::None);
+            }
+            self.polygon.remove_point(i);
+            self.selection = self.selection
+                .iter()
+                .map(|&selected| if selected > id { selected - 1 } else { selected })
+                .collect();
+            removed += 1;
+        }
+
+        if removed > 0 {
+            self.update_offset();
+        }
+        removed
+    }
+
+    /// Inserts the given vertices into the edges passing through them (within
+    /// style::SHARED_VERTEX_TOLERANCE), so the polygon stays conformal with its neighbors.
+    /// Returns the number of inserted vertices.
+    pub fn insert_shared_vertices(&mut self, vertices: &[sf::Vector2f]) -> usize {
+        let mut inserted = 0;
+        for vertex in vertices.iter() {
+            let edge = (0..self.polygon.points_count() as isize).find(|&i| {
+                match self.polygon.project_on_edge(i, *vertex) {
+                    Some((_, dist)) => dist <= style::SHARED_VERTEX_TOLERANCE &&
+                        my_math::distance(vertex, &self.polygon.get_point_pos(i)) > style::POLY_EDGE_MIN_LEN &&
+                        my_math::distance(vertex, &self.polygon.get_point_pos(i + 1)) > style::POLY_EDGE_MIN_LEN,
+                    None => false,
+                }
+            });
+            let edge = match edge {
+                Some(edge) => edge,
+                None => continue,
+            };
+
+            let before = self.polygon.clone();
+            let id = self.polygon.fix_index(edge + 1);
+            self.polygon.set_edge_contsraint(edge, EdgeConstraint::None);
+            self.polygon.insert_point_with_pos(id as isize, *vertex);
+            if self.polygon.is_self_crossing() {
+                self.polygon = before;
+                continue;
+            }
+            // The inserted point shifts the selected ids
+            self.selection = self.selection
+                .iter()
+                .map(|&selected| if selected >= id { selected + 1 } else { selected })
+                .collect();
+            inserted += 1;
+        }
+
+        if inserted > 0 {
+            self.assert_ccw();
+            self.update_offset();
+        }
+        inserted
+    }
+
+    /// Turns the edge starting at id into an arc with the given bulge, 0 makes it straight.
+    /// The bulge is kept as it was if the sampled arc would cross the other edges.
+    pub fn set_edge_bulge(&mut self, id: isize, bulge: f32) -> Result<(), PolygonError> {
+        let old = self.polygon.get_edge_bulge(id);
+        self.polygon.set_edge_bulge(id, bulge);
+        if self.polygon.is_self_crossing() {
+            self.polygon.set_edge_bulge(id, old);
+            return Err(PolygonError::WouldSelfIntersect);
+        }
+        self.update_offset();
+        Ok(())
+    }
+
+    /// Inserts a point at pos before the point id. The split edge loses its constraint and its
+    /// arc, the polygon is left as it was if straightening the arc makes it self-crossing.
+    pub fn insert_point(&mut self, id: isize, pos: sf::Vector2f) -> Result<(), PolygonError> {
+        if my_math::distance(&pos, &self.polygon.get_point_pos(id - 1)) <= style::POLY_EDGE_MIN_LEN ||
+            my_math::distance(&pos, &self.polygon.get_point_pos(id)) <= style::POLY_EDGE_MIN_LEN {
+            return Err(PolygonError::EdgeTooShort);
+        }
+
+        let before = self.polygon.clone();
         self.polygon.set_edge_contsraint(id - 1, EdgeConstraint::None);
         self.polygon.insert_point_with_pos(id, pos);
+        if self.polygon.is_self_crossing() {
+            self.polygon = before;
+            return Err(PolygonError::WouldSelfIntersect);
+        }
         self.update_offset();
         self.can_insert = false;
+        Ok(())
+    }
+
+    /// Removes the spike vertices (see Polygon::find_spikes) one by one, the angles at the
+    /// neighbors are checked again after every removal. Pinned spikes stay. Returns the
+    /// number of removed vertices, the polygon is left as it was on failure.
+    pub fn remove_spikes(&mut self, max_angle: f32) -> Result<usize, PolygonError> {
+        let before = self.polygon.clone();
+        let mut removed = 0;
+        while let Some(id) = self.polygon
+            .find_spikes(max_angle)
+            .into_iter()
+            .find(|&id| !self.polygon.is_point_pinned(id as isize)) {
+            // A triangle keeps its sharp corner
+            if self.polygon.points_count() <= 3 {
+                break;
+            }
+            if let Err(err) = self.remove_point(id as isize) {
+                self.replace_polygon(before);
+                return Err(err);
+            }
+            removed += 1;
+        }
+
+        if removed > 0 && self.polygon.is_self_crossing() {
+            self.replace_polygon(before);
+            return Err(PolygonError::WouldSelfIntersect);
+        }
+        if removed > 0 {
+            self.assert_ccw();
+            self.update_offset();
+        }
+        Ok(removed)
     }
 
     pub fn set_point_hover_color(&mut self, color: sf::Color) {
         self.hover_circle.set_fill_color(color);
     }
 
-    pub fn remove_point(&mut self, id: isize) -> Result<(), io::Error> {
+    pub fn remove_point(&mut self, id: isize) -> Result<(), PolygonError> {
         if self.polygon.points_count() <= 3 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not enough points"));
+            return Err(PolygonError::TooFewPoints);
         }
         self.polygon.set_edge_contsraint(id - 1, EdgeConstraint::None);
+        // The angles at the neighbors change
+        self.polygon.set_angle_constraint(id - 1, None);
+        self.polygon.set_angle_constraint(id + 1, None);
         self.polygon.remove_point(id);
         self.selection.remove(&(id as usize));
         self.update_offset();
@@ -1039,36 +2892,43 @@ impl<'a> PolygonObject<'a> {
         self.is_point_hovered = false;
     }
 
-    fn update_on_line_hover(&mut self, pos: sf::Vector2f) {
-        for i in 0..self.polygon.points_count() as isize {
-            let v01 = self.polygon.get_point_pos(i + 1) - self.polygon.get_point_pos(i);
-            let v0m = pos - self.polygon.get_point_pos(i);
-
-            if my_math::dot_prod(&v01, &v0m) < 0.0 {
-                continue;
-            }
-
-            let proj1 = v01 * (my_math::dot_prod(&v01, &v0m) / my_math::vec_len2(&v01));
+    /// Returns the id of the nearest edge within style::LINE_DETECTION_DISTANCE and the
+    /// parametric position (0..1) of pos projected onto that edge.
+    pub fn edge_at(&self, pos: sf::Vector2f) -> Option<(usize, f32)> {
+        let mut result: Option<(usize, f32)> = None;
+        let mut min_dist = style::LINE_DETECTION_DISTANCE;
 
-            if my_math::vec_len2(&proj1) > my_math::vec_len2(&v01) {
-                continue;
+        // The hidden closing edge can't be hovered
+        let mut end = self.polygon.points_count() as isize;
+        if self.polygon.is_open() {
+            end -= 1;
+        }
+        for i in 0..end {
+            if let Some((t, dist)) = self.polygon.project_on_edge(i, pos) {
+                if dist < min_dist {
+                    min_dist = dist;
+                    result = Some((self.polygon.fix_index(i), t));
+                }
             }
+        }
+        result
+    }
 
-            let proj2 = v0m - proj1;
-
-            let dist = my_math::vec_len(&proj2);
-
-            if dist < style::LINE_DETECTION_DISTANCE {
-                let proj_norm = my_math::vec_norm(&proj2);
-
-                self.hover_quad.set_point(0, self.polygon.get_point_pos(i) + proj_norm * style::LINE_THICKNESS / 2.);
-                self.hover_quad.set_point(1, self.polygon.get_point_pos(i + 1) + proj_norm * style::LINE_THICKNESS / 2.);
-                self.hover_quad.set_point(2, self.polygon.get_point_pos(i + 1) - proj_norm * style::LINE_THICKNESS / 2.);
-                self.hover_quad.set_point(3, self.polygon.get_point_pos(i) - proj_norm * style::LINE_THICKNESS / 2.);
-                self.hovered_line_id = self.polygon.fix_index(i);
-                self.is_line_hovered = true;
-                return;
+    fn update_on_line_hover(&mut self, pos: sf::Vector2f) {
+        if let Some((id, _t)) = self.edge_at(pos) {
+            let path = self.polygon.edge_path(id as isize);
+
+            // Offset the strip perpendicularly to the edge, arcs follow their samples
+            self.hover_strip.clear();
+            for k in 0..path.len() {
+                let dir = my_math::vec_norm(&(path[(k + 1).min(path.len() - 1)] - path[k.saturating_sub(1)]));
+                let proj_norm = sf::Vector2f::new(-dir.y, dir.x) * style::LINE_THICKNESS / 2.;
+                self.hover_strip.push(sf::Vertex::with_pos_color(path[k] + proj_norm, style::POINTS_COLOR));
+                self.hover_strip.push(sf::Vertex::with_pos_color(path[k] - proj_norm, style::POINTS_COLOR));
             }
+            self.hovered_line_id = id;
+            self.is_line_hovered = true;
+            return;
         }
         self.is_line_hovered = false;
     }
@@ -1120,6 +2980,22 @@ impl<'a> PolygonObject<'a> {
         (self.hovered_line_id, self.polygon.fix_index(self.hovered_line_id as isize + 1))
     }
 
+    /// Index, length, angle and constraint of the hovered edge.
+    pub fn hovered_edge_info(&self) -> Option<String> {
+        if !self.is_line_hovered {
+            return None;
+        }
+        let (id0, id1) = self.get_hovered_line_ids();
+        let d = self.polygon.get_point_pos(id1 as isize) - self.polygon.get_point_pos(id0 as isize);
+        // Screen y axis points down, the angle is measured counter-clockwise as usual
+        let angle = (-d.y).atan2(d.x).to_degrees();
+        let constraint = self.polygon.get_edge_constraint(id0 as isize).name();
+        Some(format!(
+            "Edge {}-{}\nLength: {:.1}\nAngle: {:.1}°\nConstraint: {}",
+            id0, id1, my_math::vec_len(&d), angle, constraint
+        ))
+    }
+
     pub fn select_point(&mut self, id: isize) {
         self.polygon.select_point(id);
         self.selection.insert(self.polygon.fix_index(id));
@@ -1137,68 +3013,408 @@ impl<'a> PolygonObject<'a> {
         self.selection.clear();
     }
 
-    pub fn select_all_points(&mut self) {
-        for id in 0..self.polygon.points_count() as isize {
-            self.polygon.select_point(id);
-            self.selection.insert(self.polygon.fix_index(id));
+    /// Reduces the polygon to the target vertex count (Visvalingam-Whyatt). The polygon
+    /// is left untouched if the result would be self-crossing.
+    pub fn simplify_to(&mut self, target: usize) -> Result<(), PolygonError> {
+        if target < 3 {
+            return Err(PolygonError::TooFewPoints);
+        }
+        let count = self.polygon.points_count();
+        if target >= count {
+            return Ok(());
         }
-    }
 
-    pub fn is_point_selected(&self, id: isize) -> bool {
-        self.polygon.is_point_selected(id)
-    }
+        let positions: Vec<sf::Vector2f> = (0..count as isize).map(|id| self.polygon.get_point_pos(id)).collect();
+        let kept: HashSet<usize> = my_math::visvalingam(&positions, target).into_iter().collect();
 
-    pub fn is_line_selected(&self, first_id: isize) -> bool {
-        self.polygon.is_point_selected(first_id) && self.polygon.is_point_selected(first_id + 1)
-    }
+        let mut simplified = self.polygon.clone();
+        for id in (0..count).rev() {
+            if !kept.contains(&id) {
+                simplified.remove_point(id as isize);
+            }
+        }
+        if simplified.is_self_crossing() {
+            return Err(PolygonError::WouldSelfIntersect);
+        }
 
-    pub fn selected_points_count(&self) -> usize {
-        self.selection.len()
+        let had_selection = !self.selection.is_empty();
+        self.deselect_all_points();
+        self.polygon = simplified;
+        self.drop_broken_constraints();
+        if had_selection {
+            self.select_all_points();
+        }
+        self.assert_ccw();
+        self.update_offset();
+        Ok(())
+    }
+
+    /// Inserts a midpoint on every edge. As with insert_point, the split edges lose their
+    /// constraints.
+    pub fn subdivide(&mut self, iterations: u32) {
+        // Indices are shifted by the insertion, so the selection is rebuilt afterwards
+        let had_selection = !self.selection.is_empty();
+        self.deselect_all_points();
+
+        for _ in 0..iterations {
+            let count = self.polygon.points_count() as isize;
+
+            // Going backwards keeps the not yet processed indices valid
+            for id in (0..count - 1).rev() {
+                let mid = (self.polygon.get_point_pos(id) + self.polygon.get_point_pos(id + 1)) / 2.;
+                self.polygon.set_edge_contsraint(id, EdgeConstraint::None);
+                self.polygon.insert_point_with_pos(id + 1, mid);
+            }
+
+            // The closing edge, its midpoint becomes the first point
+            let last = self.polygon.points_count() as isize - 1;
+            let mid = (self.polygon.get_point_pos(last) + self.polygon.get_point_pos(0)) / 2.;
+            self.polygon.set_edge_contsraint(last, EdgeConstraint::None);
+            self.polygon.insert_point_with_pos(0, mid);
+        }
+
+        if had_selection {
+            self.select_all_points();
+        }
+        self.update_offset();
+    }
+
+    pub fn select_all_points(&mut self) {
+        for id in 0..self.polygon.points_count() as isize {
+            self.polygon.select_point(id);
+            self.selection.insert(self.polygon.fix_index(id));
+        }
+    }
+
+    pub fn is_point_selected(&self, id: isize) -> bool {
+        self.polygon.is_point_selected(id)
+    }
+
+    pub fn is_line_selected(&self, first_id: isize) -> bool {
+        self.polygon.is_point_selected(first_id) && self.polygon.is_point_selected(first_id + 1)
+    }
+
+    pub fn selected_points_count(&self) -> usize {
+        self.selection.len()
+    }
+
+    pub fn offset_size(&self) -> f32 {
+        self.offset_size
+    }
+    pub fn set_offset_size(&mut self, size: f32) {
+        let size = size.clamp(0.0, style::MAX_OFFSET);
+        if size == self.offset_size {
+            return;
+        }
+        self.offset_size = size;
+        self.update_offset();
+    }
+
+    /// Projects the selected points onto their least squares line. Changes are reverted
+    /// if the polygon would become self-crossing.
+    pub fn flatten_selected_points(&mut self) -> Result<(), PolygonError> {
+        let ids: Vec<usize> = self.selection.iter().copied().collect();
+        if ids.len() < 2 {
+            return Err(PolygonError::TooFewPoints);
+        }
+        let positions: Vec<sf::Vector2f> = ids.iter().map(|id| self.polygon.get_point_pos(*id as isize)).collect();
+
+        let (center, dir) = match my_math::fit_line(&positions) {
+            Some(line) => line,
+            None => return Err(PolygonError::Degenerate),
+        };
+
+        for (id, pos) in ids.iter().zip(positions.iter()) {
+            let projected = center + dir * my_math::dot_prod(&(*pos - center), &dir);
+            self.polygon.update_point_pos(projected, *id as isize);
+        }
+
+        if self.polygon.is_self_crossing() {
+            for (id, pos) in ids.iter().zip(positions.iter()) {
+                self.polygon.update_point_pos(*pos, *id as isize);
+            }
+            return Err(PolygonError::WouldSelfIntersect);
+        }
+
+        // Drop the constraints that the projection has broken
+        self.drop_broken_constraints();
+
+        self.assert_ccw();
+        self.update_offset();
+        Ok(())
+    }
+
+    fn drop_broken_constraints(&mut self) {
+        for i in 0..self.polygon.points_count() as isize {
+            let p0 = self.polygon.get_point_pos(i);
+            let p1 = self.polygon.get_point_pos(i + 1);
+            let broken = match self.polygon.get_edge_constraint(i) {
+                EdgeConstraint::Horizontal => (p0.y - p1.y).abs() > f32::EPSILON,
+                EdgeConstraint::Vertical => (p0.x - p1.x).abs() > f32::EPSILON,
+                EdgeConstraint::FixedLength(length) => {
+                    (my_math::distance(&p0, &p1) - length).abs() > style::FIXED_LENGTH_TOLERANCE
+                }
+                EdgeConstraint::None => false,
+            };
+            if broken {
+                self.polygon.set_edge_contsraint(i, EdgeConstraint::None);
+            }
+
+            if let Some(angle) = self.polygon.get_angle_constraint(i) {
+                if (self.polygon.interior_angle(i) - angle).abs() > style::FIXED_ANGLE_TOLERANCE {
+                    self.polygon.set_angle_constraint(i, None);
+                }
+            }
+        }
+    }
+
+    pub fn set_selected_points_pinned(&mut self, pinned: bool) {
+        for id in self.selection.iter() {
+            self.polygon.set_point_pinned(*id as isize, pinned);
+        }
+    }
+
+    /// Opens the polygon, so points can be appended after its last vertex with append_point.
+    pub fn begin_append(&mut self) {
+        self.deselect_all_points();
+        // The closing edge goes away for now
+        let last = self.polygon.points_count() as isize - 1;
+        self.polygon.set_edge_contsraint(last, EdgeConstraint::None);
+        // The angles at its ends are about to change
+        self.polygon.set_angle_constraint(0, None);
+        self.polygon.set_angle_constraint(last, None);
+        self.polygon.show_last_line(false);
+    }
+
+    /// Appends a point to the open chain. Fails if the point is too close to an existing
+    /// one or if the new edge would cross the chain.
+    pub fn append_point(&mut self, pos: sf::Vector2f) -> Result<(), PolygonError> {
+        let count = self.polygon.points_count();
+        for i in 0..count as isize {
+            if my_math::distance(&pos, &self.polygon.get_point_pos(i)) <= style::POLY_EDGE_MIN_LEN {
+                return Err(PolygonError::Degenerate);
+            }
+        }
+
+        // Every edge but the one continued by the new edge is checked
+        let last = self.polygon.get_point_pos(count as isize - 1);
+        let new_line = geo::geometry::Line::new(
+            geo::coord! {x: last.x, y: last.y},
+            geo::coord! {x: pos.x, y: pos.y},
+        );
+        for i in 0..count.saturating_sub(2) as isize {
+            let p0 = self.polygon.get_point_pos(i);
+            let p1 = self.polygon.get_point_pos(i + 1);
+            let line = geo::geometry::Line::new(
+                geo::coord! {x: p0.x, y: p0.y},
+                geo::coord! {x: p1.x, y: p1.y},
+            );
+            if geo::algorithm::line_intersection::line_intersection(new_line, line).is_some() {
+                return Err(PolygonError::WouldSelfIntersect);
+            }
+        }
+
+        self.polygon.push_point_with_pos(pos);
+        self.invalidate_offset();
+        Ok(())
+    }
+
+    /// Closes the polygon opened with begin_append. Fails (leaving the chain open) if the
+    /// closing edge would make it self-crossing or if it's smaller than min_area.
+    pub fn finish_append(&mut self, min_area: f32) -> Result<(), PolygonError> {
+        if self.polygon.is_self_crossing() {
+            return Err(PolygonError::WouldSelfIntersect);
+        }
+        if self.polygon.area() < min_area {
+            return Err(PolygonError::Degenerate);
+        }
+
+        self.polygon.show_last_line(!self.polygon.is_open());
+        self.assert_ccw();
+        self.update_offset();
+        Ok(())
+    }
+
+    /// Removes the points appended after the first count points.
+    pub fn truncate_points(&mut self, count: usize) {
+        while self.polygon.points_count() > count.max(3) {
+            self.polygon.remove_point(self.polygon.points_count() as isize - 1);
+        }
+        self.invalidate_offset();
+    }
+
+    /// Ids and positions of the selected points.
+    pub fn selected_points(&self) -> Vec<(usize, sf::Vector2f)> {
+        self.selection.iter().map(|id| (*id, self.polygon.get_point_pos(*id as isize))).collect()
+    }
+
+    /// Moves the points to the given positions, ignoring the constraints. Used by
+    /// the transforms which call finish_transform once they're done.
+    pub fn set_points_pos(&mut self, points: &[(usize, sf::Vector2f)]) {
+        for (id, pos) in points.iter() {
+            if !self.polygon.is_point_pinned(*id as isize) {
+                self.polygon.update_point_pos(*pos, *id as isize);
+            }
+        }
+        self.invalidate_offset();
+    }
+
+    /// Copy shifted by offset with the constraints and the offset settings. The copy is
+    /// deselected, ungrouped and has no baseline, the caller gives it a name.
+    pub fn duplicate(&self, offset: sf::Vector2f) -> PolygonObject<'a> {
+        let mut result = self.clone();
+        result.uid = NEXT_UID.fetch_add(1, Ordering::Relaxed);
+        result.deselect_all_points();
+        result.group_id = None;
+        result.baseline = None;
+        result.translate(offset);
+        result
+    }
+
+    /// Moves the whole polygon, the pinned points included.
+    pub fn translate(&mut self, vec: sf::Vector2f) {
+        for id in 0..self.polygon.points_count() as isize {
+            self.polygon.update_point_pos(self.polygon.get_point_pos(id) + vec, id);
+        }
+        self.update_offset();
+    }
+
+    pub fn finish_transform(&mut self) {
+        self.drop_broken_constraints();
+        self.assert_ccw();
+        self.update_offset();
     }
 
     pub fn move_selected_points(&mut self, vec: sf::Vector2f) {
+        if self.selection.is_empty() || vec == sf::Vector2f::new(0., 0.) {
+            return;
+        }
+
+        // Pinned points stay in place
+        let moved: HashSet<usize> = self.selection
+            .iter()
+            .copied()
+            .filter(|id| !self.polygon.is_point_pinned(*id as isize))
+            .collect();
+
+        let old_positions: Vec<sf::Vector2f> = (0..self.polygon.points_count() as isize)
+            .map(|id| self.polygon.get_point_pos(id))
+            .collect();
+
         // Move all selected points by the given vector
-        for id in self.selection.iter() {
+        for id in moved.iter() {
             self.polygon.update_point_pos(self.polygon.get_point_pos(*id as isize) + vec, *id as isize);
         }
 
         //
-        for id in self.selection.iter() {
+        for id in moved.iter() {
             let prev_id = self.polygon.fix_index(*id as isize - 1) as isize;
             let mut prev_point = self.polygon.get_point_pos(prev_id);
             let next_id = self.polygon.fix_index(*id as isize + 1) as isize;
             let mut next_point = self.polygon.get_point_pos(next_id);
 
-            if !self.selection.contains(&(prev_id as usize)) {
+            let point = self.polygon.get_point_pos(*id as isize);
+
+            if !moved.contains(&(prev_id as usize)) && !self.polygon.is_point_pinned(prev_id) {
                 if self.polygon.get_edge_constraint(prev_id) == EdgeConstraint::Vertical {
                     prev_point.x += vec.x;
                     self.polygon.update_point_pos(prev_point, prev_id);
                 } else if self.polygon.get_edge_constraint(prev_id) == EdgeConstraint::Horizontal {
                     prev_point.y += vec.y;
                     self.polygon.update_point_pos(prev_point, prev_id);
+                } else if let EdgeConstraint::FixedLength(length) = self.polygon.get_edge_constraint(prev_id) {
+                    self.polygon.update_point_pos(Self::keep_distance(point, prev_point, length), prev_id);
                 }
             }
 
-            if !self.selection.contains(&(next_id as usize)) {
+            if !moved.contains(&(next_id as usize)) && !self.polygon.is_point_pinned(next_id) {
                 if self.polygon.get_edge_constraint(*id as isize) == EdgeConstraint::Vertical {
                     next_point.x += vec.x;
                     self.polygon.update_point_pos(next_point, next_id);
                 } else if self.polygon.get_edge_constraint(*id as isize) == EdgeConstraint::Horizontal {
                     next_point.y += vec.y;
                     self.polygon.update_point_pos(next_point, next_id);
+                } else if let EdgeConstraint::FixedLength(length) = self.polygon.get_edge_constraint(*id as isize) {
+                    self.polygon.update_point_pos(Self::keep_distance(point, next_point, length), next_id);
                 }
             }
         }
 
-        self.update_offset();
+        // Angle constraints rotate the untouched edge of the vertex, the neighbors moved by the
+        // edge constraints count as touched
+        let touched: HashSet<usize> = (0..old_positions.len())
+            .filter(|id| self.polygon.get_point_pos(*id as isize) != old_positions[*id])
+            .collect();
+        for id in 0..self.polygon.points_count() as isize {
+            let angle = match self.polygon.get_angle_constraint(id) {
+                Some(angle) => angle,
+                None => continue,
+            };
+            let prev_id = self.polygon.fix_index(id - 1);
+            let next_id = self.polygon.fix_index(id + 1);
+            let id_touched = touched.contains(&(id as usize));
+
+            if (id_touched || touched.contains(&prev_id)) && !touched.contains(&next_id) &&
+                self.can_rotate_neighbor(id, true) {
+                self.polygon.set_interior_angle(id, angle, true);
+            } else if (id_touched || touched.contains(&next_id)) && !touched.contains(&prev_id) &&
+                self.can_rotate_neighbor(id, false) {
+                self.polygon.set_interior_angle(id, angle, false);
+            }
+        }
+
+        // Offset is expensive, it's rebuilt at most once per frame
+        self.invalidate_offset();
+    }
+
+    // Whether the angle at the point can be kept by rotating the given neighbor, rotation
+    // would break a horizontal or vertical constraint of the rotated edge
+    fn can_rotate_neighbor(&self, id: isize, next: bool) -> bool {
+        let (neighbor, edge) = if next { (id + 1, id) } else { (id - 1, id - 1) };
+        !self.polygon.is_point_pinned(neighbor) && !matches!(
+            self.polygon.get_edge_constraint(edge),
+            EdgeConstraint::Horizontal | EdgeConstraint::Vertical
+        )
+    }
+
+    // Projects the neighbor onto the circle of the given radius around the moved point
+    fn keep_distance(point: sf::Vector2f, neighbor: sf::Vector2f, length: f32) -> sf::Vector2f {
+        let d = neighbor - point;
+        if my_math::vec_len2(&d) == 0. {
+            return neighbor;
+        }
+        point + my_math::vec_norm(&d) * length
     }
 
-    pub fn draw_ctx(&self, target: &mut dyn RenderTarget) {
-        self.polygon.draw_points(target);
+    fn invalidate_offset(&mut self) {
+        if self.show_offset || self.show_hull || self.show_bands {
+            self.offset_dirty = true;
+        }
+    }
+
+    /// Rebuilds the offset polygon if the geometry has changed since the last rebuild.
+    pub fn refresh_offset(&mut self) {
+        if self.offset_dirty {
+            self.offset_dirty = false;
+            self.update_offset();
+        }
+    }
+
+    /// Draws the points and the labels. Should be called after the edges of all of the polygons.
+    /// With indices_only the point circles are skipped and only the index labels are shown.
+    pub fn draw_points(&self, target: &mut dyn RenderTarget, indices_only: bool) {
+        if !indices_only {
+            self.polygon.draw_points(target);
+        }
+        self.polygon.draw_labels(target, indices_only);
+    }
 
+    /// Draws the hover and selection highlights. Should be called after the points of all of
+    /// the polygons, so the highlights are never occluded by the other polygons.
+    pub fn draw_overlays(&self, target: &mut dyn RenderTarget) {
         if !self.show_hover {
             if self.is_line_hovered {
-                target.draw(&self.hover_quad);
+                target.draw_primitives(&self.hover_strip, sf::PrimitiveType::TRIANGLE_STRIP, &sf::RenderStates::DEFAULT);
             }
 
             if self.is_point_hovered {
@@ -1211,56 +3427,170 @@ impl<'a> PolygonObject<'a> {
         for id in self.selection.iter() {
             self.polygon.draw_point_selection(*id as isize, target);
         }
+    }
+
+    pub fn draw_fill(&self, target: &mut dyn RenderTarget) {
+        self.polygon.draw_fill(target);
+    }
+
+    pub fn draw_shadow(&self, target: &mut dyn RenderTarget, shadow: &DropShadow) {
+        self.polygon.draw_shadow(target, shadow);
+    }
+
+    pub fn draw_bresenham_shadow(&self, img_target: &mut sf::Image, line_painter: &mut LinePainter, shadow: &DropShadow) {
+        self.polygon.draw_shadow_bresenham(img_target, line_painter, shadow);
+    }
 
-        self.polygon.draw_labels(target);
+    pub fn draw_bresenham_fill(&self, img_target: &mut sf::Image, line_painter: &mut LinePainter) {
+        self.polygon.draw_fill_bresenham(img_target, line_painter);
+    }
+
+    /// Snapshots the current shape, it's drawn dimmed until clear_ghost is called.
+    pub fn capture_ghost(&mut self) {
+        let mut ghost = self.polygon.clone();
+        ghost.set_fill_style(FillStyle::None);
+        ghost.set_edges_color(style::GHOST_COLOR);
+        self.ghost = Some(ghost);
+    }
+
+    pub fn clear_ghost(&mut self) {
+        self.ghost = None;
     }
 
     pub fn draw_edges(&self, target: &mut dyn RenderTarget) {
+        if let Some(ghost) = self.ghost.as_ref() {
+            ghost.draw_edges(target);
+        }
         self.polygon.draw_edges(target);
 
         if self.show_offset {
             self.offset_polygon.draw_edges(target);
         }
+        if self.show_hull {
+            self.hull_polygon.draw_edges(target);
+        }
+        if self.show_bands {
+            for band in self.band_polygons.iter() {
+                band.draw_edges(target);
+            }
+        }
+    }
+
+    /// Draws the diagonals of the triangulation, the edges of the polygon are left out.
+    pub fn draw_triangulation(&self, target: &mut dyn RenderTarget) {
+        if !self.show_triangulation || !self.polygon.is_proper() || self.polygon.is_self_crossing() {
+            return;
+        }
+        let count = self.polygon.points_count();
+        let mut vertices: Vec<sf::Vertex> = Vec::new();
+        for tri in self.polygon.triangulate() {
+            for k in 0..3 {
+                let (i, j) = (tri[k], tri[(k + 1) % 3]);
+                // Every diagonal is shared by two triangles, it's drawn once
+                if (i + 1) % count != j && (j + 1) % count != i && i < j {
+                    vertices.push(sf::Vertex::with_pos_color(self.polygon.get_point_pos(i as isize), style::TRIANGULATION_COLOR));
+                    vertices.push(sf::Vertex::with_pos_color(self.polygon.get_point_pos(j as isize), style::TRIANGULATION_COLOR));
+                }
+            }
+        }
+        target.draw_primitives(&vertices, sf::PrimitiveType::LINES, &sf::RenderStates::DEFAULT);
     }
 
     pub fn draw_bresenham_edges(&self, target: &mut dyn RenderTarget, img_target: &mut sf::Image, line_painter: &mut LinePainter) {
+        if let Some(ghost) = self.ghost.as_ref() {
+            ghost.draw_edges_bresenham(img_target, line_painter);
+        }
         self.polygon.draw_edges_bresenham(img_target, line_painter);
 
         if self.show_offset {
             self.offset_polygon.draw_edges_bresenham(img_target, line_painter);
         }
+        if self.show_hull {
+            self.hull_polygon.draw_edges_bresenham(img_target, line_painter);
+        }
+        if self.show_bands {
+            for band in self.band_polygons.iter() {
+                band.draw_edges_bresenham(img_target, line_painter);
+            }
+        }
+    }
+
+    fn update_hull(&mut self) {
+        if !self.show_hull {
+            return;
+        }
+        let points: Vec<sf::Vector2f> = (0..self.polygon.points_count())
+            .map(|i| self.polygon.get_point_pos(i as isize))
+            .collect();
+        self.hull_polygon = Polygon::create(my_math::convex_hull(&points));
+        self.hull_polygon.set_fill_style(FillStyle::None);
+        self.hull_polygon.set_edges_color(style::OFFSET_COLOR);
     }
 
     pub fn update_offset(&mut self) {
-        if !self.show_offset || self.polygon.is_self_crossing() {
+        // Every change of the geometry ends up here, the hull is derived from it as well
+        self.update_hull();
+        if self.polygon.is_self_crossing() {
+            self.band_polygons.clear();
+            return;
+        }
+        if self.show_offset {
+            self.offset_polygon = self.build_offset(self.offset_size);
+        }
+        self.update_bands();
+    }
+
+    /// Rebuilds the offset bands. Far from the polygon the crossing resolution may fall apart,
+    /// the bands stop at the first one that crosses itself or doesn't enclose the previous one.
+    fn update_bands(&mut self) {
+        self.band_polygons.clear();
+        if !self.show_bands {
             return;
         }
 
+        let mut prev_area = self.polygon.area();
+        for i in 1..=self.band_count {
+            let mut band = self.build_offset(self.band_spacing * i as f32);
+            let area = band.area();
+            if band.points_count() < 3 || !band.get_self_crossing_edges().is_empty() || area <= prev_area {
+                break;
+            }
+            prev_area = area;
+
+            let t = if self.band_count > 1 { (i - 1) as f32 / (self.band_count - 1) as f32 } else { 0. };
+            let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+            let (near, far) = (style::OFFSET_COLOR, style::OFFSET_BAND_FAR_COLOR);
+            band.set_edges_color(sf::Color::rgb(lerp(near.r, far.r), lerp(near.g, far.g), lerp(near.b, far.b)));
+            self.band_polygons.push(band);
+        }
+    }
+
+    fn build_offset(&self, size: f32) -> Polygon<'a> {
         // Create a naive offset
-        let mut naive_offset_polygon = self.polygon.clone();
-        for i in 0..naive_offset_polygon.points_count() as isize {
-            let vec = self.polygon.get_offset_vec(i);
-            let pos = self.polygon.get_point_pos(i);
-            naive_offset_polygon.update_point_pos(pos + vec * self.offset_size, i);
+        let mut naive_offset_points: Vec<sf::Vector2f> = Vec::new();
+        for i in 0..self.polygon.points_count() as isize {
+            naive_offset_points.extend(self.offset_join(i, size));
         }
+        let mut naive_offset_polygon = Polygon::create(naive_offset_points);
+        naive_offset_polygon.set_fill_style(FillStyle::None);
 
         // Find the crossing edges in the naive offset
         let mut crossings = naive_offset_polygon.get_self_crossing_edges();
 
         if crossings.is_empty() || self.naive_offset {
             // If there are no crossings, the naive offset is the solution
-            self.offset_polygon = naive_offset_polygon;
-            self.offset_polygon.set_edges_color(style::OFFSET_COLOR);
-            return;
+            naive_offset_polygon.set_edges_color(style::OFFSET_COLOR);
+            return naive_offset_polygon;
         }
 
         let mut visited: Vec<bool> = Vec::new();
-        visited.resize(self.polygon.points_count(), false);
+        visited.resize(naive_offset_polygon.points_count(), false);
 
         let mut outside_offset_polygon_points: Vec<sf::Vector2f> = Vec::new();
         let mut outside_offset_polygon_points_ids: Vec<usize> = Vec::new();
 
-        // Find min x point in order to find outside offset
+        // Find min x point in order to find outside offset, ties are broken by min y,
+        // so the start is always a vertex of the convex hull
         let mut start = 0;
         for index in 0..naive_offset_polygon.points_count() {
             if visited[index] {
@@ -1270,7 +3600,7 @@ impl<'a> PolygonObject<'a> {
             let pos = naive_offset_polygon.get_point_pos(index as isize);
             let pos_old = naive_offset_polygon.get_point_pos(start as isize);
 
-            if pos.x < pos_old.x {
+            if pos.x < pos_old.x || (pos.x == pos_old.x && pos.y < pos_old.y) {
                 start = index;
             }
         }
@@ -1369,10 +3699,188 @@ impl<'a> PolygonObject<'a> {
         }
         outside_offset_polygon_points.push(naive_offset_polygon.get_point_pos(start as isize));
 
-        self.offset_polygon = Polygon::create(outside_offset_polygon_points);
-        self.offset_polygon.set_edges_color(style::OFFSET_COLOR);
+        let mut offset_polygon = Polygon::create(outside_offset_polygon_points);
+        offset_polygon.set_edges_color(style::OFFSET_COLOR);
+        offset_polygon
     }
 
+    // Points of the naive offset at the vertex id. The concave corners and the miter joins
+    // get a single point, the edges offset lines cross there.
+    fn offset_join(&self, id: isize, size: f32) -> Vec<sf::Vector2f> {
+        let pos = self.polygon.get_point_pos(id);
+        let prev_normal = self.polygon.get_prev_normal(id);
+        let normal = self.polygon.get_normal(id);
+        let v01 = pos - self.polygon.get_point_pos(id - 1);
+        let v12 = self.polygon.get_point_pos(id + 1) - pos;
+        // The normals point to the left of the edges, turning right opens a gap between
+        // the offset edges
+        let convex = my_math::cross2(&v01, &v12) < 0.;
+
+        if !convex || self.join_style == JoinStyle::Miter {
+            return vec![pos + my_math::miter_vec(&prev_normal, &normal, self.miter_limit) * size];
+        }
+
+        let start = pos + prev_normal * size;
+        let end = pos + normal * size;
+        if self.join_style == JoinStyle::Bevel {
+            return vec![start, end];
+        }
+
+        let angle = my_math::cross2(&prev_normal, &normal).atan2(my_math::dot_prod(&prev_normal, &normal));
+        let arc_length = (angle * size).abs();
+        let segments = ((arc_length / style::ARC_SEGMENT_LENGTH).ceil() as usize).clamp(1, style::MAX_ARC_SEGMENTS);
+        (0..=segments)
+            .map(|i| my_math::rotate_around(&start, &pos, angle * i as f32 / segments as f32))
+            .collect()
+    }
+
+    /// Rigidly rotates the polygon around its center, so the edge starting at id becomes
+    /// horizontal or vertical. The smallest rotation is used, so the edge may end up
+    /// pointing either way along the axis.
+    pub fn align_edge(&mut self, id: isize, vertical: bool) {
+        let p0 = self.polygon.get_point_pos(id);
+        let p1 = self.polygon.get_point_pos(id + 1);
+        let d = p1 - p0;
+        if d.x == 0. && d.y == 0. {
+            return;
+        }
+
+        let target = if vertical { std::f32::consts::FRAC_PI_2 } else { 0. };
+        let angle = (target - d.y.atan2(d.x) + std::f32::consts::FRAC_PI_2)
+            .rem_euclid(std::f32::consts::PI) - std::f32::consts::FRAC_PI_2;
+
+        let pivot = self.polygon.find_center();
+        let rotated: Vec<(usize, sf::Vector2f)> = (0..self.polygon.points_count())
+            .map(|i| (i, my_math::rotate_around(&self.polygon.get_point_pos(i as isize), &pivot, angle)))
+            .collect();
+        self.set_points_pos(&rotated);
+
+        // Get rid of the rounding error on the aligned edge
+        let p0 = self.polygon.get_point_pos(id);
+        let mut p1 = self.polygon.get_point_pos(id + 1);
+        if vertical {
+            p1.x = p0.x;
+        } else {
+            p1.y = p0.y;
+        }
+        self.polygon.update_point_pos(p1, id + 1);
+
+        self.finish_transform();
+    }
+
+    /// Rotates the whole polygon about its center, the angle is in radians. Horizontal and
+    /// vertical constraints are dropped unless the edge still satisfies them.
+    pub fn rotate(&mut self, radians: f32) {
+        let center = self.polygon.find_center();
+        self.polygon.rotate_around(center, radians);
+        self.finish_transform();
+    }
+
+    /// Scales the whole polygon about its center. Fails if an edge would get shorter than
+    /// style::POLY_EDGE_MIN_LEN, the polygon is left as it was then. A uniform scale can't
+    /// make the polygon self-crossing. Fixed lengths are dropped.
+    pub fn scale(&mut self, factor: f32) -> Result<(), PolygonError> {
+        if factor <= 0. {
+            return Err(PolygonError::Degenerate);
+        }
+        for i in 0..self.polygon.points_count() as isize {
+            let length = my_math::distance(&self.polygon.get_point_pos(i), &self.polygon.get_point_pos(i + 1));
+            if length * factor < style::POLY_EDGE_MIN_LEN {
+                return Err(PolygonError::EdgeTooShort);
+            }
+        }
+
+        self.polygon.scale_about_center(factor);
+        self.finish_transform();
+        Ok(())
+    }
+
+    /// Mirrors the polygon around its center, left to right or top to bottom. Edge and angle
+    /// constraints survive, horizontal and vertical edges stay horizontal and vertical. The
+    /// arcs are mirrored along.
+    /// Pinned points stay in place, the polygon is left untouched if that makes it self-crossing.
+    pub fn mirror(&mut self, left_right: bool, winding: MirrorWinding) -> Result<(), PolygonError> {
+        let before: Vec<(usize, sf::Vector2f)> = (0..self.polygon.points_count())
+            .map(|i| (i, self.polygon.get_point_pos(i as isize)))
+            .collect();
+        let center = self.polygon.find_center();
+        let mirrored: Vec<(usize, sf::Vector2f)> = (0..self.polygon.points_count())
+            .map(|i| {
+                let p = self.polygon.get_point_pos(i as isize);
+                let p = if left_right {
+                    sf::Vector2f::new(2. * center.x - p.x, p.y)
+                } else {
+                    sf::Vector2f::new(p.x, 2. * center.y - p.y)
+                };
+                (i, p)
+            })
+            .collect();
+        // The reflection turns the arcs the other way, the reversal below turns them back
+        // for the edges walked backwards
+        let bulges: Vec<f32> = (0..self.polygon.points_count() as isize)
+            .map(|i| self.polygon.get_edge_bulge(i))
+            .collect();
+        self.set_points_pos(&mirrored);
+        self.polygon.set_edge_bulges(&bulges.iter().map(|bulge| -bulge).collect::<Vec<f32>>());
+        if self.polygon.is_self_crossing() {
+            // Revert changes
+            self.polygon.set_edge_bulges(&bulges);
+            self.set_points_pos(&before);
+            return Err(PolygonError::WouldSelfIntersect);
+        }
+
+        // Back to counter-clockwise, the selection follows the points
+        self.polygon.reverse_points(winding == MirrorWinding::KeepFirstVertex);
+        self.selection = (0..self.polygon.points_count())
+            .filter(|&i| self.polygon.is_point_selected(i as isize))
+            .collect();
+        self.finish_transform();
+        Ok(())
+    }
+
+    /// Inserts a point at t along the edge starting at id. As with insert_point, the
+    /// split edge loses its constraint and its arc and a point too close to an end of the
+    /// edge is refused. The new point becomes the selection.
+    pub fn insert_point_on_edge(&mut self, id: isize, t: f32) -> Result<(), PolygonError> {
+        let pos = self.polygon.edge_point(id, t);
+        if my_math::distance(&pos, &self.polygon.get_point_pos(id)) <= style::POLY_EDGE_MIN_LEN ||
+            my_math::distance(&pos, &self.polygon.get_point_pos(id + 1)) <= style::POLY_EDGE_MIN_LEN {
+            return Err(PolygonError::EdgeTooShort);
+        }
+
+        self.deselect_all_points();
+        let new_id = self.polygon.fix_index(id + 1) as isize;
+        self.polygon.set_edge_contsraint(id, EdgeConstraint::None);
+        self.polygon.insert_point_with_pos(new_id, pos);
+        self.select_point(new_id);
+        self.update_offset();
+        Ok(())
+    }
+
+    /// Splits the edge starting at id into count + 1 equal parts. As with a single insert
+    /// the split edge loses its constraint and the new points become the selection.
+    pub fn insert_points_on_edge(&mut self, id: isize, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let positions: Vec<sf::Vector2f> = (1..=count)
+            .map(|i| self.polygon.edge_point(id, i as f32 / (count + 1) as f32))
+            .collect();
+
+        self.deselect_all_points();
+        let new_id = self.polygon.fix_index(id + 1) as isize;
+        self.polygon.set_edge_contsraint(id, EdgeConstraint::None);
+        self.polygon.insert_points_with_pos(new_id, &positions);
+        for i in 0..count as isize {
+            self.select_point(new_id + i);
+        }
+        self.update_offset();
+    }
+}
+
+// The egui panels, the rest of the PolygonObject works without the gui feature
+#[cfg(feature = "gui")]
+impl<'a> PolygonObject<'a> {
     fn draw_line_constraints_egui(&mut self, id: isize, ui: &mut egui::Ui) {
         let line_prev = self.polygon.fix_index(id - 1) as isize;
         let line0 = self.polygon.fix_index(id) as isize;
@@ -1380,37 +3888,46 @@ impl<'a> PolygonObject<'a> {
 
         let p0 = self.polygon.get_point_pos(line0);
         let p1 = self.polygon.get_point_pos(line1);
+        let length = my_math::distance(&p0, &p1);
+
+        // Moving a fixed length neighbor changes both coordinates, so it can't be chained with
+        // the other constraints
+        let prev_constraint = self.polygon.get_edge_constraint(line_prev);
+        let next_constraint = self.polygon.get_edge_constraint(line1);
+        let fixed_neighbor = matches!(prev_constraint, EdgeConstraint::FixedLength(_)) ||
+            matches!(next_constraint, EdgeConstraint::FixedLength(_));
 
         // Pick the drawing method
         let mut old = self.polygon.get_edge_constraint(line0);
         let mut new = old.clone();
 
         egui::ComboBox::from_label(format!("({}, {}) Constraint", line0, line1))
-            .selected_text(match new {
-                EdgeConstraint::None => "None",
-                EdgeConstraint::Horizontal => "Horizontal",
-                EdgeConstraint::Vertical => "Vertical"
-            })
+            .selected_text(new.name())
             .show_ui(ui, |ui| {
                 ui.selectable_value(&mut new, EdgeConstraint::None, "None");
-                if (p1.x - p0.x).abs() > style::POINT_DETECTION_RADIUS &&
-                    self.polygon.get_edge_constraint(line_prev) != EdgeConstraint::Horizontal &&
-                    self.polygon.get_edge_constraint(line1) != EdgeConstraint::Horizontal {
+                if (p1.x - p0.x).abs() > style::POINT_DETECTION_RADIUS && !fixed_neighbor &&
+                    prev_constraint != EdgeConstraint::Horizontal &&
+                    next_constraint != EdgeConstraint::Horizontal {
                     ui.selectable_value(&mut new, EdgeConstraint::Horizontal, "Horizontal");
                 }
-                if (p1.y - p0.y).abs() > style::POINT_DETECTION_RADIUS &&
-                    self.polygon.get_edge_constraint(line_prev) != EdgeConstraint::Vertical &&
-                    self.polygon.get_edge_constraint(line1) != EdgeConstraint::Vertical {
+                if (p1.y - p0.y).abs() > style::POINT_DETECTION_RADIUS && !fixed_neighbor &&
+                    prev_constraint != EdgeConstraint::Vertical &&
+                    next_constraint != EdgeConstraint::Vertical {
                     ui.selectable_value(&mut new, EdgeConstraint::Vertical, "Vertical");
                 }
+                if length > style::POINT_DETECTION_RADIUS &&
+                    prev_constraint == EdgeConstraint::None &&
+                    next_constraint == EdgeConstraint::None {
+                    ui.selectable_value(&mut new, EdgeConstraint::FixedLength(length), format!("Fixed length ({:.1})", length));
+                }
             });
 
         if old != new {
             if new != EdgeConstraint::None &&
-                (new == self.polygon.get_edge_constraint(line0 - 1) ||
-                    new == self.polygon.get_edge_constraint(line1)) {
+                (new == prev_constraint || new == next_constraint) {
                 return;
             }
+            let before = self.polygon.clone();
             self.polygon.set_edge_contsraint(line0, new.clone());
 
             match new {
@@ -1425,6 +3942,8 @@ impl<'a> PolygonObject<'a> {
                     self.polygon.update_point_pos(sf::Vector2f::new(avg, p0.y), line0);
                     self.polygon.update_point_pos(sf::Vector2f::new(avg, p1.y), line1);
                 }
+                // The current length is kept, nothing moves
+                EdgeConstraint::FixedLength(_) => (),
                 EdgeConstraint::None => (),
             }
             if self.polygon.is_self_crossing() {
@@ -1432,26 +3951,123 @@ impl<'a> PolygonObject<'a> {
                 self.polygon.update_point_pos(p1, line1);
                 self.polygon.set_edge_contsraint(line0, old);
             } else {
+                self.pending_change = Some(before);
                 self.update_offset();
             }
         }
     }
 
+    fn draw_align_edge_egui(&mut self, id: isize, ui: &mut egui::Ui) {
+        let has_pinned = (0..self.polygon.points_count() as isize).any(|i| self.polygon.is_point_pinned(i));
+
+        let mut align: Option<bool> = None;
+        ui.add_enabled_ui(!has_pinned, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Make horizontal").on_hover_text("Rotate the polygon so the edge becomes horizontal").clicked() {
+                    align = Some(false);
+                }
+                if ui.button("Make vertical").on_hover_text("Rotate the polygon so the edge becomes vertical").clicked() {
+                    align = Some(true);
+                }
+            });
+        }).response.on_disabled_hover_text("A polygon with pinned points can't be rotated");
+
+        if let Some(vertical) = align {
+            self.pending_change = Some(self.polygon.clone());
+            self.align_edge(id, vertical);
+        }
+    }
+
+    fn draw_edge_bulge_egui(&mut self, id: isize, ui: &mut egui::Ui) {
+        let old = self.polygon.get_edge_bulge(id);
+        let mut bulge = old;
+        let mut dragged = false;
+        ui.horizontal(|ui| {
+            let response = ui.add(egui::Slider::new(&mut bulge, -style::MAX_BULGE..=style::MAX_BULGE).text("Bulge"))
+                .on_hover_text("Turn the edge into a circular arc, 0 is straight and 1 is a half circle");
+            dragged = response.dragged();
+            if old != 0. && ui.button("Straighten").clicked() {
+                bulge = 0.;
+            }
+        });
+
+        if bulge != old {
+            let before = self.polygon.clone();
+            if self.set_edge_bulge(id, bulge).is_ok() {
+                // A slider drag is recorded once
+                if !self.point_drag_recorded {
+                    self.pending_change = Some(before);
+                }
+                self.point_drag_recorded = dragged;
+            }
+        }
+        if !dragged {
+            self.point_drag_recorded = false;
+        }
+    }
+
+    fn draw_precise_insert_egui(&mut self, id: isize, ui: &mut egui::Ui) {
+        let p0 = self.polygon.get_point_pos(id);
+        let p1 = self.polygon.get_point_pos(id + 1);
+        let length = my_math::distance(&p0, &p1);
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.insert_by_distance, false, "t");
+            ui.selectable_value(&mut self.insert_by_distance, true, "Distance");
+            if self.insert_by_distance {
+                ui.add(egui::DragValue::new(&mut self.insert_param).clamp_range(0.0..=length).speed(0.5));
+            } else {
+                ui.add(egui::DragValue::new(&mut self.insert_param).clamp_range(0.0..=1.0).speed(0.01));
+            }
+        });
+
+        let t = if self.insert_by_distance {
+            if length == 0. { 0. } else { self.insert_param / length }
+        } else {
+            self.insert_param
+        };
+        let pos = self.polygon.edge_point(id, t.clamp(0., 1.));
+        let fits = my_math::distance(&pos, &p0) > style::POLY_EDGE_MIN_LEN &&
+            my_math::distance(&pos, &p1) > style::POLY_EDGE_MIN_LEN;
+        if ui.add_enabled(fits, egui::Button::new("Insert vertex"))
+            .on_disabled_hover_text("The vertex would be too close to an end of the edge")
+            .clicked() {
+            let before = self.polygon.clone();
+            if self.insert_point_on_edge(id, t.clamp(0., 1.)).is_ok() {
+                self.pending_change = Some(before);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.insert_count).clamp_range(1..=style::MAX_EDGE_INSERT_COUNT));
+            if ui.button("Insert evenly spaced").on_hover_text("Split the edge into equal parts").clicked() {
+                self.pending_change = Some(self.polygon.clone());
+                self.insert_points_on_edge(id, self.insert_count);
+            }
+        });
+    }
+
     pub fn draw_selected_edge_egui(&mut self, ui: &mut egui::Ui) -> bool {
         if self.selection.len() != 2 {
             return false;
         }
 
-        if let Some(id) = self.selection.iter().next() {
-            let next_id = self.polygon.fix_index(*id as isize + 1);
-            let prev_id = self.polygon.fix_index(*id as isize - 1);
+        if let Some(&id) = self.selection.iter().next() {
+            let next_id = self.polygon.fix_index(id as isize + 1);
+            let prev_id = self.polygon.fix_index(id as isize - 1);
 
             if self.selection.contains(&next_id) {
-                self.draw_line_constraints_egui(*id as isize, ui);
+                self.draw_line_constraints_egui(id as isize, ui);
+                self.draw_align_edge_egui(id as isize, ui);
+                self.draw_edge_bulge_egui(id as isize, ui);
+                self.draw_precise_insert_egui(id as isize, ui);
                 return true;
             }
             if self.selection.contains(&prev_id) {
                 self.draw_line_constraints_egui(prev_id as isize, ui);
+                self.draw_align_edge_egui(prev_id as isize, ui);
+                self.draw_edge_bulge_egui(prev_id as isize, ui);
+                self.draw_precise_insert_egui(prev_id as isize, ui);
                 return true;
             }
         }
@@ -1459,23 +4075,277 @@ impl<'a> PolygonObject<'a> {
     }
 
     pub fn draw_polygon_options_egui(&mut self, ui: &mut egui::Ui) {
-        let mut show_offset = self.show_offset;
-        let mut offset = self.offset_size;
-        let mut naive = self.naive_offset;
-
-        ui.checkbox(&mut show_offset, "Show Offset");
-        ui.checkbox(&mut naive, "Naive Offset");
-        ui.add(egui::Slider::new(&mut offset, 0.0..=style::MAX_OFFSET).text("Offset"));
-
-        if show_offset != self.show_offset || offset != self.offset_size || naive != self.naive_offset {
-            self.offset_size = offset;
-            self.naive_offset = naive;
-            self.show_offset = show_offset;
-            self.update_offset();
+        if self.differs_from_baseline() &&
+            ui.button("Revert").on_hover_text("Restore the geometry from the last load or save").clicked() {
+            self.revert_to_baseline();
+        }
+
+        let mut open = self.polygon.is_open();
+        if ui.checkbox(&mut open, "Hide closing edge")
+            .on_hover_text("Display as an open chain, the area, fill and offset still use the closed polygon")
+            .changed() {
+            self.pending_change = Some(self.polygon.clone());
+            self.polygon.set_open(open);
+        }
+
+        let color = self.polygon.edges_color();
+        let mut rgb = [color.r, color.g, color.b];
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgb(&mut rgb);
+            ui.label("Edges color");
+        });
+        self.polygon.set_edges_color(sf::Color::rgb(rgb[0], rgb[1], rgb[2]));
+
+        self.draw_rotate_egui(ui);
+
+        if ui.checkbox(&mut self.show_hull, "Show convex hull")
+            .on_hover_text("Draw the smallest convex polygon containing all of the points")
+            .changed() {
+            self.update_hull();
+        }
+
+        // Offset and fill are only defined for proper, simple polygons
+        let invalid_reason = if !self.polygon.is_proper() {
+            Some("The polygon has less than 3 points")
+        } else if self.polygon.is_self_crossing() {
+            Some("The polygon is self-crossing")
+        } else {
+            None
+        };
+
+        let response = ui.add_enabled_ui(invalid_reason.is_none(), |ui| {
+            let mut show_offset = self.show_offset;
+            let mut offset = self.offset_size;
+            let mut naive = self.naive_offset;
+            let mut join_style = self.join_style;
+            let mut miter_limit = self.miter_limit;
+            let mut show_bands = self.show_bands;
+            let mut band_count = self.band_count;
+            let mut band_spacing = self.band_spacing;
+
+            ui.checkbox(&mut self.show_triangulation, "Show triangulation")
+                .on_hover_text("Draw the diagonals splitting the polygon into triangles");
+            ui.checkbox(&mut show_offset, "Show Offset");
+            ui.checkbox(&mut naive, "Naive Offset");
+            ui.add(egui::Slider::new(&mut offset, 0.0..=style::MAX_OFFSET).text("Offset"));
+            ui.horizontal(|ui| {
+                ui.label("Join");
+                for join in [JoinStyle::Miter, JoinStyle::Round, JoinStyle::Bevel] {
+                    ui.selectable_value(&mut join_style, join, join.name());
+                }
+            });
+            // The concave corners are mitered with every join style
+            ui.add(egui::Slider::new(&mut miter_limit, 1.0..=style::MAX_MITER_LIMIT).text("Miter limit"))
+                .on_hover_text("Longest miter join in multiples of the offset, concave corners included");
+
+            ui.checkbox(&mut show_bands, "Offset bands")
+                .on_hover_text("Draw nested offsets at multiples of the spacing, like contour lines");
+            ui.add_enabled_ui(show_bands, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut band_count).clamp_range(1..=style::MAX_OFFSET_BANDS).prefix("Bands: "));
+                    ui.add(egui::DragValue::new(&mut band_spacing).clamp_range(1.0..=style::MAX_OFFSET).speed(0.5).prefix("Spacing: "));
+                });
+                if self.show_bands && self.band_polygons.len() < self.band_count {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(237, 123, 123),
+                        format!("Stopped after {} bands, the offset breaks down further out", self.band_polygons.len()),
+                    );
+                }
+            });
+
+            if show_offset != self.show_offset || offset != self.offset_size || naive != self.naive_offset ||
+                join_style != self.join_style || miter_limit != self.miter_limit ||
+                show_bands != self.show_bands || band_count != self.band_count || band_spacing != self.band_spacing {
+                self.offset_size = offset;
+                self.naive_offset = naive;
+                self.show_offset = show_offset;
+                self.join_style = join_style;
+                self.miter_limit = miter_limit;
+                self.show_bands = show_bands;
+                self.band_count = band_count;
+                self.band_spacing = band_spacing;
+                self.update_offset();
+            }
+
+            self.draw_fill_options_egui(ui);
+        }).response;
+
+        if let Some(reason) = invalid_reason {
+            response.on_hover_text(reason);
+        }
+    }
+
+    fn draw_rotate_egui(&mut self, ui: &mut egui::Ui) {
+        let has_pinned = (0..self.polygon.points_count() as isize).any(|i| self.polygon.is_point_pinned(i));
+
+        ui.add_enabled_ui(!has_pinned, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.rotate_angle).clamp_range(-180.0..=180.0).speed(1.).suffix("°"));
+                if ui.button("Rotate").on_hover_text("Rotate the polygon about its center, counter-clockwise on the screen").clicked() {
+                    self.pending_change = Some(self.polygon.clone());
+                    // Screen y axis points down
+                    self.rotate(-self.rotate_angle.to_radians());
+                }
+            });
+        }).response.on_disabled_hover_text("A polygon with pinned points can't be rotated");
+
+        ui.add_enabled_ui(!has_pinned, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut self.scale_factor, 0.1..=10.0).logarithmic(true));
+                if ui.button("Scale").on_hover_text("Scale the polygon about its center").clicked() {
+                    let before = self.polygon.clone();
+                    match self.scale(self.scale_factor) {
+                        Ok(()) => {
+                            self.pending_change = Some(before);
+                            self.scale_error = None;
+                        }
+                        Err(err) => self.scale_error = Some(err),
+                    }
+                }
+            });
+            if let Some(err) = self.scale_error.as_ref() {
+                ui.colored_label(egui::Color32::from_rgb(237, 123, 123), format!("Can't scale: {}", err));
+            }
+        }).response.on_disabled_hover_text("A polygon with pinned points can't be scaled");
+    }
+
+    fn draw_fill_options_egui(&mut self, ui: &mut egui::Ui) {
+        let mut fill_style = self.polygon.fill_style();
+        egui::ComboBox::from_label("Fill")
+            .selected_text(match fill_style {
+                FillStyle::None => "None",
+                FillStyle::Solid => "Solid",
+                FillStyle::Texture => "Texture",
+                FillStyle::Hatch => "Hatch",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut fill_style, FillStyle::None, "None");
+                ui.selectable_value(&mut fill_style, FillStyle::Solid, "Solid");
+                ui.selectable_value(&mut fill_style, FillStyle::Texture, "Texture");
+                ui.selectable_value(&mut fill_style, FillStyle::Hatch, "Hatch");
+            });
+        self.polygon.set_fill_style(fill_style.clone());
+
+        match fill_style {
+            FillStyle::None => (),
+            FillStyle::Solid | FillStyle::Hatch => {
+                let color = self.polygon.fill_color();
+                let mut rgba = egui::Color32::from_rgba_unmultiplied(color.r, color.g, color.b, color.a);
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgba(&mut rgba);
+                    ui.label("Fill color");
+                });
+                let [r, g, b, a] = rgba.to_srgba_unmultiplied();
+                self.polygon.set_fill_color(sf::Color::rgba(r, g, b, a));
+
+                if fill_style == FillStyle::Hatch {
+                    let mut angle = self.polygon.hatch_angle();
+                    let mut spacing = self.polygon.hatch_spacing();
+                    ui.add(egui::Slider::new(&mut angle, 0.0..=180.0).text("Hatch angle"));
+                    ui.add(egui::Slider::new(&mut spacing, style::HATCH_MIN_SPACING..=50.0).text("Hatch spacing"));
+                    self.polygon.set_hatch_angle(angle);
+                    self.polygon.set_hatch_spacing(spacing);
+                }
+            }
+            FillStyle::Texture => {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.texture_path_input);
+                    if ui.button("Load texture").clicked() {
+                        let path = self.texture_path_input.clone();
+                        self.polygon.load_fill_texture(&path);
+                    }
+                });
+                if let Some(path) = self.polygon.fill_texture_path() {
+                    ui.label(format!("Texture: {}", path));
+                } else {
+                    ui.label("Texture: None");
+                }
+
+                let mut scale = self.polygon.fill_texture_scale();
+                ui.add(egui::Slider::new(&mut scale, 0.1..=10.0).text("Tiling scale"));
+                self.polygon.set_fill_texture_scale(scale);
+            }
+        }
+    }
+
+    /// Coordinates of the only selected point. Constrained neighbours follow the point,
+    /// moves that would make the polygon self-crossing are rejected.
+    fn draw_selected_point_egui(&mut self, ui: &mut egui::Ui) {
+        if self.selection.len() != 1 {
+            return;
+        }
+        let id = *self.selection.iter().next().unwrap() as isize;
+        let old = self.polygon.get_point_pos(id);
+        let mut new = old;
+        let mut dragged = false;
+
+        ui.add_enabled_ui(!self.polygon.is_point_pinned(id), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Point {}", id));
+                dragged |= ui.add(egui::DragValue::new(&mut new.x).prefix("x: ").speed(0.5)).dragged();
+                dragged |= ui.add(egui::DragValue::new(&mut new.y).prefix("y: ").speed(0.5)).dragged();
+            });
+        }).response.on_disabled_hover_text("The point is pinned");
+
+        let old_constraint = self.polygon.get_angle_constraint(id);
+        let rotate_next = if self.can_rotate_neighbor(id, true) {
+            Some(true)
+        } else if self.can_rotate_neighbor(id, false) {
+            Some(false)
+        } else {
+            None
+        };
+        let mut fixed = old_constraint.is_some();
+        let old_degrees = old_constraint.unwrap_or_else(|| self.polygon.interior_angle(id)).to_degrees();
+        let mut degrees = old_degrees;
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut fixed, "Fix angle");
+            ui.add_enabled_ui(fixed && rotate_next.is_some(), |ui| {
+                let response = ui.add(egui::DragValue::new(&mut degrees)
+                    .suffix("°")
+                    .clamp_range(1.0..=359.0)
+                    .speed(0.5));
+                dragged |= response.dragged();
+            }).response.on_disabled_hover_text("Both neighbors are locked");
+        });
+
+        if new != old {
+            let before = self.polygon.clone();
+            self.move_selected_points(new - old);
+            if self.polygon.is_self_crossing() {
+                self.polygon = before;
+            } else {
+                if !self.point_drag_recorded {
+                    self.pending_change = Some(before);
+                }
+                self.point_drag_recorded = dragged;
+                self.assert_ccw();
+                self.update_offset();
+            }
+        } else if fixed != old_constraint.is_some() {
+            self.pending_change = Some(self.polygon.clone());
+            self.polygon.set_angle_constraint(id, fixed.then(|| self.polygon.interior_angle(id)));
+        } else if let (true, Some(rotate_next)) = (degrees != old_degrees, rotate_next) {
+            let before = self.polygon.clone();
+            self.polygon.set_angle_constraint(id, Some(degrees.to_radians()));
+            self.polygon.set_interior_angle(id, degrees.to_radians(), rotate_next);
+            if self.polygon.is_self_crossing() {
+                self.polygon = before;
+            } else {
+                if !self.point_drag_recorded {
+                    self.pending_change = Some(before);
+                }
+                self.point_drag_recorded = dragged;
+                self.update_offset();
+            }
+        }
+        if !dragged {
+            self.point_drag_recorded = false;
         }
     }
 
     pub fn draw_egui(&mut self, ui: &mut egui::Ui) {
+        self.draw_selected_point_egui(ui);
         self.draw_polygon_options_egui(ui);
 
         egui::CollapsingHeader::new("Edges")
@@ -1486,4 +4356,651 @@ impl<'a> PolygonObject<'a> {
                 }
             });
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(size: f32) -> Vec<sf::Vector2f> {
+        vec![
+            sf::Vector2f::new(0., 0.),
+            sf::Vector2f::new(size, 0.),
+            sf::Vector2f::new(size, size),
+            sf::Vector2f::new(0., size),
+        ]
+    }
+
+    fn circle(radius: f32, count: usize) -> Vec<sf::Vector2f> {
+        (0..count)
+            .map(|i| {
+                let angle = 2. * std::f32::consts::PI * i as f32 / count as f32;
+                sf::Vector2f::new(radius * angle.cos(), radius * angle.sin())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn edge_at_projects_onto_square_edges() {
+        let poly = PolygonObject::from(Polygon::create(square(100.)));
+
+        let (id, t) = poly.edge_at(sf::Vector2f::new(25., 3.)).unwrap();
+        assert_eq!(id, 0);
+        assert!((t - 0.25).abs() < 1e-5);
+
+        let (id, t) = poly.edge_at(sf::Vector2f::new(104., 60.)).unwrap();
+        assert_eq!(id, 1);
+        assert!((t - 0.6).abs() < 1e-5);
+
+        // The closing edge goes from the last point back to the first one
+        let (id, t) = poly.edge_at(sf::Vector2f::new(-2., 90.)).unwrap();
+        assert_eq!(id, 3);
+        assert!((t - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn edge_at_ignores_far_points() {
+        let poly = PolygonObject::from(Polygon::create(square(100.)));
+        assert_eq!(poly.edge_at(sf::Vector2f::new(50., 50.)), None);
+        assert_eq!(poly.edge_at(sf::Vector2f::new(50., -style::LINE_DETECTION_DISTANCE - 1.)), None);
+    }
+
+    // A half circle bulging up from the top edge of the square
+    fn arched_square() -> Polygon<'static> {
+        let mut polygon = Polygon::create(square(100.));
+        polygon.set_edge_bulge(0, 1.);
+        polygon
+    }
+
+    #[test]
+    fn edge_at_follows_arcs() {
+        let mut poly = PolygonObject::from(arched_square());
+
+        // The top of the arc is far away from the chord
+        let (id, t) = poly.edge_at(sf::Vector2f::new(50., -48.)).unwrap();
+        assert_eq!(id, 0);
+        assert!((t - 0.5).abs() < 0.05);
+        assert!(my_math::distance(&poly.polygon().edge_point(0, t), &sf::Vector2f::new(50., -50.)) < 1.);
+        assert_eq!(poly.edge_at(sf::Vector2f::new(50., 2.)), None);
+
+        poly.insert_point_on_edge(0, 0.5).unwrap();
+        assert!(my_math::distance(&poly.polygon().get_point_pos(1), &sf::Vector2f::new(50., -50.)) < 1e-3);
+    }
+
+    #[test]
+    fn insert_on_edge_refuses_the_endpoints() {
+        let mut poly = PolygonObject::from(Polygon::create(square(100.)));
+        assert_eq!(poly.insert_point_on_edge(0, 0.), Err(PolygonError::EdgeTooShort));
+        assert_eq!(poly.insert_point_on_edge(0, 1.), Err(PolygonError::EdgeTooShort));
+        assert_eq!(poly.polygon().points_count(), 4);
+
+        poly.insert_point_on_edge(0, 0.25).unwrap();
+        assert_eq!(poly.polygon().points_count(), 5);
+    }
+
+    #[test]
+    fn insert_point_keeps_an_arc_that_would_cross() {
+        let p = sf::Vector2f::new;
+        // A notch reaching up under the arc of the top edge
+        let mut polygon = Polygon::create(vec![
+            p(0., 0.), p(100., 0.), p(100., 100.), p(60., 100.), p(50., -30.), p(40., 100.), p(0., 100.),
+        ]);
+        polygon.set_edge_bulge(0, 1.);
+        let mut poly = PolygonObject::from(polygon);
+        assert!(!poly.polygon().is_self_crossing());
+
+        // The straightened halves pass below the tip of the notch
+        let angle = 30f32.to_radians();
+        let on_arc = p(50. - 50. * angle.cos(), -50. * angle.sin());
+        assert_eq!(poly.insert_point(1, on_arc), Err(PolygonError::WouldSelfIntersect));
+        assert_eq!(poly.polygon().points_count(), 7);
+        assert_eq!(poly.polygon().get_edge_bulge(0), 1.);
+
+        assert_eq!(poly.insert_point(1, p(1., 0.)), Err(PolygonError::EdgeTooShort));
+    }
+
+    #[test]
+    fn fill_covers_arcs() {
+        let mut polygon = arched_square();
+        polygon.set_hatch_angle(0.);
+        polygon.set_hatch_spacing(10.);
+
+        let outline = polygon.outline();
+        assert!(outline.len() > polygon.points_count());
+        let area: f32 = my_math::ear_clipping(&outline)
+            .iter()
+            .map(|tri| my_math::signed_area(&tri.map(|id| outline[id])).abs())
+            .sum();
+        let half_disc = std::f32::consts::PI * 50. * 50. / 2.;
+        assert!((area - 100. * 100. - half_disc).abs() < 0.02 * half_disc);
+
+        // Hatching lines above the chord cross the arc only
+        assert!(polygon.hatch_segments().iter().any(|(p0, _)| p0.y < -30.));
+    }
+
+    #[test]
+    fn closest_points_of_nested_polygons_coincide() {
+        let outer = Polygon::create(square(100.));
+        let inner = Polygon::create(square(20.).into_iter().map(|p| p + sf::Vector2f::new(40., 40.)).collect());
+        let (p, q) = outer.closest_points_to(&inner).unwrap();
+        assert_eq!(p, q);
+        let (p, q) = inner.closest_points_to(&outer).unwrap();
+        assert_eq!(p, q);
+
+        let apart = Polygon::create(square(20.).into_iter().map(|p| p + sf::Vector2f::new(130., 40.)).collect());
+        let (p, q) = outer.closest_points_to(&apart).unwrap();
+        assert!((my_math::distance(&p, &q) - 30.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn simplify_circle_to_octagon() {
+        let mut poly = PolygonObject::from(Polygon::create(circle(100., 100)));
+        poly.simplify_to(8).unwrap();
+
+        let polygon = poly.polygon();
+        assert_eq!(polygon.points_count(), 8);
+        assert!(!polygon.is_self_crossing());
+        for id in 0..8 {
+            let pos = polygon.get_point_pos(id);
+            assert!(((pos.x * pos.x + pos.y * pos.y).sqrt() - 100.).abs() < 1e-3);
+        }
+        // A regular octagon inscribed in the circle covers 2*sqrt(2)*r^2
+        assert!((polygon.area().abs() - 2. * 2f32.sqrt() * 100. * 100.).abs() < 1500.);
+    }
+
+    #[test]
+    fn simplify_rejects_targets_below_a_triangle() {
+        let mut poly = PolygonObject::from(Polygon::create(circle(100., 100)));
+        assert_eq!(poly.simplify_to(2), Err(PolygonError::TooFewPoints));
+        assert_eq!(poly.polygon().points_count(), 100);
+    }
+
+    #[test]
+    fn offset_of_a_slot_with_a_vertical_left_edge() {
+        // The slot is narrower than twice the offset, so the naive offset crosses itself.
+        // The left edge is vertical with a collinear point, three offset points share the
+        // minimal x and the loop has to start at the topmost one.
+        let mut poly = PolygonObject::from(Polygon::create(vec![
+            sf::Vector2f::new(0., 0.),
+            sf::Vector2f::new(100., 0.),
+            sf::Vector2f::new(100., 100.),
+            sf::Vector2f::new(55., 100.),
+            sf::Vector2f::new(55., 20.),
+            sf::Vector2f::new(45., 20.),
+            sf::Vector2f::new(45., 100.),
+            sf::Vector2f::new(0., 100.),
+            sf::Vector2f::new(0., 50.),
+        ]));
+        poly.assert_ccw();
+
+        let offset = poly.build_offset(10.);
+        assert!(offset.get_self_crossing_edges().is_empty());
+        assert!(offset.area().abs() > poly.polygon().area().abs());
+
+        let bounds = offset.bounds();
+        assert!((bounds.left + 10.).abs() < 1e-3);
+        assert!((bounds.top + 10.).abs() < 1e-3);
+        assert!((bounds.width - 120.).abs() < 1e-3);
+        assert!((bounds.height - 120.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn try_create_merges_repeated_points() {
+        let mut points = square(100.);
+        points.insert(1, points[0]);
+        points.push(points[0]);
+        let polygon = Polygon::try_create(points, style::POLY_MIN_AREA).unwrap();
+        assert_eq!(polygon.points_count(), 4);
+    }
+
+    #[test]
+    fn try_create_rejects_invalid_points() {
+        let p = sf::Vector2f::new;
+        assert_eq!(
+            Polygon::try_create(vec![p(0., 0.), p(10., 0.), p(10., 0.), p(0., 0.)], style::POLY_MIN_AREA).err(),
+            Some(PolygonError::TooFewPoints),
+        );
+        assert_eq!(
+            Polygon::try_create(vec![p(0., 0.), p(10., 0.), p(20., 0.)], style::POLY_MIN_AREA).err(),
+            Some(PolygonError::Degenerate),
+        );
+        assert_eq!(
+            Polygon::try_create(vec![p(0., 0.), p(10., 10.), p(10., 0.), p(0., 10.)], style::POLY_MIN_AREA).err(),
+            Some(PolygonError::WouldSelfIntersect),
+        );
+        // Not exactly collinear, but a sliver
+        assert_eq!(
+            Polygon::try_create(vec![p(0., 0.), p(100., 0.), p(50., 0.5)], style::POLY_MIN_AREA).err(),
+            Some(PolygonError::Degenerate),
+        );
+    }
+
+    #[test]
+    fn try_create_makes_the_winding_counter_clockwise() {
+        let mut reversed = square(100.);
+        reversed.reverse();
+        for points in [square(100.), reversed] {
+            let mut polygon = Polygon::try_create(points, style::POLY_MIN_AREA).unwrap();
+            assert!(!polygon.assert_ccw());
+        }
+    }
+
+    #[test]
+    fn merge_collinear_edges_keeps_only_shared_direction() {
+        let p = sf::Vector2f::new;
+        let mut poly = PolygonObject::from(Polygon::create(vec![
+            p(0., 0.), p(50., 0.), p(100., 0.), p(100., 100.), p(50., 100.), p(0., 100.),
+        ]));
+        poly.polygon.set_edge_contsraint(0, EdgeConstraint::Horizontal);
+        poly.polygon.set_edge_contsraint(1, EdgeConstraint::Horizontal);
+        poly.polygon.set_edge_contsraint(3, EdgeConstraint::FixedLength(50.));
+        poly.polygon.set_edge_contsraint(4, EdgeConstraint::FixedLength(50.));
+
+        assert_eq!(poly.merge_collinear_edges(0.5, &[]), 2);
+        assert_eq!(poly.polygon().points_count(), 4);
+        assert_eq!(poly.polygon().get_edge_constraint(0), EdgeConstraint::Horizontal);
+        assert_eq!(poly.polygon().get_edge_constraint(2), EdgeConstraint::None);
+    }
+
+    #[test]
+    fn merge_collinear_edges_spares_kept_points() {
+        let p = sf::Vector2f::new;
+        let mut poly = PolygonObject::from(Polygon::create(vec![
+            p(0., 0.), p(50., 0.), p(100., 0.), p(100., 100.), p(50., 100.2), p(0., 100.),
+        ]));
+        poly.polygon.set_point_pinned(1, true);
+
+        // The top midpoint is off the line by more than the tolerance
+        assert_eq!(poly.merge_collinear_edges(0.1, &[]), 0);
+        assert_eq!(poly.merge_collinear_edges(0.5, &[p(50., 100.)]), 0);
+        assert_eq!(poly.merge_collinear_edges(0.5, &[]), 1);
+        assert_eq!(poly.polygon().points_count(), 5);
+    }
+
+    #[test]
+    fn factory_keeps_the_edges_color_between_frames() {
+        let p = sf::Vector2f::new;
+        let mut factory = PolygonObjectFactory::new();
+        factory.add(p(0., 0.));
+        factory.add(p(100., 0.));
+        factory.add(p(100., 100.));
+
+        factory.update(0., p(50., 150.));
+        let builds = factory.polygon().unwrap().lines_vb_builds;
+        factory.update(0., p(40., 150.));
+        factory.update(0., p(30., 150.));
+        assert_eq!(factory.polygon().unwrap().lines_vb_builds, builds);
+
+        // Crossing the first edge turns the edges red once
+        factory.update(0., p(50., -50.));
+        factory.update(0., p(60., -50.));
+        assert_eq!(factory.polygon().unwrap().edges_color(), style::LINES_COLOR_INCORRECT);
+        assert_eq!(factory.polygon().unwrap().lines_vb_builds, builds + 1);
+    }
+
+    #[test]
+    fn factory_splits_only_accepted_clicks() {
+        let p = sf::Vector2f::new;
+        let mut factory = PolygonObjectFactory::new();
+        factory.set_split_at_crossings(true);
+        factory.add(p(0., 0.));
+        factory.add(p(100., 0.));
+        factory.add(p(100., 100.));
+        factory.add(p(50., 100.));
+
+        // Too close to the crossing with the first edge, nothing is cut off
+        factory.update(0., p(50., -2.));
+        assert!(factory.add_or_build(p(50., -2.)).is_none());
+        assert!(factory.take_split_polygons().is_empty());
+        assert_eq!(factory.polygon().unwrap().points_count(), 4);
+
+        factory.update(0., p(50., -50.));
+        assert!(factory.add_or_build(p(50., -50.)).is_none());
+        assert_eq!(factory.take_split_polygons().len(), 1);
+        assert_eq!(factory.polygon().unwrap().points_count(), 3);
+    }
+
+    #[test]
+    fn factory_plans_the_splits_at_the_click() {
+        let p = sf::Vector2f::new;
+        let mut factory = PolygonObjectFactory::new();
+        factory.set_split_at_crossings(true);
+        factory.add(p(0., 0.));
+        factory.add(p(100., 0.));
+        factory.add(p(100., 100.));
+        factory.add(p(50., 100.));
+
+        // The cursor was elsewhere during the last update
+        factory.update(0., p(50., 150.));
+        assert!(factory.add_or_build(p(50., -50.)).is_none());
+        assert_eq!(factory.take_split_polygons().len(), 1);
+        assert_eq!(factory.polygon().unwrap().points_count(), 3);
+    }
+
+    #[test]
+    fn offset_rasterizes_without_a_window() {
+        let p = sf::Vector2f::new;
+        let mut poly = PolygonObject::from(Polygon::create(vec![p(20., 20.), p(80., 20.), p(80., 80.), p(20., 80.)]));
+        poly.assert_ccw();
+        let offset = poly.build_offset(10.);
+
+        let mut image = sf::Image::new(100, 100);
+        let mut painter = LinePainter::new(style::LINES_COLOR, 1.);
+        poly.polygon().draw_edges_bresenham(&mut image, &mut painter);
+        offset.draw_edges_bresenham(&mut image, &mut painter);
+
+        assert!(painter.pixels_drawn() > 0);
+        unsafe {
+            assert_eq!(image.pixel_at(20, 50), style::LINES_COLOR);
+            assert_eq!(image.pixel_at(10, 50), style::OFFSET_COLOR);
+            assert_eq!(image.pixel_at(50, 50), sf::Color::BLACK);
+        }
+    }
+
+    #[test]
+    fn solid_fill_rasterizes_without_a_window() {
+        let mut polygon = Polygon::create(square(40.));
+        polygon.set_fill_style(FillStyle::Solid);
+        polygon.set_fill_color(sf::Color::RED);
+
+        let mut image = sf::Image::new(50, 50);
+        let mut painter = LinePainter::new(style::LINES_COLOR, 1.);
+        polygon.draw_fill_bresenham(&mut image, &mut painter);
+
+        assert_eq!(painter.pixels_drawn(), 40 * 40);
+        unsafe {
+            assert_eq!(image.pixel_at(20, 20), sf::Color::RED);
+            assert_eq!(image.pixel_at(45, 20), sf::Color::BLACK);
+        }
+    }
+
+    #[test]
+    fn mirror_refuses_to_cross_pinned_points() {
+        let p = sf::Vector2f::new;
+        let points = vec![p(0., 0.), p(100., 0.), p(80., 100.), p(0., 60.)];
+        let mut poly = PolygonObject::from(Polygon::create(points.clone()));
+        poly.polygon.set_point_pinned(1, true);
+
+        assert_eq!(poly.mirror(true, MirrorWinding::KeepFirstVertex), Err(PolygonError::WouldSelfIntersect));
+        for (id, pos) in points.iter().enumerate() {
+            assert_eq!(poly.polygon().get_point_pos(id as isize), *pos);
+        }
+    }
+
+    fn house() -> Polygon<'static> {
+        let p = sf::Vector2f::new;
+        let mut polygon = Polygon::create(vec![p(0., 0.), p(100., 0.), p(100., 60.), p(50., 100.), p(0., 60.)]);
+        polygon.set_edge_contsraint(0, EdgeConstraint::Horizontal);
+        polygon.set_edge_contsraint(1, EdgeConstraint::Vertical);
+        polygon.set_edge_contsraint(3, EdgeConstraint::FixedLength(64.03));
+        polygon.set_edge_bulge(2, 0.25);
+        polygon
+    }
+
+    // Edge of the polygon going between the given positions, in either direction
+    fn find_edge(polygon: &Polygon, a: sf::Vector2f, b: sf::Vector2f) -> Option<(isize, bool)> {
+        (0..polygon.points_count() as isize).find_map(|id| {
+            let (p0, p1) = (polygon.get_point_pos(id), polygon.get_point_pos(id + 1));
+            if (p0, p1) == (a, b) {
+                Some((id, true))
+            } else if (p0, p1) == (b, a) {
+                Some((id, false))
+            } else {
+                None
+            }
+        })
+    }
+
+    #[test]
+    fn reverse_points_keeps_constraints_on_their_edges() {
+        let original = house();
+        for keep_first in [false, true] {
+            let mut reversed = original.clone();
+            reversed.reverse_points(keep_first);
+            if keep_first {
+                assert_eq!(reversed.get_point_pos(0), original.get_point_pos(0));
+            }
+
+            for id in 0..original.points_count() as isize {
+                let (a, b) = (original.get_point_pos(id), original.get_point_pos(id + 1));
+                let (new_id, forward) = find_edge(&reversed, a, b).unwrap();
+                assert!(!forward);
+                assert_eq!(reversed.get_edge_constraint(new_id), original.get_edge_constraint(id));
+                assert_eq!(reversed.get_edge_bulge(new_id), -original.get_edge_bulge(id));
+            }
+        }
+    }
+
+    #[test]
+    fn raw_keeps_angle_constraints() {
+        let mut polygon = Polygon::create(square(100.));
+        polygon.set_angle_constraint(1, Some(std::f32::consts::FRAC_PI_2));
+        let json = to_string(&polygon.get_raw()).unwrap();
+
+        let mut loaded = Polygon::new();
+        loaded.set_points_from_raw(from_str(&json).unwrap());
+        assert_eq!(loaded.get_angle_constraint(0), None);
+        assert_eq!(loaded.get_angle_constraint(1), Some(std::f32::consts::FRAC_PI_2));
+
+        // The files saved before the angle constraints still load
+        let raw: RawPolygonCoords = from_str(r#"{"coords":[{"x":0,"y":0},{"x":1,"y":0},{"x":0,"y":1}]}"#).unwrap();
+        assert!(raw.angles.is_empty());
+    }
+
+    #[test]
+    fn begin_append_drops_the_end_angles() {
+        let mut polygon = Polygon::create(square(100.));
+        for id in 0..4 {
+            polygon.set_angle_constraint(id, Some(std::f32::consts::FRAC_PI_2));
+        }
+        let mut poly = PolygonObject::from(polygon);
+        poly.begin_append();
+        let angles: Vec<_> = (0..4).map(|id| poly.polygon().get_angle_constraint(id)).collect();
+        assert_eq!(angles, [None, Some(std::f32::consts::FRAC_PI_2), Some(std::f32::consts::FRAC_PI_2), None]);
+    }
+
+    #[test]
+    fn centered_at_centers_the_shape_not_the_unit_box() {
+        let p = sf::Vector2f::new;
+        // Wider than tall, the normalized coords only fill the top of the unit box
+        let raw = RawPolygonCoords::from_sf_points(vec![p(10., 10.), p(210., 10.), p(210., 60.), p(10., 60.)]).normalized(false);
+        let mut polygon = Polygon::new();
+        polygon.set_points_from_raw(raw.centered_at(p(500., 500.)));
+
+        let bounds = polygon.bounds();
+        assert_eq!((bounds.left, bounds.top, bounds.width, bounds.height), (400., 475., 200., 50.));
+    }
+
+    #[test]
+    fn hull_follows_moved_points() {
+        let mut poly = PolygonObject::from(Polygon::create(square(100.)));
+        poly.show_hull = true;
+        poly.update_offset();
+
+        poly.set_points_pos(&[(2, sf::Vector2f::new(150., 150.))]);
+        poly.refresh_offset();
+        let hull = &poly.hull_polygon;
+        assert!((0..hull.points_count() as isize).any(|id| hull.get_point_pos(id) == sf::Vector2f::new(150., 150.)));
+    }
+
+    #[test]
+    fn bands_follow_edits() {
+        let mut poly = PolygonObject::from(Polygon::create(square(100.)));
+        poly.assert_ccw();
+        poly.show_bands = true;
+        poly.update_offset();
+        let area = poly.band_polygons[0].area();
+
+        let corner = (0..4).find(|&id| poly.polygon.get_point_pos(id as isize) == sf::Vector2f::new(100., 100.)).unwrap();
+        poly.set_points_pos(&[(corner, sf::Vector2f::new(150., 150.))]);
+        poly.refresh_offset();
+        assert!(poly.band_polygons[0].area() > area);
+
+        // Crossing the square over drops the stale bands
+        poly.set_points_pos(&[(corner, sf::Vector2f::new(-50., 50.))]);
+        poly.update_offset();
+        assert!(poly.band_polygons.is_empty());
+    }
+
+    #[test]
+    fn mirror_reflects_arcs() {
+        for (left_right, winding) in [
+            (true, MirrorWinding::KeepFirstVertex),
+            (false, MirrorWinding::KeepFirstVertex),
+            (false, MirrorWinding::Reverse),
+        ] {
+            // The top edge bulges up to (50, -50)
+            let mut poly = PolygonObject::from(arched_square());
+            poly.mirror(left_right, winding).unwrap();
+
+            let polygon = poly.polygon();
+            let arc = (0..polygon.points_count() as isize)
+                .find(|&id| polygon.get_edge_bulge(id) != 0.)
+                .unwrap();
+            let expected = if left_right { sf::Vector2f::new(50., -50.) } else { sf::Vector2f::new(50., 150.) };
+            assert!(my_math::distance(&polygon.edge_point(arc, 0.5), &expected) < 1.);
+        }
+    }
+
+    #[test]
+    fn mirror_keeps_edge_constraints() {
+        for (left_right, winding) in [
+            (true, MirrorWinding::KeepFirstVertex),
+            (true, MirrorWinding::Reverse),
+            (false, MirrorWinding::KeepFirstVertex),
+        ] {
+            let mut poly = PolygonObject::from(house());
+            poly.assert_ccw();
+            let first = poly.polygon().get_point_pos(0);
+            poly.mirror(left_right, winding).unwrap();
+
+            let polygon = poly.polygon();
+            let mut found = Vec::new();
+            for id in 0..polygon.points_count() as isize {
+                let (p0, p1) = (polygon.get_point_pos(id), polygon.get_point_pos(id + 1));
+                match polygon.get_edge_constraint(id) {
+                    EdgeConstraint::Horizontal => assert_eq!(p0.y, p1.y),
+                    EdgeConstraint::Vertical => assert_eq!(p0.x, p1.x),
+                    EdgeConstraint::FixedLength(length) => {
+                        assert!((my_math::distance(&p0, &p1) - length).abs() < 0.01)
+                    }
+                    EdgeConstraint::None => continue,
+                }
+                found.push(polygon.get_edge_constraint(id));
+            }
+            assert_eq!(found.len(), 3);
+
+            if winding == MirrorWinding::KeepFirstVertex {
+                // The mirror doesn't move the center
+                let center = polygon.find_center();
+                let expected = if left_right {
+                    sf::Vector2f::new(2. * center.x - first.x, first.y)
+                } else {
+                    sf::Vector2f::new(first.x, 2. * center.y - first.y)
+                };
+                assert!(my_math::distance(&polygon.get_point_pos(0), &expected) < 1e-3);
+            }
+        }
+    }
+
+    // Square with a thin needle sticking out of the right edge, the tip is the vertex 3
+    fn needle(tip: sf::Vector2f) -> Polygon<'static> {
+        let p = sf::Vector2f::new;
+        let mut polygon = Polygon::create(vec![
+            p(0., 0.), p(100., 0.), p(100., 40.), tip, p(100., 42.), p(100., 100.), p(0., 100.),
+        ]);
+        polygon.assert_ccw();
+        polygon
+    }
+
+    #[test]
+    fn find_spikes_finds_the_needle_tip() {
+        let polygon = needle(sf::Vector2f::new(300., 41.));
+        let tip = (0..polygon.points_count())
+            .find(|&i| polygon.get_point_pos(i as isize) == sf::Vector2f::new(300., 41.))
+            .unwrap();
+        assert_eq!(polygon.find_spikes(style::SPIKE_ANGLE), vec![tip]);
+
+        // A blunt one isn't a spike
+        let polygon = needle(sf::Vector2f::new(110., 41.));
+        assert!(polygon.find_spikes(style::SPIKE_ANGLE).is_empty());
+    }
+
+    #[test]
+    fn offset_vec_is_bounded_at_a_needle() {
+        let polygon = needle(sf::Vector2f::new(300., 41.));
+        for id in 0..polygon.points_count() as isize {
+            let len = my_math::vec_len(&polygon.get_offset_vec(id));
+            assert!(len.is_finite());
+            assert!(len <= style::MAX_MITER_RATIO + 1e-3);
+        }
+
+        // Folded back exactly, the bisector is undefined
+        let p = sf::Vector2f::new;
+        let folded = Polygon::create(vec![p(0., 0.), p(100., 0.), p(50., 0.), p(50., 50.)]);
+        assert!(my_math::vec_len(&folded.get_offset_vec(1)).is_finite());
+    }
+
+    #[test]
+    fn remove_spikes_removes_the_needle() {
+        let mut poly = PolygonObject::from(needle(sf::Vector2f::new(300., 41.)));
+        assert_eq!(poly.remove_spikes(style::SPIKE_ANGLE), Ok(1));
+        assert_eq!(poly.polygon().points_count(), 6);
+        assert!(poly.polygon().find_spikes(style::SPIKE_ANGLE).is_empty());
+    }
+
+    // Five-pointed star centered at the origin, the tips are the even vertices
+    fn star() -> PolygonObject<'static> {
+        let points = (0..10)
+            .map(|i| {
+                let radius = if i % 2 == 0 { 100. } else { 40. };
+                let angle = std::f32::consts::PI * i as f32 / 5.;
+                sf::Vector2f::new(radius * angle.cos(), radius * angle.sin())
+            })
+            .collect();
+        let mut poly = PolygonObject::from(Polygon::create(points));
+        poly.assert_ccw();
+        poly
+    }
+
+    #[test]
+    fn offset_join_styles_on_a_star() {
+        let size = 10.;
+        let mut poly = star();
+        for join_style in [JoinStyle::Miter, JoinStyle::Round, JoinStyle::Bevel] {
+            poly.join_style = join_style;
+            for id in 0..poly.polygon().points_count() as isize {
+                let pos = poly.polygon().get_point_pos(id);
+                let join = poly.offset_join(id, size);
+                let is_tip = my_math::vec_len(&pos) > 50.;
+
+                // The concave corners always get the single point where the offset edges cross
+                if !is_tip || join_style == JoinStyle::Miter {
+                    assert_eq!(join.len(), 1);
+                    let dist = my_math::distance(&join[0], &pos);
+                    assert!(dist >= size - 1e-3 && dist <= poly.miter_limit * size + 1e-3);
+                    assert!(my_math::vec_len(&join[0]) > my_math::vec_len(&pos));
+                    continue;
+                }
+
+                let expected_len = if join_style == JoinStyle::Bevel { 2 } else { 3 };
+                assert!(join.len() >= expected_len);
+                assert_eq!(join[0], pos + poly.polygon().get_prev_normal(id) * size);
+                assert!(my_math::distance(join.last().unwrap(), &(pos + poly.polygon().get_normal(id) * size)) < 1e-3);
+                for point in join.iter() {
+                    assert!((my_math::distance(point, &pos) - size).abs() < 1e-3);
+                }
+            }
+        }
+
+        // The round join lies between the miter and the bevel
+        let areas: Vec<f32> = [JoinStyle::Miter, JoinStyle::Round, JoinStyle::Bevel]
+            .iter()
+            .map(|join_style| {
+                poly.join_style = *join_style;
+                poly.build_offset(size).area().abs()
+            })
+            .collect();
+        assert!(areas[0] > areas[1] && areas[1] > areas[2]);
+    }
+}
@@ -0,0 +1,210 @@
+use super::sf;
+
+/// Everything that can be triggered from the keyboard or with a mouse gesture.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    ToggleShortcutsHelp,
+    SetGridOriginToCursor,
+    RotateSelection,
+    Cancel,
+    Undo,
+    Redo,
+    Copy,
+    Paste,
+
+    // Mouse gestures, handled directly in Application::handle_input
+    SelectOrDrag,
+    ToggleSelection,
+    SelectPolygon,
+    SelectPolygonByEdge,
+    AxisLockedDrag,
+    CopyDrag,
+    MarqueeSelect,
+    PlaceWithoutSnap,
+    ZoomView,
+    PanView,
+    ScrollOffset,
+}
+
+pub enum Trigger {
+    Key { code: sf::Key, ctrl: bool, shift: bool },
+    Mouse,
+}
+
+pub struct Shortcut {
+    pub trigger: Trigger,
+    pub action: Action,
+    pub keys: &'static str,
+    pub description: &'static str,
+}
+
+/// Single source of truth for the shortcuts, used both by the input handling and the help overlay.
+pub const SHORTCUTS: &[Shortcut] = &[
+    Shortcut {
+        trigger: Trigger::Key { code: sf::Key::F1, ctrl: false, shift: false },
+        action: Action::ToggleShortcutsHelp,
+        keys: "F1",
+        description: "Show/hide this help",
+    },
+    Shortcut {
+        trigger: Trigger::Key { code: sf::Key::Slash, ctrl: false, shift: true },
+        action: Action::ToggleShortcutsHelp,
+        keys: "?",
+        description: "Show/hide this help",
+    },
+    Shortcut {
+        trigger: Trigger::Key { code: sf::Key::O, ctrl: false, shift: true },
+        action: Action::SetGridOriginToCursor,
+        keys: "Shift + O",
+        description: "Move the grid origin to the cursor",
+    },
+    Shortcut {
+        trigger: Trigger::Key { code: sf::Key::R, ctrl: false, shift: false },
+        action: Action::RotateSelection,
+        keys: "R",
+        description: "Rotate the selection with the mouse, LM confirms (hold Shift to snap to 15°)",
+    },
+    Shortcut {
+        trigger: Trigger::Key { code: sf::Key::Escape, ctrl: false, shift: false },
+        action: Action::Cancel,
+        keys: "Esc",
+        description: "Cancel the current operation (same as the Cancel button)",
+    },
+    Shortcut {
+        trigger: Trigger::Key { code: sf::Key::Z, ctrl: true, shift: false },
+        action: Action::Undo,
+        keys: "Ctrl + Z",
+        description: "Undo the last edit",
+    },
+    Shortcut {
+        trigger: Trigger::Key { code: sf::Key::Y, ctrl: true, shift: false },
+        action: Action::Redo,
+        keys: "Ctrl + Y",
+        description: "Redo the last undone edit",
+    },
+    Shortcut {
+        trigger: Trigger::Key { code: sf::Key::C, ctrl: true, shift: false },
+        action: Action::Copy,
+        keys: "Ctrl + C",
+        description: "Copy the selected polygons",
+    },
+    Shortcut {
+        trigger: Trigger::Key { code: sf::Key::V, ctrl: true, shift: false },
+        action: Action::Paste,
+        keys: "Ctrl + V",
+        description: "Paste the copied polygons, slightly shifted",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::SelectOrDrag,
+        keys: "LM",
+        description: "Select and drag a point or an edge",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::ToggleSelection,
+        keys: "Ctrl + LM",
+        description: "Add/remove a point or an edge to/from the selection",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::SelectPolygon,
+        keys: "Ctrl + A + LM",
+        description: "Select all points of the hovered polygon",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::SelectPolygonByEdge,
+        keys: "Double click on an edge",
+        description: "Select the whole polygon (a single click selects just the edge)",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::AxisLockedDrag,
+        keys: "Shift + drag",
+        description: "Constrain dragging to the dominant axis",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::CopyDrag,
+        keys: "Alt + drag",
+        description: "Drag a copy of the selected polygons, the originals stay in place",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::MarqueeSelect,
+        keys: "LM drag on empty space",
+        description: "Select the points inside the rectangle (hold Ctrl to add to the selection)",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::PlaceWithoutSnap,
+        keys: "Alt + LM",
+        description: "Place a point of a new polygon without snapping to the polygon edges",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::ZoomView,
+        keys: "Scroll",
+        description: "Zoom the view at the cursor",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::PanView,
+        keys: "Middle mouse drag",
+        description: "Pan the view",
+    },
+    Shortcut {
+        trigger: Trigger::Mouse,
+        action: Action::ScrollOffset,
+        keys: "Shift + scroll",
+        description: "Change the offset of the selected polygon",
+    },
+];
+
+/// Finds the action bound to the given key combination.
+pub fn find_action(code: sf::Key, ctrl: bool, shift: bool) -> Option<Action> {
+    SHORTCUTS.iter().find_map(|shortcut| match shortcut.trigger {
+        Trigger::Key { code: c, ctrl: cl, shift: sh } if c == code && cl == ctrl && sh == shift => Some(shortcut.action),
+        _ => None,
+    })
+}
+
+/// Finds the action bound to the given key press. Nothing is triggered while egui wants the
+/// keyboard (egui::Context::wants_keyboard_input), the keys go to the focused text field then.
+pub fn find_key_action(code: sf::Key, ctrl: bool, shift: bool, egui_wants_keyboard: bool) -> Option<Action> {
+    if egui_wants_keyboard {
+        return None;
+    }
+    find_action(code, ctrl, shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui_sfml::egui;
+
+    // Runs an egui frame with a text field, focused if requested
+    fn wants_keyboard(focus: bool) -> bool {
+        let ctx = egui::Context::default();
+        let mut text = String::from("Polygon #0");
+        for _ in 0..2 {
+            let _output = ctx.run(egui::RawInput::default(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    let response = ui.text_edit_singleline(&mut text);
+                    if focus {
+                        response.request_focus();
+                    }
+                });
+            });
+        }
+        ctx.wants_keyboard_input()
+    }
+
+    #[test]
+    fn focused_text_field_swallows_shortcuts() {
+        assert_eq!(find_key_action(sf::Key::R, false, false, wants_keyboard(false)), Some(Action::RotateSelection));
+        assert_eq!(find_key_action(sf::Key::R, false, false, wants_keyboard(true)), None);
+        assert_eq!(find_key_action(sf::Key::Z, true, false, wants_keyboard(true)), None);
+    }
+}
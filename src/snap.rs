@@ -0,0 +1,110 @@
+use sfml::graphics::{CircleShape, ConvexShape, RenderTarget, Shape, Transformable};
+use super::sf;
+use super::style;
+use super::my_math;
+
+/// Ring drawn around the current snap target (first vertex, grid node, edge point...).
+/// All snapping features report their target through a single instance stored in the
+/// `AppContext`, so they look the same and never get confused with hover/selection circles.
+pub struct SnapIndicator<'a> {
+    ring: CircleShape<'a>,
+    visible: bool,
+    // Highlight of the edge the target lies on, if any
+    edge: ConvexShape<'a>,
+    edge_visible: bool,
+    // Diamond shown instead of the ring when the target is an edge midpoint
+    midpoint: ConvexShape<'a>,
+    midpoint_visible: bool,
+}
+
+impl<'a> SnapIndicator<'a> {
+    pub fn new() -> SnapIndicator<'a> {
+        let mut ring = CircleShape::new(style::SNAP_INDICATOR_RADIUS, 30);
+        ring.set_origin(sf::Vector2f::new(style::SNAP_INDICATOR_RADIUS, style::SNAP_INDICATOR_RADIUS));
+        ring.set_fill_color(sf::Color::TRANSPARENT);
+        ring.set_outline_color(style::SNAP_INDICATOR_COLOR);
+        ring.set_outline_thickness(style::SNAP_INDICATOR_THICKNESS);
+
+        let mut edge = ConvexShape::new(4);
+        edge.set_fill_color(style::SNAP_INDICATOR_COLOR);
+
+        let mut midpoint = ConvexShape::new(4);
+        midpoint.set_fill_color(sf::Color::TRANSPARENT);
+        midpoint.set_outline_color(style::SNAP_MIDPOINT_COLOR);
+        midpoint.set_outline_thickness(style::SNAP_INDICATOR_THICKNESS);
+        let size = style::SNAP_MIDPOINT_SIZE;
+        midpoint.set_point(0, sf::Vector2f::new(0., -size));
+        midpoint.set_point(1, sf::Vector2f::new(size, 0.));
+        midpoint.set_point(2, sf::Vector2f::new(0., size));
+        midpoint.set_point(3, sf::Vector2f::new(-size, 0.));
+
+        SnapIndicator {
+            ring,
+            visible: false,
+            edge,
+            edge_visible: false,
+            midpoint,
+            midpoint_visible: false,
+        }
+    }
+
+    pub fn set_color(&mut self, color: sf::Color) {
+        self.ring.set_outline_color(color);
+        self.edge.set_fill_color(color);
+    }
+
+    pub fn color(&self) -> sf::Color {
+        self.ring.outline_color()
+    }
+
+    /// Shows the indicator at the given snap target.
+    pub fn show(&mut self, pos: sf::Vector2f) {
+        self.ring.set_position(pos);
+        self.visible = true;
+    }
+
+    /// Highlights the edge the current snap target lies on.
+    pub fn show_edge(&mut self, p0: sf::Vector2f, p1: sf::Vector2f) {
+        let dir = my_math::vec_norm(&(p1 - p0));
+        let norm = sf::Vector2f::new(-dir.y, dir.x) * style::SNAP_EDGE_THICKNESS / 2.;
+        self.edge.set_point(0, p0 + norm);
+        self.edge.set_point(1, p1 + norm);
+        self.edge.set_point(2, p1 - norm);
+        self.edge.set_point(3, p0 - norm);
+        self.edge_visible = true;
+    }
+
+    /// Shows the midpoint marker at the given snap target.
+    pub fn show_midpoint(&mut self, pos: sf::Vector2f) {
+        self.midpoint.set_position(pos);
+        self.midpoint_visible = true;
+    }
+
+    pub fn hide(&mut self) {
+        self.visible = false;
+        self.edge_visible = false;
+        self.midpoint_visible = false;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible || self.midpoint_visible
+    }
+
+    pub fn draw(&self, target: &mut dyn RenderTarget) {
+        if self.edge_visible {
+            target.draw(&self.edge);
+        }
+        if self.visible {
+            target.draw(&self.ring);
+        }
+        if self.midpoint_visible {
+            target.draw(&self.midpoint);
+        }
+    }
+}
+
+impl<'a> Default for SnapIndicator<'a> {
+    fn default() -> SnapIndicator<'a> {
+        SnapIndicator::new()
+    }
+}
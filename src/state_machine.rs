@@ -1,17 +1,28 @@
-use std::io;
-use super::{sf, style};
+use super::{my_math, sf, style};
 use super::app::AppContext;
+use super::history::Change;
+use super::polygon::{PolygonError, RawPolygonCoords};
 
 pub trait State {
     fn on_left_mouse_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State>;
+    // The second click of a double click, it's called instead of on_left_mouse_clicked
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State>;
     fn on_left_mouse_released(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State>;
     fn on_ctrl_left_mouse_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State>;
     fn on_ctrl_a_left_mouse_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State>;
+    fn on_rotate_key(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State>;
     fn on_add_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State>;
     fn on_edit_points_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State>;
     fn on_cancel_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State>;
     fn update(&mut self, dt: f32, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext);
+    // Called for every mouse move event with its exact position, before the frame's update
+    fn on_mouse_moved(&mut self, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext);
     fn state_name(&self) -> &'static str;
+    // Whether an edit made outside of the state (the panels, undo, the commands) took away
+    // what it works on. The state is cancelled then.
+    fn is_stale(&self, _app_ctx: &AppContext) -> bool {
+        false
+    }
 }
 
 pub struct IdleState;
@@ -58,10 +69,22 @@ impl SelectionState {
 pub struct DraggingState {
     prev_mouse_point: sf::Vector2f,
     start_mouse_point: sf::Vector2f,
+    // Selection is made after the state is created, so the ghosts are captured on the first update
+    ghosts_captured: bool,
+    // Offset from the cursor to the grabbed point, this point snaps to the grid
+    anchor_offset: Option<sf::Vector2f>,
+    // Alt was held when the drag started, the copies of the selected polygons are dragged
+    copy_on_start: bool,
+    // Originals of the dragged copies with their selected points
+    copied_from: Vec<(usize, Vec<usize>)>,
 }
 
 impl DraggingState {
     pub fn new(mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> DraggingState {
+        // Grouped polygons are always moved together
+        app_ctx.expand_selection_to_groups();
+        app_ctx.checkpoint();
+
         for poly in app_ctx.polygon_objs.iter_mut() {
             poly.disable_hover_show();
             poly.set_point_hover_color(style::POINTS_COLOR);
@@ -70,10 +93,204 @@ impl DraggingState {
         DraggingState {
             prev_mouse_point: mouse_pos,
             start_mouse_point: mouse_pos,
+            ghosts_captured: false,
+            anchor_offset: None,
+            copy_on_start: app_ctx.modifiers.alt,
+            copied_from: Vec::new(),
+        }
+    }
+
+    // Appends a copy of every polygon with selected points, the copies take over the selection
+    fn copy_selected(&mut self, app_ctx: &mut AppContext) {
+        for id in 0..app_ctx.polygon_objs.len() {
+            let selected: Vec<usize> = app_ctx.polygon_objs[id]
+                .selected_points()
+                .iter()
+                .map(|(point_id, _)| *point_id)
+                .collect();
+            if selected.is_empty() {
+                continue;
+            }
+
+            let mut raw = app_ctx.polygon_objs[id].get_raw();
+            raw.name = None;
+            raw.group = None;
+            let mut copy = app_ctx.polygon_obj_factory.build_from_raw(raw);
+            copy.disable_hover_show();
+            copy.select_all_points();
+            app_ctx.polygon_objs[id].deselect_all_points();
+            app_ctx.polygon_objs.push(copy);
+            self.copied_from.push((id, selected));
+        }
+    }
+
+    // Removes the copies of a drag that didn't move anything, the originals get their selection back
+    fn drop_copies(&self, app_ctx: &mut AppContext) {
+        let len = app_ctx.polygon_objs.len() - self.copied_from.len();
+        app_ctx.polygon_objs.truncate(len);
+        for (id, selected) in self.copied_from.iter() {
+            for point_id in selected.iter() {
+                app_ctx.polygon_objs[*id].select_point(*point_id as isize);
+            }
+        }
+    }
+}
+
+
+/// Rubber-band selection, started by dragging on the empty canvas.
+pub struct MarqueeState {
+    start: sf::Vector2f,
+    end: sf::Vector2f,
+}
+
+impl MarqueeState {
+    /// Unless "additive" is set, the current selection is replaced.
+    pub fn new(mouse_pos: sf::Vector2f, additive: bool, app_ctx: &mut AppContext) -> MarqueeState {
+        for poly in app_ctx.polygon_objs.iter_mut() {
+            if !additive {
+                poly.deselect_all_points();
+            }
+            poly.disable_hover_show();
+        }
+
+        MarqueeState {
+            start: mouse_pos,
+            end: mouse_pos,
+        }
+    }
+
+    // The rectangle never extends under the docked panels or outside of the window
+    fn clip_to_canvas(pos: sf::Vector2f, app_ctx: &AppContext) -> sf::Vector2f {
+        let canvas = app_ctx.canvas;
+        sf::Vector2f::new(
+            pos.x.clamp(canvas.left, canvas.left + canvas.width.max(0.)),
+            pos.y.clamp(canvas.top, canvas.top + canvas.height.max(0.)),
+        )
+    }
+
+    fn finish(app_ctx: &mut AppContext) -> Box<dyn State> {
+        if app_ctx.polygon_objs.iter().any(|poly| poly.selected_points_count() > 0) {
+            Box::new(SelectionState::new(app_ctx))
+        } else {
+            Box::new(IdleState::new(app_ctx))
         }
     }
 }
 
+pub struct RotatingState {
+    pivot: sf::Vector2f,
+    start_angle: f32,
+    // Selected points of every polygon at the beginning of the rotation
+    originals: Vec<Vec<(usize, sf::Vector2f)>>,
+}
+
+impl RotatingState {
+    pub fn new(mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> RotatingState {
+        // Grouped polygons are always rotated together
+        app_ctx.expand_selection_to_groups();
+        app_ctx.checkpoint();
+
+        let originals: Vec<Vec<(usize, sf::Vector2f)>> = app_ctx.polygon_objs
+            .iter()
+            .map(|poly| poly.selected_points())
+            .collect();
+
+        // A single group turns around its shared pivot, anything else around the center of
+        // the selection
+        let mut selected_groups = app_ctx.polygon_objs
+            .iter()
+            .filter(|poly| poly.selected_points_count() > 0)
+            .map(|poly| poly.group_id());
+        let first_group = selected_groups.next().flatten();
+        let group = first_group.filter(|_| selected_groups.all(|group| group == first_group));
+
+        let mut pivot = sf::Vector2f::new(0., 0.);
+        let mut count = 0;
+        for (_, pos) in originals.iter().flatten() {
+            pivot += *pos;
+            count += 1;
+        }
+        if count > 0 {
+            pivot /= count as f32;
+        }
+        if let Some(group_pivot) = group.and_then(|group| app_ctx.group_pivot(group)) {
+            pivot = group_pivot;
+        }
+
+        for poly in app_ctx.polygon_objs.iter_mut() {
+            poly.disable_hover_show();
+        }
+
+        let d = mouse_pos - pivot;
+        RotatingState {
+            pivot,
+            start_angle: d.y.atan2(d.x),
+            originals,
+        }
+    }
+
+    fn revert(&self, app_ctx: &mut AppContext) {
+        for (poly, originals) in app_ctx.polygon_objs.iter_mut().zip(self.originals.iter()) {
+            poly.set_points_pos(originals);
+        }
+    }
+}
+
+pub struct StampState {
+    template: RawPolygonCoords,
+}
+
+impl StampState {
+    pub fn new(template: RawPolygonCoords, app_ctx: &mut AppContext) -> StampState {
+        for poly in app_ctx.polygon_objs.iter_mut() {
+            poly.deselect_all_points();
+            poly.disable_hover_show();
+        }
+
+        StampState {
+            template,
+        }
+    }
+}
+
+/// Appends points to an existing polygon, clicking its first point closes it again.
+pub struct ResumePolygonState {
+    target: usize,
+    target_uid: usize,
+    // Polygons count when resuming, the index is stale once the list changes
+    polygons_count: usize,
+    // Points count before resuming, cancel falls back to it if the chain can't be closed
+    original_count: usize,
+}
+
+impl ResumePolygonState {
+    pub fn new(target: usize, app_ctx: &mut AppContext) -> ResumePolygonState {
+        for poly in app_ctx.polygon_objs.iter_mut() {
+            poly.deselect_all_points();
+            poly.disable_hover_show();
+        }
+
+        let polygons_count = app_ctx.polygon_objs.len();
+        let poly = &mut app_ctx.polygon_objs[target];
+        let original_count = poly.polygon().points_count();
+        app_ctx.history.push(Change::Polygon(target, poly.snapshot()));
+        poly.begin_append();
+
+        ResumePolygonState {
+            target,
+            target_uid: poly.uid(),
+            polygons_count,
+            original_count,
+        }
+    }
+
+    // Index of the resumed polygon, None if the polygon list has changed since
+    fn target(&self, app_ctx: &AppContext) -> Option<usize> {
+        let unchanged = app_ctx.polygon_objs.len() == self.polygons_count &&
+            app_ctx.polygon_objs[self.target].uid() == self.target_uid;
+        unchanged.then_some(self.target)
+    }
+}
 
 pub struct EditPointsState;
 
@@ -90,14 +307,29 @@ impl EditPointsState {
 
 impl State for AddPolygonState {
     fn on_left_mouse_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        let mouse_pos = app_ctx.grid.apply_snap(mouse_pos);
+        let mouse_pos = app_ctx.snap_to_polygon_edges(mouse_pos);
         let poly_opt = app_ctx.polygon_obj_factory.add_or_build(mouse_pos);
+        let split_off = app_ctx.polygon_obj_factory.take_split_polygons();
+        // One undo step per click
+        if !split_off.is_empty() || poly_opt.is_some() {
+            app_ctx.checkpoint();
+        }
+        app_ctx.polygon_objs.extend(split_off);
         if let Some(poly) = poly_opt {
             app_ctx.polygon_objs.push(poly);
             return Box::new(IdleState::new(app_ctx));
         }
+        if let Some(reason) = app_ctx.polygon_obj_factory.take_rejection() {
+            app_ctx.toasts.push(reason);
+        }
         self
     }
 
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        self.on_left_mouse_clicked(mouse_pos, app_ctx)
+    }
+
     fn on_left_mouse_released(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
         self
     }
@@ -116,17 +348,35 @@ impl State for AddPolygonState {
 
     fn on_edit_points_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State> {
         app_ctx.polygon_obj_factory.clear();
-        println!("AddPolygon -> EditPopints");
+        log::debug!("AddPolygon -> EditPopints");
         Box::new(EditPointsState::new(app_ctx))
     }
 
+    fn on_rotate_key(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
     fn on_cancel_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State> {
         app_ctx.polygon_obj_factory.clear();
         Box::new(IdleState::new(app_ctx))
     }
 
+    fn on_mouse_moved(&mut self, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) {}
+
     fn update(&mut self, dt: f32, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
+        // Grid snapping goes first, so the magnet to the first point still wins
+        let mouse_pos = app_ctx.grid.apply_snap(mouse_pos);
+        if app_ctx.grid.snap_enabled {
+            app_ctx.snap_indicator.show(mouse_pos);
+        }
+        // The closing vertex can land on a neighbor's edge, so the regions share the boundary
+        let mouse_pos = app_ctx.snap_to_polygon_edges(mouse_pos);
         app_ctx.polygon_obj_factory.update(dt, mouse_pos);
+        if let Some(pos) = app_ctx.polygon_obj_factory.snap_target() {
+            app_ctx.snap_indicator.hide();
+            app_ctx.snap_indicator.show(pos);
+        }
+        app_ctx.hud = Some(app_ctx.polygon_obj_factory.segment_readout(mouse_pos));
     }
 
     fn state_name(&self) -> &'static str {
@@ -152,8 +402,24 @@ impl IdleState {
     }
 }
 
+// A single click on an edge selects the edge, a double click selects the whole polygon.
+// The polygon can be dragged right away, like after the single click.
+fn select_polygon_by_hovered_edge(mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Option<Box<dyn State>> {
+    let id = app_ctx.polygon_objs
+        .iter()
+        .position(|poly| poly.is_line_hovered() && !poly.is_point_hovered())?;
+    for poly in app_ctx.polygon_objs.iter_mut() {
+        poly.deselect_all_points();
+    }
+    app_ctx.polygon_objs[id].select_all_points();
+    Some(Box::new(DraggingState::new(mouse_pos, app_ctx)))
+}
+
 impl State for IdleState {
     fn on_left_mouse_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        if !app_ctx.polygon_objs.iter().any(|poly| poly.is_point_hovered() || poly.is_line_hovered()) {
+            return Box::new(MarqueeState::new(mouse_pos, false, app_ctx));
+        }
         let result = Box::new(DraggingState::new(mouse_pos, app_ctx));
         self.select_points_and_return_state(
             mouse_pos,
@@ -162,11 +428,21 @@ impl State for IdleState {
         )
     }
 
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        match select_polygon_by_hovered_edge(mouse_pos, app_ctx) {
+            Some(state) => state,
+            None => self.on_left_mouse_clicked(mouse_pos, app_ctx),
+        }
+    }
+
     fn on_left_mouse_released(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
         self
     }
 
     fn on_ctrl_left_mouse_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        if !app_ctx.polygon_objs.iter().any(|poly| poly.is_point_hovered() || poly.is_line_hovered()) {
+            return Box::new(MarqueeState::new(mouse_pos, true, app_ctx));
+        }
         let result = Box::new(SelectionState::new(app_ctx));
         self.select_points_and_return_state(
             mouse_pos,
@@ -193,14 +469,25 @@ impl State for IdleState {
         Box::new(EditPointsState::new(app_ctx))
     }
 
+    fn on_rotate_key(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
     fn on_cancel_btn(self: Box<Self>, _app_ctx: &mut AppContext) -> Box<dyn State> {
         self
     }
 
+    fn on_mouse_moved(&mut self, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
+        for poly in app_ctx.polygon_objs.iter_mut() {
+            poly.update_hover(mouse_pos);
+        }
+    }
+
     fn update(&mut self, dt: f32, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
         for poly in app_ctx.polygon_objs.iter_mut() {
             poly.update_hover(mouse_pos);
         }
+        app_ctx.hud = app_ctx.polygon_objs.iter().find_map(|poly| poly.hovered_edge_info());
     }
 
     fn state_name(&self) -> &'static str {
@@ -235,17 +522,21 @@ impl State for SelectionState {
             }
         }
 
-        for poly in app_ctx.polygon_objs.iter_mut() {
-            poly.deselect_all_points();
+        Box::new(MarqueeState::new(mouse_pos, false, app_ctx))
+    }
+
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        match select_polygon_by_hovered_edge(mouse_pos, app_ctx) {
+            Some(state) => state,
+            None => self.on_left_mouse_clicked(mouse_pos, app_ctx),
         }
-        return Box::new(IdleState::new(app_ctx));
     }
 
     fn on_left_mouse_released(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
         self
     }
 
-    fn on_ctrl_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+    fn on_ctrl_left_mouse_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
         let mut nothing_hovered = true;
 
         for poly in app_ctx.polygon_objs.iter_mut() {
@@ -280,10 +571,7 @@ impl State for SelectionState {
         }
 
         if nothing_hovered {
-            for poly in app_ctx.polygon_objs.iter_mut() {
-                poly.deselect_all_points();
-            }
-            return Box::new(IdleState::new(app_ctx));
+            return Box::new(MarqueeState::new(mouse_pos, true, app_ctx));
         }
 
         self
@@ -323,10 +611,20 @@ impl State for SelectionState {
         return Box::new(EditPointsState::new(app_ctx));
     }
 
+    fn on_rotate_key(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        Box::new(RotatingState::new(mouse_pos, app_ctx))
+    }
+
     fn on_cancel_btn(self: Box<Self>, _app_ctx: &mut AppContext) -> Box<dyn State> {
         self
     }
 
+    fn on_mouse_moved(&mut self, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
+        for poly in app_ctx.polygon_objs.iter_mut() {
+            poly.update_hover(mouse_pos);
+        }
+    }
+
     fn update(&mut self, _dt: f32, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
         for poly in app_ctx.polygon_objs.iter_mut() {
             poly.update_hover(mouse_pos);
@@ -338,19 +636,109 @@ impl State for SelectionState {
     }
 }
 
-impl State for DraggingState {
+impl State for MarqueeState {
     fn on_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
         self
     }
 
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        self.on_left_mouse_clicked(mouse_pos, app_ctx)
+    }
+
     fn on_left_mouse_released(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        let end = Self::clip_to_canvas(mouse_pos, app_ctx);
+        let min = sf::Vector2f::new(self.start.x.min(end.x), self.start.y.min(end.y));
+        let max = sf::Vector2f::new(self.start.x.max(end.x), self.start.y.max(end.y));
+
+        // The points hidden under the floating windows are skipped as well
+        let mut picked: Vec<(usize, isize)> = Vec::new();
+        for (poly_id, poly) in app_ctx.polygon_objs.iter().enumerate() {
+            for id in 0..poly.polygon().points_count() as isize {
+                let pos = poly.polygon().get_point_pos(id);
+                if pos.x >= min.x && pos.x <= max.x && pos.y >= min.y && pos.y <= max.y && app_ctx.is_on_canvas(pos) {
+                    picked.push((poly_id, id));
+                }
+            }
+        }
+        for (poly_id, id) in picked {
+            app_ctx.polygon_objs[poly_id].select_point(id);
+        }
+
+        Self::finish(app_ctx)
+    }
+
+    fn on_ctrl_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_ctrl_a_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_add_btn(self: Box<Self>, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_edit_points_btn(self: Box<Self>, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_rotate_key(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_cancel_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State> {
+        Self::finish(app_ctx)
+    }
+
+    fn on_mouse_moved(&mut self, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) {}
+
+    fn update(&mut self, _dt: f32, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
+        self.end = Self::clip_to_canvas(mouse_pos, app_ctx);
+        app_ctx.marquee = Some((self.start, self.end));
+    }
+
+    fn state_name(&self) -> &'static str {
+        "Marquee State"
+    }
+}
+
+impl State for DraggingState {
+    fn on_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        self.on_left_mouse_clicked(mouse_pos, app_ctx)
+    }
+
+    fn on_left_mouse_released(self: Box<Self>, _mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
         for poly in app_ctx.polygon_objs.iter_mut() {
             if poly.polygon().is_self_crossing() {
-                // Revert changes
-                poly.move_selected_points(self.start_mouse_point - mouse_pos);
+                // Revert changes, prev_mouse_point is where the points have actually been moved
+                poly.move_selected_points(self.start_mouse_point - self.prev_mouse_point);
             } else {
                 poly.assert_ccw();
             }
+            poly.clear_ghost();
+        }
+        if self.start_mouse_point == self.prev_mouse_point {
+            // Nothing has been moved
+            self.drop_copies(app_ctx);
+            app_ctx.history.discard_last();
+        } else {
+            // The dragged points stay selected, so they are never merged
+            for id in 0..app_ctx.polygon_objs.len() {
+                if app_ctx.polygon_objs[id].selected_points_count() > 0 {
+                    app_ctx.merge_collinear_edges(id);
+                }
+            }
+            if app_ctx.insert_shared_vertices {
+                let inserted = app_ctx.insert_shared_vertices();
+                if inserted > 0 {
+                    app_ctx.toasts.push(format!("Inserted {} shared vertex(es)", inserted));
+                }
+            }
         }
         Box::new(SelectionState::new(app_ctx))
     }
@@ -371,15 +759,65 @@ impl State for DraggingState {
         self
     }
 
+    fn on_rotate_key(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
     fn on_cancel_btn(self: Box<Self>, _app_ctx: &mut AppContext) -> Box<dyn State> {
         self
     }
 
+    fn on_mouse_moved(&mut self, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) {}
+
     fn update(&mut self, _dt: f32, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
+        let mut target = mouse_pos;
+        if app_ctx.modifiers.shift {
+            // Lock the movement to the dominant axis of the whole drag
+            let drag = mouse_pos - self.start_mouse_point;
+            if drag.x.abs() >= drag.y.abs() {
+                target.y = self.start_mouse_point.y;
+            } else {
+                target.x = self.start_mouse_point.x;
+            }
+        }
+
+        if !self.ghosts_captured {
+            self.ghosts_captured = true;
+            if self.copy_on_start {
+                self.copy_selected(app_ctx);
+            }
+            if app_ctx.show_drag_ghost {
+                for poly in app_ctx.polygon_objs.iter_mut() {
+                    if poly.selected_points_count() > 0 {
+                        poly.capture_ghost();
+                    }
+                }
+            }
+
+            // The selected point closest to where the drag started is the grabbed one
+            let start = self.start_mouse_point;
+            self.anchor_offset = app_ctx.polygon_objs
+                .iter()
+                .flat_map(|poly| poly.selected_points())
+                .map(|(_, pos)| pos)
+                .min_by(|a, b| my_math::distance2(a, &start).partial_cmp(&my_math::distance2(b, &start)).unwrap())
+                .map(|pos| pos - start);
+        }
+
+        if let Some(mid) = self.anchor_offset.and_then(|offset| app_ctx.snap_to_midpoints(target + offset)) {
+            // Edge midpoints are more specific targets than the grid nodes
+            target = mid - self.anchor_offset.unwrap();
+        } else if let (true, Some(offset)) = (app_ctx.grid.snap_enabled, self.anchor_offset) {
+            // Only the movement is snapped, so the constraints are kept by move_selected_points
+            let anchor = app_ctx.grid.snap(target + offset);
+            app_ctx.snap_indicator.show(anchor);
+            target = anchor - offset;
+        }
+
         for poly in app_ctx.polygon_objs.iter_mut() {
-            poly.move_selected_points(mouse_pos - self.prev_mouse_point);
+            poly.move_selected_points(target - self.prev_mouse_point);
         }
-        self.prev_mouse_point = mouse_pos;
+        self.prev_mouse_point = target;
     }
 
     fn state_name(&self) -> &'static str {
@@ -387,22 +825,183 @@ impl State for DraggingState {
     }
 }
 
+impl State for StampState {
+    fn on_left_mouse_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        // Keep the template's size, center its bounding box at the cursor
+        let raw = self.template.clone().centered_at(mouse_pos);
+
+        let poly = app_ctx.polygon_obj_factory.build_from_raw(raw);
+        app_ctx.checkpoint();
+        app_ctx.polygon_objs.push(poly);
+        Box::new(IdleState::new(app_ctx))
+    }
+
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        self.on_left_mouse_clicked(mouse_pos, app_ctx)
+    }
+
+    fn on_left_mouse_released(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_ctrl_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_ctrl_a_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_add_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State> {
+        Box::new(AddPolygonState::new(app_ctx))
+    }
+
+    fn on_edit_points_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State> {
+        Box::new(EditPointsState::new(app_ctx))
+    }
+
+    fn on_rotate_key(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_cancel_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State> {
+        Box::new(IdleState::new(app_ctx))
+    }
+
+    fn on_mouse_moved(&mut self, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) {}
+
+    fn update(&mut self, _dt: f32, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) {}
+
+    fn state_name(&self) -> &'static str {
+        "Stamp State"
+    }
+}
+
+impl State for ResumePolygonState {
+    fn on_left_mouse_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        let min_area = app_ctx.polygon_obj_factory.min_area();
+        let poly = match self.target(app_ctx) {
+            Some(target) => &mut app_ctx.polygon_objs[target],
+            None => return self.on_cancel_btn(app_ctx),
+        };
+
+        let polygon = poly.polygon();
+        let first = polygon.get_point_pos(0);
+        if polygon.points_count() >= 3 && my_math::distance(&first, &mouse_pos) <= style::POINT_DETECTION_RADIUS {
+            return match poly.finish_append(min_area) {
+                Ok(()) => Box::new(IdleState::new(app_ctx)),
+                Err(err) => {
+                    app_ctx.toasts.push(format!("Can't close the polygon: {}", err));
+                    self
+                }
+            };
+        }
+
+        if let Err(err) = poly.append_point(app_ctx.grid.apply_snap(mouse_pos)) {
+            app_ctx.toasts.push(format!("Can't add the point: {}", err));
+        }
+        self
+    }
+
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        self.on_left_mouse_clicked(mouse_pos, app_ctx)
+    }
+
+    fn on_left_mouse_released(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_ctrl_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_ctrl_a_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_rotate_key(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_add_btn(self: Box<Self>, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_edit_points_btn(self: Box<Self>, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_cancel_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State> {
+        let min_area = app_ctx.polygon_obj_factory.min_area();
+        let unchanged = self.target(app_ctx).is_some();
+        // The polygon may have moved or be gone if the list has changed
+        if let Some(target) = app_ctx.find_polygon(self.target_uid) {
+            let poly = &mut app_ctx.polygon_objs[target];
+            // Keep the appended points if possible, otherwise drop them
+            if poly.finish_append(min_area).is_err() {
+                poly.truncate_points(self.original_count);
+                let _err = poly.finish_append(0.);
+                // Later edits are on top of the resume entry once the list has changed
+                if unchanged {
+                    app_ctx.history.discard_last();
+                }
+            }
+        }
+        Box::new(IdleState::new(app_ctx))
+    }
+
+    fn on_mouse_moved(&mut self, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) {}
+
+    fn update(&mut self, _dt: f32, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
+        if let Some(target) = self.target(app_ctx) {
+            let poly = &app_ctx.polygon_objs[target];
+            let first = poly.polygon().get_point_pos(0);
+            if my_math::distance(&first, &mouse_pos) <= style::POINT_DETECTION_RADIUS {
+                app_ctx.snap_indicator.show(first);
+                app_ctx.hud = Some(String::from("Close the polygon"));
+            } else {
+                app_ctx.hud = Some(String::from("Click to append a point"));
+            }
+        }
+    }
+
+    fn state_name(&self) -> &'static str {
+        "Resume Polygon State"
+    }
+
+    fn is_stale(&self, app_ctx: &AppContext) -> bool {
+        self.target(app_ctx).is_none()
+    }
+}
+
 impl State for EditPointsState {
     fn on_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
-        for poly in app_ctx.polygon_objs.iter_mut() {
+        for (id, poly) in app_ctx.polygon_objs.iter_mut().enumerate() {
             if poly.is_point_hovered() {
-                let err = poly.remove_point(poly.get_hovered_point_id() as isize);
-                if let Err(e) = err {
+                let before = poly.snapshot();
+                match poly.remove_point(poly.get_hovered_point_id() as isize) {
                     // Ignore if polygon is simplex
-                    if e.kind() == io::ErrorKind::InvalidData {
-                        continue;
+                    Err(PolygonError::TooFewPoints) => continue,
+                    Err(err) => {
+                        app_ctx.toasts.push(format!("Can't remove the point: {}", err));
+                        return self;
                     }
+                    Ok(()) => (),
                 }
+                app_ctx.history.push(Change::Polygon(id, before));
+                app_ctx.merge_collinear_edges(id);
                 return Box::new(IdleState::new(app_ctx));
             } else if poly.is_line_hovered() {
                 if poly.can_insert() {
+                    let before = poly.snapshot();
                     let line = poly.get_hovered_line_ids();
-                    let _err = poly.insert_point(line.1 as isize, poly.get_insert_pos());
+                    match poly.insert_point(line.1 as isize, poly.get_insert_pos()) {
+                        Ok(()) => app_ctx.history.push(Change::Polygon(id, before)),
+                        Err(err) => {
+                            app_ctx.toasts.push(format!("Can't insert the point: {}", err));
+                            return self;
+                        }
+                    }
                     return Box::new(IdleState::new(app_ctx));
                 }
             }
@@ -410,6 +1009,10 @@ impl State for EditPointsState {
         self
     }
 
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        self.on_left_mouse_clicked(mouse_pos, app_ctx)
+    }
+
     fn on_left_mouse_released(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
         self
     }
@@ -430,16 +1033,230 @@ impl State for EditPointsState {
         self
     }
 
+    fn on_rotate_key(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
     fn on_cancel_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State> {
         Box::new(IdleState::new(app_ctx))
     }
 
+    fn on_mouse_moved(&mut self, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
+        for poly in app_ctx.polygon_objs.iter_mut() {
+            poly.update_insertion(mouse_pos);
+            poly.update_hover(mouse_pos);
+        }
+    }
+
     fn update(&mut self, _dt: f32, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
         for poly in app_ctx.polygon_objs.iter_mut() {
             poly.update_insertion(mouse_pos);
             poly.update_hover(mouse_pos);
         }
+        app_ctx.hud = app_ctx.polygon_objs.iter().find_map(|poly| poly.hovered_edge_info());
     }
 
     fn state_name(&self) -> &'static str { "Edit Point State" }
-}
\ No newline at end of file
+}
+
+impl State for RotatingState {
+    fn on_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        for (poly, originals) in app_ctx.polygon_objs.iter_mut().zip(self.originals.iter()) {
+            if originals.is_empty() {
+                continue;
+            }
+            if poly.polygon().is_self_crossing() {
+                poly.set_points_pos(originals);
+            }
+            poly.finish_transform();
+        }
+        Box::new(SelectionState::new(app_ctx))
+    }
+
+    fn on_left_mouse_double_clicked(self: Box<Self>, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) -> Box<dyn State> {
+        self.on_left_mouse_clicked(mouse_pos, app_ctx)
+    }
+
+    fn on_left_mouse_released(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_ctrl_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_ctrl_a_left_mouse_clicked(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_rotate_key(self: Box<Self>, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_add_btn(self: Box<Self>, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_edit_points_btn(self: Box<Self>, _app_ctx: &mut AppContext) -> Box<dyn State> {
+        self
+    }
+
+    fn on_cancel_btn(self: Box<Self>, app_ctx: &mut AppContext) -> Box<dyn State> {
+        self.revert(app_ctx);
+        app_ctx.history.discard_last();
+        Box::new(SelectionState::new(app_ctx))
+    }
+
+    fn on_mouse_moved(&mut self, _mouse_pos: sf::Vector2f, _app_ctx: &mut AppContext) {}
+
+    fn update(&mut self, _dt: f32, mouse_pos: sf::Vector2f, app_ctx: &mut AppContext) {
+        let d = mouse_pos - self.pivot;
+        let mut angle = (d.y.atan2(d.x) - self.start_angle).to_degrees();
+
+        // Shift snaps the angle to fixed increments
+        if app_ctx.modifiers.shift {
+            angle = (angle / style::ROTATION_SNAP_STEP).round() * style::ROTATION_SNAP_STEP;
+        }
+
+        for (poly, originals) in app_ctx.polygon_objs.iter_mut().zip(self.originals.iter()) {
+            if originals.is_empty() {
+                continue;
+            }
+            let rotated: Vec<(usize, sf::Vector2f)> = originals
+                .iter()
+                .map(|(id, pos)| (*id, my_math::rotate_around(pos, &self.pivot, angle.to_radians())))
+                .collect();
+            poly.set_points_pos(&rotated);
+        }
+
+        // Normalize to (-180, 180]
+        let mut shown = angle % 360.;
+        if shown > 180. {
+            shown -= 360.;
+        } else if shown <= -180. {
+            shown += 360.;
+        }
+        app_ctx.hud = Some(format!("{:.1}°", shown));
+    }
+
+    fn state_name(&self) -> &'static str {
+        "Rotating State"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polygon::{Polygon, PolygonObject};
+
+    // An app context with a single 100x100 square whose bottom edge is hovered
+    fn hovered_square_edge() -> (AppContext<'static>, sf::Vector2f) {
+        let p = sf::Vector2f::new;
+        let mut app_ctx = AppContext::new(800, 600);
+        let mut poly = PolygonObject::from(Polygon::create(vec![p(0., 0.), p(100., 0.), p(100., 100.), p(0., 100.)]));
+        poly.assert_ccw();
+        app_ctx.polygon_objs.push(poly);
+
+        let mouse_pos = p(50., 1.);
+        let mut state: Box<dyn State> = Box::new(IdleState);
+        state.on_mouse_moved(mouse_pos, &mut app_ctx);
+        assert!(app_ctx.polygon_objs[0].is_line_hovered());
+        (app_ctx, mouse_pos)
+    }
+
+    #[test]
+    fn click_on_edge_selects_the_edge() {
+        let (mut app_ctx, mouse_pos) = hovered_square_edge();
+        let state = Box::new(IdleState).on_left_mouse_clicked(mouse_pos, &mut app_ctx);
+        assert_eq!(state.state_name(), "Dragging State");
+        assert_eq!(app_ctx.polygon_objs[0].selected_points_count(), 2);
+    }
+
+    #[test]
+    fn double_click_on_edge_selects_the_polygon() {
+        let (mut app_ctx, mouse_pos) = hovered_square_edge();
+        let state = Box::new(IdleState).on_left_mouse_clicked(mouse_pos, &mut app_ctx);
+        let state = state.on_left_mouse_released(mouse_pos, &mut app_ctx);
+        let state = state.on_left_mouse_double_clicked(mouse_pos, &mut app_ctx);
+        assert_eq!(state.state_name(), "Dragging State");
+        assert_eq!(app_ctx.polygon_objs[0].selected_points_count(), 4);
+    }
+
+    #[test]
+    fn double_click_away_from_polygons_starts_a_marquee() {
+        let (mut app_ctx, _) = hovered_square_edge();
+        let far = sf::Vector2f::new(400., 400.);
+        let mut state: Box<dyn State> = Box::new(IdleState);
+        state.on_mouse_moved(far, &mut app_ctx);
+        let state = state.on_left_mouse_double_clicked(far, &mut app_ctx);
+        assert_eq!(state.state_name(), "Marquee State");
+        assert_eq!(app_ctx.polygon_objs[0].selected_points_count(), 0);
+    }
+
+    fn drag_marquee(app_ctx: &mut AppContext, start: sf::Vector2f, end: sf::Vector2f) -> usize {
+        let mut state: Box<dyn State> = Box::new(MarqueeState::new(start, false, app_ctx));
+        state.update(0., end, app_ctx);
+        state.on_left_mouse_released(end, app_ctx);
+        app_ctx.polygon_objs[0].selected_points_count()
+    }
+
+    #[test]
+    fn marquee_is_clipped_to_the_canvas() {
+        let (mut app_ctx, _) = hovered_square_edge();
+        // The menu bar covers the top 20 units, the square's top edge is under it
+        app_ctx.canvas = sf::FloatRect::new(0., 20., 800., 580.);
+        let p = sf::Vector2f::new;
+        assert_eq!(drag_marquee(&mut app_ctx, p(150., 150.), p(-50., -50.)), 2);
+        assert_eq!(app_ctx.marquee, Some((p(150., 150.), p(0., 20.))));
+    }
+
+    #[test]
+    fn marquee_skips_points_under_floating_windows() {
+        let (mut app_ctx, _) = hovered_square_edge();
+        let p = sf::Vector2f::new;
+        assert_eq!(drag_marquee(&mut app_ctx, p(-10., -10.), p(300., 300.)), 4);
+
+        // A window over the bottom right corner of the square
+        app_ctx.panels.push(sf::FloatRect::new(80., 80., 200., 200.));
+        assert_eq!(drag_marquee(&mut app_ctx, p(-10., -10.), p(300., 300.)), 3);
+    }
+
+    #[test]
+    fn resume_is_cancelled_when_the_polygons_change() {
+        let p = sf::Vector2f::new;
+        let mut app_ctx = AppContext::new(800, 600);
+        for x in [0., 200., 400.] {
+            app_ctx.polygon_objs.push(PolygonObject::from(Polygon::create(vec![
+                p(x, 0.), p(x + 100., 0.), p(x + 100., 100.), p(x, 100.),
+            ])));
+        }
+        let state = ResumePolygonState::new(1, &mut app_ctx);
+        assert!(!state.is_stale(&app_ctx));
+
+        // The panel removes the first polygon, the index 1 now points at the third one
+        app_ctx.polygon_objs.remove(0);
+        assert!(state.is_stale(&app_ctx));
+        let state = Box::new(state).on_left_mouse_clicked(p(450., 300.), &mut app_ctx);
+        assert_eq!(state.state_name(), "Idle State");
+        assert_eq!(app_ctx.polygon_objs[0].polygon().points_count(), 4);
+        assert_eq!(app_ctx.polygon_objs[1].polygon().points_count(), 4);
+    }
+
+    #[test]
+    fn group_rotates_around_the_shared_pivot() {
+        let p = sf::Vector2f::new;
+        let mut app_ctx = AppContext::new(800, 600);
+        // The centers are (10, 10) and (115, 15), the mean of the seven points is elsewhere
+        app_ctx.polygon_objs.push(PolygonObject::from(Polygon::create(vec![p(0., 0.), p(30., 0.), p(0., 30.)])));
+        app_ctx.polygon_objs.push(PolygonObject::from(Polygon::create(vec![
+            p(100., 0.), p(130., 0.), p(130., 30.), p(100., 30.),
+        ])));
+        for poly in app_ctx.polygon_objs.iter_mut() {
+            poly.select_all_points();
+        }
+        app_ctx.group_selected();
+
+        let state = RotatingState::new(p(200., 10.), &mut app_ctx);
+        assert!(my_math::distance(&state.pivot, &p(62.5, 12.5)) < 1e-4);
+    }
+}
@@ -1,9 +1,3 @@
-use std::io::WriterPanicked;
-use std::ops::Deref;
-use std::ptr::NonNull;
-use std::sync::{Mutex, Once, OnceLock, RwLock};
-use sfml::graphics::Texture;
-use sfml::LoadResult;
 use super::sf;
 
 pub const LINE_THICKNESS: f32 = 2.0;
@@ -11,21 +5,140 @@ pub const LINE_DETECTION_DISTANCE: f32 = 10.0;
 pub const POINT_RADIUS: f32 = 5.0;
 pub const LINES_COLOR: sf::Color = sf::Color::rgb(180, 180, 179);
 pub const LINES_COLOR_INCORRECT: sf::Color = sf::Color::rgb(237, 123, 123);
+// The in-progress polygon whose next point cuts off a loop
+pub const LINES_COLOR_SPLIT: sf::Color = sf::Color::rgb(240, 180, 90);
 pub const POLY_EDGE_MIN_LEN: f32 = 5.;
+// Distance from an edge at which a vertex of another polygon is inserted into it
+pub const SHARED_VERTEX_TOLERANCE: f32 = 1.5;
+// Distance from the segment between its neighbors at which a vertex is considered redundant
+pub const COLLINEAR_TOLERANCE: f32 = 0.5;
 pub const POINTS_COLOR: sf::Color = sf::Color::rgb(247, 233, 135);
 pub const POINT_DETECTION_RADIUS: f32 = 10.0;
+// Default distance from the first vertex at which a new polygon snaps closed
+pub const POLY_CLOSE_RADIUS: f32 = POINT_DETECTION_RADIUS;
 pub const POINT_DETECTION_COLOR_CORRECT: sf::Color = sf::Color::rgb(100, 204, 197);
 pub const POINT_DETECTION_COLOR_INCORRECT: sf::Color = sf::Color::rgb(237, 123, 123);
 pub const POINT_SELECTED_COLOR: sf::Color = sf::Color::rgb(167, 187, 236);
+// Distance of the index label from its vertex
+pub const POINT_LABEL_DISTANCE: f32 = 26.0;
 
 pub const BACKGROUND_COLOR: sf::Color = sf::Color::rgb(37, 43, 72);
 
 
 pub const CONSTRAINT_SPRITE_SIZE: sf::Vector2f = sf::Vector2f::new(32., 32.);
+// Fixed length constraints drifting further than that are dropped after a transform
+pub const FIXED_LENGTH_TOLERANCE: f32 = 0.01;
+// In radians
+pub const FIXED_ANGLE_TOLERANCE: f32 = 0.001;
+// Distance from the boundary at which Polygon::contains_point treats a point as lying on it
+pub const POINT_ON_EDGE_TOLERANCE: f32 = 0.001;
+
+// Arc edges are drawn with segments of about that length
+pub const ARC_SEGMENT_LENGTH: f32 = 4.;
+pub const MAX_ARC_SEGMENTS: usize = 64;
+// Bulge of a half circle, the slider doesn't go further
+pub const MAX_BULGE: f32 = 1.;
 
 pub const WIN_SIZE_X: u32 = 1280;
 pub const WIN_SIZE_Y: u32 = 720;
 
 pub const MAX_OFFSET: f32 = 50.;
+// Longest miter of the offset polygon, relative to the offset
+pub const MAX_MITER_RATIO: f32 = 10.;
+// Upper bound of the miter limit of the offset joins
+pub const MAX_MITER_LIMIT: f32 = 50.;
+// Interior angle (or its complement to a full turn) below which a vertex is a spike, in radians
+pub const SPIKE_ANGLE: f32 = 0.09;
+
+// Upper limit of the vertices inserted on an edge at once
+pub const MAX_EDGE_INSERT_COUNT: usize = 100;
+
+// Camera zoom, in world units per pixel
+pub const ZOOM_STEP: f32 = 1.1;
+pub const MIN_ZOOM: f32 = 0.05;
+pub const MAX_ZOOM: f32 = 20.;
+
+pub const SCROLL_OFFSET_STEP: f32 = 1.;
+pub const SCROLL_THICKNESS_STEP: f32 = 0.5;
+
+// Default patterns of the CPU line styles, in world units
+pub const DASH_LENGTH: f32 = 10.;
+pub const DASH_GAP: f32 = 6.;
+pub const DOT_GAP: f32 = 4.;
+
+// Rotation angle increment with Shift held, in degrees
+pub const ROTATION_SNAP_STEP: f32 = 15.;
+
+// Shift of the pasted polygons from the copied ones, in world units
+pub const PASTE_OFFSET: f32 = 20.;
+
+pub const POLY_MIN_AREA: f32 = 100.;
+// Max distance between the matching points of duplicate polygons
+pub const DUPLICATE_EPSILON: f32 = 0.5;
+// Max number of undoable edits
+pub const HISTORY_SIZE: usize = 100;
+pub const TOAST_DURATION: f32 = 3.;
+// Max time (in seconds) and cursor travel (in pixels) between the clicks of a double click
+pub const DOUBLE_CLICK_TIME: f32 = 0.4;
+pub const DOUBLE_CLICK_DISTANCE: i32 = 4;
+pub const THUMBNAIL_SIZE: f32 = 48.;
+
+// Pixels shown around the cursor in each direction and their on-screen size
+pub const LOUPE_RADIUS: u32 = 8;
+pub const LOUPE_CELL_SIZE: f32 = 12.;
+
+pub const GRID_SPACING: f32 = 40.;
+pub const GRID_MIN_SPACING: f32 = 5.;
+pub const GRID_COLOR: sf::Color = sf::Color::rgb(52, 59, 95);
+
+// Unit of the readouts once a real-world scale is calibrated
+pub const DEFAULT_UNIT_NAME: &str = "m";
 
 pub const OFFSET_COLOR: sf::Color = sf::Color::rgb(167, 187, 236);
+// The offset bands fade from OFFSET_COLOR to this one
+pub const OFFSET_BAND_FAR_COLOR: sf::Color = sf::Color::rgb(200, 110, 170);
+pub const MAX_OFFSET_BANDS: usize = 10;
+pub const OFFSET_BAND_SPACING: f32 = 10.;
+pub const COMPARISON_DIVIDER_COLOR: sf::Color = sf::Color::rgb(230, 200, 90);
+pub const GHOST_COLOR: sf::Color = sf::Color::rgb(85, 92, 125);
+pub const FILL_COLOR: sf::Color = sf::Color::rgba(100, 204, 197, 90);
+pub const HATCH_ANGLE: f32 = 45.;
+pub const HATCH_SPACING: f32 = 8.;
+pub const HATCH_MIN_SPACING: f32 = 2.;
+
+pub const PINNED_POINT_COLOR: sf::Color = sf::Color::rgb(240, 170, 60);
+pub const PINNED_POINT_OUTLINE: f32 = 2.0;
+
+// Debug overlay of the vectors computed for every vertex
+pub const DEBUG_VECTOR_LENGTH: f32 = 30.;
+pub const DEBUG_ARROW_HEAD: f32 = 6.;
+pub const NORMAL_COLOR: sf::Color = sf::Color::rgb(120, 230, 120);
+pub const PREV_NORMAL_COLOR: sf::Color = sf::Color::rgb(90, 170, 240);
+pub const OFFSET_VEC_COLOR: sf::Color = sf::Color::rgb(240, 120, 200);
+pub const DIRECTION_COLOR: sf::Color = sf::Color::rgb(240, 220, 90);
+
+pub const MEASUREMENT_COLOR: sf::Color = sf::Color::rgb(240, 220, 90);
+pub const CONTAINMENT_COLOR: sf::Color = sf::Color::rgb(120, 220, 140);
+pub const TRIANGULATION_COLOR: sf::Color = sf::Color::rgb(110, 140, 200);
+
+// Drop shadow of the filled polygons
+pub const SHADOW_COLOR: sf::Color = sf::Color::rgb(10, 12, 25);
+pub const SHADOW_OFFSET: f32 = 8.;
+pub const SHADOW_OPACITY: f32 = 0.5;
+pub const SHADOW_SOFTNESS: f32 = 3.;
+// Copies of the silhouette around the core making up the blur
+pub const SHADOW_LAYERS: usize = 8;
+// Outline of the polygons containing the cursor in the test point mode
+pub const TEST_POINT_INSIDE_COLOR: sf::Color = sf::Color::rgb(250, 160, 60);
+
+pub const MARQUEE_FILL_COLOR: sf::Color = sf::Color::rgba(167, 187, 236, 40);
+pub const MARQUEE_OUTLINE_COLOR: sf::Color = sf::Color::rgb(167, 187, 236);
+pub const MARQUEE_THICKNESS: f32 = 1.0;
+
+pub const SNAP_INDICATOR_COLOR: sf::Color = sf::Color::rgb(120, 230, 120);
+pub const SNAP_INDICATOR_RADIUS: f32 = 7.0;
+pub const SNAP_INDICATOR_THICKNESS: f32 = 2.0;
+pub const SNAP_EDGE_THICKNESS: f32 = 4.0;
+// Diamond marking a snap to an edge midpoint, the half of its diagonal
+pub const SNAP_MIDPOINT_SIZE: f32 = 8.0;
+pub const SNAP_MIDPOINT_COLOR: sf::Color = sf::Color::rgb(240, 150, 220);
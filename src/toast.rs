@@ -0,0 +1,60 @@
+use egui_sfml::egui;
+use super::style;
+
+struct Toast {
+    text: String,
+    time_left: f32,
+}
+
+/// Short-lived notifications shown in the bottom right corner of the window.
+pub struct Toasts {
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn new() -> Toasts {
+        Toasts {
+            toasts: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, text: impl Into<String>) {
+        self.toasts.push(Toast {
+            text: text.into(),
+            time_left: style::TOAST_DURATION,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.toasts.is_empty()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for toast in self.toasts.iter_mut() {
+            toast.time_left -= dt;
+        }
+        self.toasts.retain(|toast| toast.time_left > 0.);
+    }
+
+    pub fn draw_egui(&self, ctx: &egui::Context) {
+        if self.toasts.is_empty() {
+            return;
+        }
+
+        egui::Area::new("Toasts")
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(-10., -10.))
+            .show(ctx, |ui| {
+                for toast in self.toasts.iter() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(&toast.text);
+                    });
+                }
+            });
+    }
+}
+
+impl Default for Toasts {
+    fn default() -> Toasts {
+        Toasts::new()
+    }
+}
@@ -0,0 +1,101 @@
+use super::style;
+
+/// Real-world units of the readouts. The scale is calibrated from a known distance,
+/// without it the lengths and areas are shown in world units (px).
+pub struct Units {
+    pub enabled: bool,
+    pub name: String,
+    // Real units per world unit
+    pub scale: f32,
+}
+
+impl Units {
+    pub fn new() -> Units {
+        Units {
+            enabled: false,
+            name: String::from(style::DEFAULT_UNIT_NAME),
+            scale: 1.,
+        }
+    }
+
+    /// Sets the scale so the world distance measures real_length units.
+    pub fn calibrate(&mut self, world_length: f32, real_length: f32) -> Result<(), String> {
+        if world_length <= 0. || real_length <= 0. {
+            return Err(String::from("Both lengths have to be positive"));
+        }
+        self.scale = real_length / world_length;
+        self.enabled = true;
+        Ok(())
+    }
+
+    fn is_active(&self) -> bool {
+        self.enabled && self.scale > 0.
+    }
+
+    /// Length in world units converted to the calibrated units.
+    pub fn length(&self, world_length: f32) -> f32 {
+        if self.is_active() { world_length * self.scale } else { world_length }
+    }
+
+    /// Area in square world units converted to the calibrated square units.
+    pub fn area(&self, world_area: f32) -> f32 {
+        if self.is_active() { world_area * self.scale * self.scale } else { world_area }
+    }
+
+    pub fn format_length(&self, world_length: f32) -> String {
+        if self.is_active() {
+            format!("{:.2} {}", self.length(world_length), self.name)
+        } else {
+            format!("{:.1} px", world_length)
+        }
+    }
+
+    pub fn format_area(&self, world_area: f32) -> String {
+        if self.is_active() {
+            format!("{:.2} {}²", self.area(world_area), self.name)
+        } else {
+            format!("{:.1} px²", world_area)
+        }
+    }
+}
+
+impl Default for Units {
+    fn default() -> Units {
+        Units::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uncalibrated_units_are_world_units() {
+        let units = Units::new();
+        assert_eq!(units.length(120.), 120.);
+        assert_eq!(units.area(400.), 400.);
+        assert_eq!(units.format_length(120.), "120.0 px");
+    }
+
+    #[test]
+    fn calibrate_scales_lengths_and_areas() {
+        let mut units = Units::new();
+        units.name = String::from("cm");
+        // 200 px measure 5 cm
+        units.calibrate(200., 5.).unwrap();
+        assert!((units.length(400.) - 10.).abs() < 1e-5);
+        // A 100x100 px square is 2.5x2.5 cm
+        assert!((units.area(100. * 100.) - 6.25).abs() < 1e-5);
+        assert_eq!(units.format_length(400.), "10.00 cm");
+        assert_eq!(units.format_area(100. * 100.), "6.25 cm²");
+    }
+
+    #[test]
+    fn calibrate_rejects_non_positive_lengths() {
+        let mut units = Units::new();
+        assert!(units.calibrate(0., 5.).is_err());
+        assert!(units.calibrate(200., -1.).is_err());
+        assert!(!units.enabled);
+        assert_eq!(units.scale, 1.);
+    }
+}